@@ -1,13 +1,14 @@
 use std::convert::TryInto;
 use std::time::Duration;
 
-use stdc_diving_algorithms::deco_algorithm::update_model_state;
-use stdc_diving_algorithms::deco_algorithm::{DecoSettings, MVALUES, TISSUES, calc_deco_schedule};
+use stdc_diving_algorithms::deco_algorithm::{DecoSettings, calc_deco_schedule};
+use stdc_diving_algorithms::deco_model::{DecoModel, THALMANN_XVAL_HE9_040};
 use stdc_diving_algorithms::dive::{DiveMeasurement, DiveProfile};
 use stdc_diving_algorithms::gas::{AIR, TissuesLoading};
-use stdc_diving_algorithms::gas::{GasDensitySettings, MAX_PO2_DECO, TMX10_80};
+use stdc_diving_algorithms::gas::{GasDensitySettings, IcdSettings, NarcosisSettings, MAX_PO2_DECO, TMX10_80};
+use stdc_diving_algorithms::gas_policy::GasSwitchPolicy;
 use stdc_diving_algorithms::pressure_unit::{Pa, Pressure, msw};
-use stdc_diving_algorithms::setup::NUM_STOP_DEPTHS;
+use stdc_diving_algorithms::mptt_thalmann::NUM_STOP_DEPTHS_THALMANN;
 
 fn main() {
     const NUM_GASES: usize = 1;
@@ -67,10 +68,8 @@ fn main() {
         } = &w[1];
         let delta_time = Duration::from_millis((time_ms - time_ms_prev) as u64);
         let midpoint = (*depth + *depth_prev) / 2.0;
-        update_model_state(
+        THALMANN_XVAL_HE9_040.update_model_state(
             &mut loadings,
-            &TISSUES,
-            &MVALUES,
             &profile.gases[*gas],
             midpoint,
             &delta_time,
@@ -79,17 +78,27 @@ fn main() {
 
     let settings = DecoSettings {
         gas_density_settings: GasDensitySettings::Ignore,
+        narcosis_settings: NarcosisSettings::Ignore,
         max_deco_po2: MAX_PO2_DECO.to_pa(),
         surface_pressure: msw::new(0.0).to_pa(),
-        ignore_icd: false,
+        icd_settings: IcdSettings::AnyCounterdiffusion,
         gf_low: 0.50,
         gf_high: 0.85,
         last_deco_stop: msw::new(3.0),
+        ascent_rate_per_meter: Duration::from_secs(6),
+        gas_switch_policy: GasSwitchPolicy::default(),
     };
 
-    let schedule =
-        calc_deco_schedule::<{ NUM_STOP_DEPTHS - 1 }, 1>(&loadings, &gases, &[true], &settings)
-            .expect("schedule");
+    let schedule = calc_deco_schedule::<_, _, { NUM_STOP_DEPTHS_THALMANN - 1 }, 1>(
+        &THALMANN_XVAL_HE9_040,
+        &loadings,
+        profile.measurements[NUM_MEASUREMENTS - 1].depth.to_msw(),
+        0,
+        &gases,
+        &[true],
+        &settings,
+    )
+    .expect("schedule");
 
     println!("first_stop={:?}", schedule.first_stop());
     for s in schedule.stops().iter() {
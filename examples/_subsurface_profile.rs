@@ -1,14 +1,25 @@
+#![allow(
+    clippy::collapsible_if,
+    clippy::erasing_op,
+    clippy::manual_clamp,
+    clippy::needless_range_loop,
+    clippy::ptr_arg
+)]
+
 use std::convert::TryInto;
 use std::time::Duration;
 
-use stdc_diving_algorithms::deco_algorithm::update_model_state;
-use stdc_diving_algorithms::deco_algorithm::{DecoSettings, MVALUES, TISSUES, calc_deco_schedule};
+use stdc_diving_algorithms::deco_algorithm::{DecoSettings, calc_deco_schedule};
+use stdc_diving_algorithms::deco_model::{BUEHLMANN_ZHL16C, DecoModel};
 use stdc_diving_algorithms::dive::{DiveMeasurement, DiveProfile};
 use stdc_diving_algorithms::gas::{
-    AIR, GasDensitySettings, MAX_PO2_DECO, NX50, TMX10_80, TissuesLoading,
+    AIR, GasDensitySettings, IcdSettings, NarcosisSettings, MAX_PO2_DECO, NX50, TMX10_80, TissuesLoading,
 };
+use stdc_diving_algorithms::gas_policy::GasSwitchPolicy;
 use stdc_diving_algorithms::pressure_unit::{Pa, Pressure, msw};
-use stdc_diving_algorithms::setup::NUM_STOP_DEPTHS;
+use stdc_diving_algorithms::mptt_buehlmann::{
+    NUM_STOP_DEPTHS_BUEHLMANN as NUM_STOP_DEPTHS, NUM_TISSUES_BUEHLMANN as NUM_TISSUES,
+};
 
 const SUBSTEP_MS: usize = 10_000;
 
@@ -26,8 +37,7 @@ fn push_segment(
 }
 
 fn update_segment_substepped(
-    loadings: &mut TissuesLoading<{ stdc_diving_algorithms::setup::NUM_TISSUES }, Pa>,
-    mvalues: &stdc_diving_algorithms::deco_algorithm::MValues<Pa>,
+    loadings: &mut TissuesLoading<NUM_TISSUES, Pa>,
     gas: &stdc_diving_algorithms::gas::GasMix<f32>,
     prev_depth: Pa,
     next_depth: Pa,
@@ -45,10 +55,8 @@ fn update_segment_substepped(
         let end_ratio = (elapsed_ms + chunk_ms) as f32 / total_ms as f32;
         let mid_ratio = (start_ratio + end_ratio) * 0.5;
         let interpolated_depth = prev_depth + (next_depth - prev_depth) * mid_ratio;
-        update_model_state(
+        BUEHLMANN_ZHL16C.update_model_state(
             loadings,
-            &TISSUES,
-            mvalues,
             gas,
             interpolated_depth,
             &Duration::from_millis(chunk_ms as u64),
@@ -120,12 +128,15 @@ fn main() {
     let mut loadings = TissuesLoading::new(msw::new(0.0).to_pa(), &TMX10_80);
     let settings = DecoSettings {
         gas_density_settings: GasDensitySettings::Ignore,
+        narcosis_settings: NarcosisSettings::Ignore,
         max_deco_po2: MAX_PO2_DECO.to_pa(),
         surface_pressure: msw::new(0.0).to_pa(),
-        ignore_icd: true,
+        icd_settings: IcdSettings::Ignore,
         gf_low: 0.50,
         gf_high: 0.85,
         last_deco_stop: msw::new(6.0),
+        ascent_rate_per_meter: Duration::from_secs(6),
+        gas_switch_policy: GasSwitchPolicy::default(),
     };
     for window in profile.measurements.windows(2) {
         let prev = window[0];
@@ -152,9 +163,15 @@ fn main() {
         // would ascend above the current deco ceiling (first-stop). We derive
         // the ceiling by building a short schedule from the current loading
         // and inspecting its first stop.
-        if let Ok(schedule) =
-            calc_deco_schedule::<{ NUM_STOP_DEPTHS - 1 }, 3>(&loadings, &gases, &enabled, &settings)
-        {
+        if let Ok(schedule) = calc_deco_schedule::<_, _, { NUM_STOP_DEPTHS - 1 }, 3>(
+            &BUEHLMANN_ZHL16C,
+            &loadings,
+            prev.depth.to_msw(),
+            prev.gas,
+            &gases,
+            &enabled,
+            &settings,
+        ) {
             if let Some(first) = schedule.first_stop() {
                 let first_stop = first.depth();
                 if next.depth.to_msw().to_f32() < first_stop.to_msw().to_f32() {
@@ -166,8 +183,6 @@ fn main() {
                     // both last_deco_stop = 6m and last_deco_stop = 3m using the
                     // current loading snapshot so we can compare behavior.
                     {
-                        use stdc_diving_algorithms::deco_algorithm::compute_stop_time;
-                        use stdc_diving_algorithms::deco_algorithm::TISSUES as BUEHL_TISSUES;
                         fn interp_gf(initial_first_stop: msw, stop_depth: msw, low: f32, high: f32) -> f32 {
                             if initial_first_stop.to_msw().to_f32() <= 0.0 {
                                 high
@@ -183,17 +198,17 @@ fn main() {
                         let gf3 = interp_gf(initial, msw::new(3.0), settings.gf_low, settings.gf_high);
                         // best mixes for depths
                         use stdc_diving_algorithms::gas::best_available_mix;
-                        let mix6 = best_available_mix(settings.max_deco_po2, msw::new(6.0).to_pa().into(), &gases, &enabled, &loadings, settings.ignore_icd, &settings.gas_density_settings);
-                        let mix3 = best_available_mix(settings.max_deco_po2, msw::new(3.0).to_pa().into(), &gases, &enabled, &loadings, settings.ignore_icd, &settings.gas_density_settings);
+                        let mix6 = best_available_mix(settings.max_deco_po2, msw::new(6.0).to_pa(), &gases, &enabled, None, &loadings, &settings.icd_settings, &settings.gas_density_settings, &settings.narcosis_settings);
+                        let mix3 = best_available_mix(settings.max_deco_po2, msw::new(3.0).to_pa(), &gases, &enabled, None, &loadings, &settings.icd_settings, &settings.gas_density_settings, &settings.narcosis_settings);
                         println!("Diagnostic GF: gf6={:.3} gf3={:.3}", gf6, gf3);
                             if let Some((_i, g6)) = mix6 {
-                            let d6_as_final = compute_stop_time(&loadings, &BUEHL_TISSUES, g6, &MVALUES, msw::new(6.0), gf6, msw::new(0.0).to_pa(), msw::new(6.0));
-                            let d6_with_3floor = compute_stop_time(&loadings, &BUEHL_TISSUES, g6, &MVALUES, msw::new(6.0), gf6, msw::new(0.0).to_pa(), msw::new(3.0));
+                            let d6_as_final = BUEHLMANN_ZHL16C.compute_stop_time(&loadings, g6, msw::new(6.0), gf6, msw::new(0.0).to_pa(), msw::new(6.0)).unwrap();
+                            let d6_with_3floor = BUEHLMANN_ZHL16C.compute_stop_time(&loadings, g6, msw::new(6.0), gf6, msw::new(0.0).to_pa(), msw::new(3.0)).unwrap();
                             println!("compute_stop_time 6m final(6m floor) = {:.1}s", d6_as_final.as_secs_f32());
                             println!("compute_stop_time 6m non-final(3m floor) = {:.1}s", d6_with_3floor.as_secs_f32());
                         }
                         if let Some((_i, g3)) = mix3 {
-                            let d3_final = compute_stop_time(&loadings, &BUEHL_TISSUES, g3, &MVALUES, msw::new(3.0), gf3, msw::new(0.0).to_pa(), msw::new(3.0));
+                            let d3_final = BUEHLMANN_ZHL16C.compute_stop_time(&loadings, g3, msw::new(3.0), gf3, msw::new(0.0).to_pa(), msw::new(3.0)).unwrap();
                             println!("compute_stop_time 3m final(3m floor) = {:.1}s", d3_final.as_secs_f32());
                         }
                     }
@@ -210,12 +225,10 @@ fn main() {
                                 s.duration().as_secs_f32(),
                                 gas.fo2()
                             );
-                            update_model_state(
+                            BUEHLMANN_ZHL16C.update_model_state(
                                 &mut loadings,
-                                &TISSUES,
-                                &MVALUES,
                                 &gas,
-                                stop_depth.to_pa().into(),
+                                stop_depth.to_pa(),
                                 &s.duration(),
                             );
                         } else {
@@ -225,12 +238,10 @@ fn main() {
                                 stop_depth.to_msw().to_f32(),
                                 s.duration().as_secs_f32()
                             );
-                            update_model_state(
+                            BUEHLMANN_ZHL16C.update_model_state(
                                 &mut loadings,
-                                &TISSUES,
-                                &MVALUES,
                                 &gases[0],
-                                stop_depth.to_pa().into(),
+                                stop_depth.to_pa(),
                                 &s.duration(),
                             );
                         }
@@ -241,7 +252,6 @@ fn main() {
 
         update_segment_substepped(
             &mut loadings,
-            &MVALUES,
             gas,
             prev.depth,
             next.depth,
@@ -256,33 +266,32 @@ fn main() {
     //     gas_density_settings: GasDensitySettings::Ignore,
     //     max_deco_po2: MAX_PO2_DECO.to_pa(),
     //     surface_pressure: msw::new(0.0).to_pa(),
-    //     ignore_icd: true,
+    //     icd_settings: IcdSettings::Ignore,
     //     gf_low: 0.50,
     //     gf_high: 0.85,
     //     last_deco_stop: msw::new(6.0),
     // };
 
     // Diagnostic: replicate first_stop_depth_with_gf logic here to see why no stop
-    #[cfg(not(feature = "lin_exp"))]
     {
-        use stdc_diving_algorithms::deco_algorithm::TISSUES as BUEHL_TISSUES;
+        let buehl_tissues = BUEHLMANN_ZHL16C.tissues();
         println!("Running standalone first-stop detection (Buehlmann)");
         let mut found: Option<msw> = None;
-        for mvalues_at_depth in MVALUES.iter().rev() {
+        for mvalues_at_depth in BUEHLMANN_ZHL16C.m_values().iter().rev() {
             let depth = mvalues_at_depth.depth;
             let mut any = false;
             // scan tissues but don't print each depth
-            for i in 0..(stdc_diving_algorithms::setup::NUM_TISSUES) {
-                let p_n2 = loadings.n2[i].to_pa();
-                let p_he = loadings.he[i].to_pa();
+            for i in 0..NUM_TISSUES {
+                let p_n2 = loadings.n2()[i].to_pa();
+                let p_he = loadings.he()[i].to_pa();
                 let total = p_n2 + p_he;
                 if total.to_f32() <= 0.0 {
                     continue;
                 }
-                let a_n2 = BUEHL_TISSUES[i].n2.a.to_pa();
-                let a_he = BUEHL_TISSUES[i].he.a.to_pa();
-                let b_n2 = BUEHL_TISSUES[i].n2.b;
-                let b_he = BUEHL_TISSUES[i].he.b;
+                let a_n2 = buehl_tissues[i].n2.a.to_pa();
+                let a_he = buehl_tissues[i].he.a.to_pa();
+                let b_n2 = buehl_tissues[i].n2.b;
+                let b_he = buehl_tissues[i].he.b;
                 let n2_frac = p_n2 / total;
                 let he_frac = p_he / total;
                 let a_mix = a_n2 * n2_frac + a_he * he_frac;
@@ -307,29 +316,28 @@ fn main() {
         }
         println!("diagnostic first stop: {:?}", found);
         if let Some(depth) = found {
-            use stdc_diving_algorithms::deco_algorithm::compute_stop_time;
             use stdc_diving_algorithms::gas::best_available_mix;
             let mix = best_available_mix(
                 settings.max_deco_po2,
-                depth.to_pa().into(),
+                depth.to_pa(),
                 &gases,
                 &enabled,
+                None,
                 &loadings,
-                settings.ignore_icd,
+                &settings.icd_settings,
                 &settings.gas_density_settings,
+                &settings.narcosis_settings,
             );
             println!("best mix at {:?} => {:?}", depth, mix);
             if let Some((_idx, gas)) = mix {
-                let dur =
-                    compute_stop_time(
-                        &loadings,
-                        &TISSUES,
-                        gas,
-                        &MVALUES,
-                        depth,
-                        settings.gf_low,
-                        settings.last_deco_stop,
-                    );
+                let dur = BUEHLMANN_ZHL16C.compute_stop_time(
+                    &loadings,
+                    gas,
+                    depth,
+                    settings.gf_low,
+                    settings.surface_pressure,
+                    settings.last_deco_stop,
+                ).unwrap();
                 println!(
                     "computed stop duration at {:?} = {:.3}s",
                     depth,
@@ -342,9 +350,16 @@ fn main() {
 
     // Build schedule and then print per-tissue comparisons at initial first stop
 
-    let schedule =
-        calc_deco_schedule::<{ NUM_STOP_DEPTHS - 1 }, 3>(&loadings, &gases, &enabled, &settings)
-            .expect("schedule");
+    let schedule = calc_deco_schedule::<_, _, { NUM_STOP_DEPTHS - 1 }, 3>(
+        &BUEHLMANN_ZHL16C,
+        &loadings,
+        profile.measurements[23].depth.to_msw(),
+        profile.measurements[23].gas,
+        &gases,
+        &enabled,
+        &settings,
+    )
+    .expect("schedule");
     println!("first_stop={:?}", schedule.first_stop());
     if let Some(initial_first) = schedule.first_stop() {
         println!("initial_first_stop={:?}", initial_first);
@@ -368,10 +383,8 @@ fn main() {
             continue;
         }
         if let Some(gas) = s.gas() {
-            update_model_state(
+            BUEHLMANN_ZHL16C.update_model_state(
                 &mut deco_loadings,
-                &TISSUES,
-                &MVALUES,
                 &gas,
                 s.depth().to_pa(),
                 &s.duration(),
@@ -1,18 +1,21 @@
+#![allow(clippy::implicit_saturating_sub, clippy::useless_format)]
+
 /// Note: This example is AI-Generated by MS Copilot
 use std::fs::File;
 use std::io::Write;
 use std::time::Duration;
 
-use stdc_diving_algorithms::deco_algorithm::{
-    DecoSettings, MVALUES, TISSUES, calc_deco_schedule, update_model_state,
-};
+use stdc_diving_algorithms::deco_algorithm::{DecoSettings, calc_deco_schedule};
+use stdc_diving_algorithms::deco_model::{DecoModel, THALMANN_XVAL_HE9_040};
 use stdc_diving_algorithms::dive::{DiveMeasurement, DiveProfile};
 use stdc_diving_algorithms::gas::best_available_mix;
 use stdc_diving_algorithms::gas::{
-    GasDensitySettings, MAX_PO2_DECO, NX50, NX100, TMX18_45, TissuesLoading,
+    GasDensitySettings, IcdSettings, NarcosisSettings, MAX_PO2_DECO, NX50, NX100, TMX18_45, TissuesLoading,
 };
+use stdc_diving_algorithms::gas_policy::GasSwitchPolicy;
 use stdc_diving_algorithms::pressure_unit::{Pa, Pressure, msw};
-use stdc_diving_algorithms::setup::{DINC, NUM_STOP_DEPTHS};
+use stdc_diving_algorithms::mptt_thalmann::NUM_STOP_DEPTHS_THALMANN;
+use stdc_diving_algorithms::setup::DINC;
 
 fn main() {
     // Build gases array: TMX18/45 (0), NX50 (1), NX100 (2)
@@ -124,29 +127,28 @@ fn main() {
         let delta_time = Duration::from_millis((next.time_ms - prev.time_ms) as u64);
         let midpoint = (prev.depth + next.depth) / 2.0;
         let gas = &profile.gases[next.gas];
-        update_model_state(
-            &mut loadings,
-            &TISSUES,
-            &MVALUES,
-            gas,
-            midpoint,
-            &delta_time,
-        );
+        THALMANN_XVAL_HE9_040.update_model_state(&mut loadings, gas, midpoint, &delta_time);
     }
 
     // Compute deco schedule
     let deco_settings = DecoSettings {
         gas_density_settings: GasDensitySettings::Ignore,
+        narcosis_settings: NarcosisSettings::Ignore,
         max_deco_po2: MAX_PO2_DECO.to_pa(),
         surface_pressure: msw::new(0.0).to_pa(),
-        ignore_icd: false,
+        icd_settings: IcdSettings::AnyCounterdiffusion,
         gf_low: 0.50,
         gf_high: 0.85,
         last_deco_stop: msw::new(6.0),
+        ascent_rate_per_meter: Duration::from_secs(6),
+        gas_switch_policy: GasSwitchPolicy::default(),
     };
 
-    let stops = match calc_deco_schedule::<{ NUM_STOP_DEPTHS - 1 }, 3>(
+    let stops = match calc_deco_schedule::<_, _, { NUM_STOP_DEPTHS_THALMANN - 1 }, 3>(
+        &THALMANN_XVAL_HE9_040,
         &loadings,
+        msw::new(bottom),
+        0,
         &gases,
         &gases_enabled,
         &deco_settings,
@@ -171,12 +173,14 @@ fn main() {
         println!("first stop candidate: {:.1}m", fs.to_msw().to_f32());
         let mix = best_available_mix(
             stdc_diving_algorithms::gas::MAX_PO2_DECO.to_pa(),
-            fs.to_pa().into(),
+            fs.to_pa(),
             &gases,
             &gases_enabled,
+            None,
             &loadings,
-            false,
+            &IcdSettings::AnyCounterdiffusion,
             &GasDensitySettings::Ignore,
+            &NarcosisSettings::Ignore,
         );
         println!("best_available_mix at first stop: {:?}", mix);
     } else {
@@ -0,0 +1,396 @@
+use core::time::Duration;
+
+#[allow(unused)]
+use num::Float;
+
+use crate::{
+    deco_algorithm::{
+        CCRSettings, DecoSettings, calc_ccr_deco_schedule, calc_deco_schedule, ccr_diluent,
+    },
+    deco_model::DecoModel,
+    dive::StopSchedule,
+    gas::{GasMix, TissuesLoading},
+    gas_plan::ascent_gas_l,
+    pressure_unit::{Pa, Pressure, ambient_pressure_at_depth, msw},
+};
+
+/** Open-circuit breathing assumed for a bailout ascent. */
+#[derive(Debug, Clone, Copy)]
+pub struct BailoutSettings {
+    /// Respiratory minute volume at the surface, in l/min.
+    pub rmv_l_min: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct BailoutPlan<const NUM_STOPS: usize, const NUM_GASES: usize> {
+    /// Depth at which the diver leaves the loop.
+    pub depth: msw,
+    pub schedule: StopSchedule<NUM_STOPS>,
+    /// Surface litres of each bailout gas, ascent legs included.
+    pub gas_required_l: [f32; NUM_GASES],
+    pub time_to_surface: Duration,
+}
+
+impl<const NUM_STOPS: usize, const NUM_GASES: usize> BailoutPlan<NUM_STOPS, NUM_GASES> {
+    pub fn total_gas_l(&self) -> f32 {
+        self.gas_required_l.iter().sum()
+    }
+}
+
+/// Constant-depth leg of a CCR plan. Depth changes between levels are instantaneous.
+#[derive(Debug, Clone, Copy)]
+pub struct Level {
+    pub depth: msw,
+    pub duration: Duration,
+}
+
+/** Levels, setpoints and diluents of a closed-circuit dive. */
+pub struct CCRPlan<'a, const NUM_DILUENTS: usize> {
+    pub levels: &'a [Level],
+    pub ccr: CCRSettings<Pa>,
+    pub diluents: [GasMix<f32>; NUM_DILUENTS],
+    pub diluents_enabled: [bool; NUM_DILUENTS],
+}
+
+#[derive(Debug, Clone)]
+pub struct WorstCaseBailout<const NUM_STOPS: usize, const NUM_GASES: usize> {
+    /// Runtime of the CCR plan at which the worst bailout starts.
+    pub runtime: Duration,
+    pub plan: BailoutPlan<NUM_STOPS, NUM_GASES>,
+    /// Most of each gas needed by any bailout point, i.e. what has to be carried.
+    pub gas_required_l: [f32; NUM_GASES],
+}
+
+/**
+* Open-circuit ascent from `depth` with the tissue state `loading`. The
+* bottom gas is the one `gas_switch_policy` picks at `depth`; the stops
+* are those of `calc_deco_schedule` on the bailout gases.
+*/
+pub fn calc_bailout_plan<
+    M,
+    const NUM_TISSUES: usize,
+    const NUM_STOPS: usize,
+    const NUM_GASES: usize,
+>(
+    model: &M,
+    loading: &TissuesLoading<NUM_TISSUES, Pa>,
+    depth: msw,
+    gases: &[GasMix<f32>; NUM_GASES],
+    gases_enabled: &[bool; NUM_GASES],
+    deco_settings: &DecoSettings<Pa>,
+    bailout: &BailoutSettings,
+) -> Result<BailoutPlan<NUM_STOPS, NUM_GASES>, &'static str>
+where
+    M: DecoModel<NUM_TISSUES, Pa>,
+{
+    let surface_pressure = deco_settings.surface_pressure;
+    let bottom_gas_idx = deco_settings
+        .gas_switch_policy
+        .select(
+            None,
+            depth,
+            model.stop_increment(),
+            deco_settings.max_deco_po2,
+            gases,
+            gases_enabled,
+            loading,
+            deco_settings,
+        )
+    .ok_or("No bailout gas for depth.")?;
+    let schedule: StopSchedule<NUM_STOPS> = calc_deco_schedule(
+        model,
+        loading,
+        depth,
+        bottom_gas_idx,
+        gases,
+        gases_enabled,
+        deco_settings,
+    )?;
+
+    let (gas_required_l, time_to_surface) = ascent_gas_l(
+        depth,
+        bottom_gas_idx,
+        &schedule,
+        gases,
+        bailout.rmv_l_min,
+        &deco_settings.ascent_rate_per_meter,
+        surface_pressure,
+    )?;
+
+    Ok(BailoutPlan {
+        depth,
+        schedule,
+        gas_required_l,
+        time_to_surface,
+    })
+}
+
+/**
+* Bails out at the end of every level of `plan` and at the start of every
+* stop of its CCR ascent, and returns the point needing the most gas. Travel
+* between CCR stops is loaded at `ascent_rate_per_meter` of `deco_settings`,
+* as in the schedule builder.
+*/
+#[allow(clippy::too_many_arguments)]
+pub fn worst_case_bailout<
+    M,
+    const NUM_TISSUES: usize,
+    const NUM_STOPS: usize,
+    const NUM_DILUENTS: usize,
+    const NUM_GASES: usize,
+>(
+    model: &M,
+    loading: &TissuesLoading<NUM_TISSUES, Pa>,
+    plan: &CCRPlan<NUM_DILUENTS>,
+    gases: &[GasMix<f32>; NUM_GASES],
+    gases_enabled: &[bool; NUM_GASES],
+    deco_settings: &DecoSettings<Pa>,
+    bailout: &BailoutSettings,
+) -> Result<WorstCaseBailout<NUM_STOPS, NUM_GASES>, &'static str>
+where
+    M: DecoModel<NUM_TISSUES, Pa>,
+{
+    let surface_pressure = deco_settings.surface_pressure;
+    let mut loading = loading.clone();
+    let mut runtime = Duration::ZERO;
+    let mut worst: Option<WorstCaseBailout<NUM_STOPS, NUM_GASES>> = None;
+    let mut bail_out_at = |runtime: Duration,
+                           depth: msw,
+                           loading: &TissuesLoading<NUM_TISSUES, Pa>|
+     -> Result<(), &'static str> {
+        let candidate: BailoutPlan<NUM_STOPS, NUM_GASES> = calc_bailout_plan(
+            model,
+            loading,
+            depth,
+            gases,
+            gases_enabled,
+            deco_settings,
+            bailout,
+        )?;
+        let mut gas_required_l = candidate.gas_required_l;
+        if let Some(w) = &worst {
+            for (required, so_far) in gas_required_l.iter_mut().zip(w.gas_required_l) {
+                *required = required.max(so_far);
+            }
+            if candidate.total_gas_l() <= w.plan.total_gas_l() {
+                worst = Some(WorstCaseBailout {
+                    gas_required_l,
+                    ..w.clone()
+                });
+                return Ok(());
+            }
+        }
+        worst = Some(WorstCaseBailout {
+            runtime,
+            plan: candidate,
+            gas_required_l,
+        });
+        Ok(())
+    };
+
+    let mut diluent = None;
+    for level in plan.levels {
+        let idx = ccr_diluent(
+            level.depth,
+            model.stop_increment(),
+            &loading,
+            &plan.diluents,
+            &plan.diluents_enabled,
+            diluent,
+            deco_settings,
+        )?;
+        model.update_model_state_loop(
+            &mut loading,
+            &plan.ccr.loop_gas(&plan.diluents[idx], level.depth, false),
+            ambient_pressure_at_depth(surface_pressure, level.depth),
+            &level.duration,
+        );
+        diluent = Some(idx);
+        runtime += level.duration;
+        bail_out_at(runtime, level.depth, &loading)?;
+    }
+
+    let mut diluent = diluent.ok_or("A CCR plan needs at least one level.")?;
+    let mut depth = plan
+        .levels
+        .last()
+        .ok_or("A CCR plan needs at least one level.")?
+        .depth;
+    let ccr_schedule: StopSchedule<NUM_STOPS> = calc_ccr_deco_schedule(
+        model,
+        &loading,
+        depth,
+        diluent,
+        &plan.ccr,
+        &plan.diluents,
+        &plan.diluents_enabled,
+        deco_settings,
+    )?;
+    for stop in ccr_schedule
+        .stops()
+        .iter()
+        .filter(|s| !s.duration().is_zero())
+    {
+        let travel = deco_settings
+            .ascent_rate_per_meter
+            .mul_f32((depth.to_f32() - stop.depth().to_f32()).max(0.0));
+        model.update_model_state_travel(
+            &mut loading,
+            &plan.ccr.loop_gas(&plan.diluents[diluent], depth, true),
+            ambient_pressure_at_depth(surface_pressure, depth),
+            ambient_pressure_at_depth(surface_pressure, stop.depth()),
+            &travel,
+        );
+        runtime += travel;
+        bail_out_at(runtime, stop.depth(), &loading)?;
+        diluent = ccr_diluent(
+            stop.depth(),
+            model.stop_increment(),
+            &loading,
+            &plan.diluents,
+            &plan.diluents_enabled,
+            Some(diluent),
+            deco_settings,
+        )?;
+        let gas = stop.gas().ok_or("CCR stop without a loop gas.")?;
+        model.update_model_state(
+            &mut loading,
+            &gas,
+            ambient_pressure_at_depth(surface_pressure, stop.depth()),
+            &stop.duration(),
+        );
+        runtime += stop.duration();
+        depth = stop.depth();
+    }
+
+    worst.ok_or("A CCR plan needs at least one level.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        deco_algorithm::ccr_breathing_gas,
+        deco_model::BUEHLMANN_ZHL16C,
+        gas::{
+            AIR, GasDensitySettings, IcdSettings, MAX_PO2_DECO, NX50, NX100, NarcosisSettings,
+            TMX15_55, TMX18_45,
+        },
+        gas_policy::GasSwitchPolicy,
+        pressure_unit::Bar,
+    };
+
+    const BAILOUT: BailoutSettings = BailoutSettings { rmv_l_min: 20.0 };
+
+    fn deco_settings() -> DecoSettings<'static, Pa> {
+        DecoSettings {
+            gas_density_settings: GasDensitySettings::Ignore,
+            narcosis_settings: NarcosisSettings::Ignore,
+            max_deco_po2: MAX_PO2_DECO.to_pa(),
+            surface_pressure: msw::new(0.0).to_pa(),
+            icd_settings: IcdSettings::Ignore,
+            gf_low: 0.5,
+            gf_high: 0.85,
+            last_deco_stop: msw::new(3.0),
+            ascent_rate_per_meter: Duration::from_secs(6),
+            gas_switch_policy: GasSwitchPolicy::default(),
+        }
+    }
+
+    fn ccr_plan(levels: &[Level]) -> CCRPlan<'_, 1> {
+        CCRPlan {
+            levels,
+            ccr: CCRSettings {
+                low_set_point: Bar::new(0.7).to_pa(),
+                high_set_point: Bar::new(1.3).to_pa(),
+                switch_up_depth: msw::new(10.0),
+                switch_down_depth: msw::new(0.0),
+            },
+            diluents: [TMX15_55],
+            diluents_enabled: [true],
+        }
+    }
+
+    #[test]
+    fn bailout_gas_covers_every_stop_gas() {
+        let levels = [Level {
+            depth: msw::new(55.0),
+            duration: Duration::from_secs(25 * 60),
+        }];
+        let plan = ccr_plan(&levels);
+        let surface = msw::new(0.0).to_pa();
+        let mut loading = TissuesLoading::new(surface, &AIR);
+        let gas = ccr_breathing_gas(
+            msw::new(55.0),
+            BUEHLMANN_ZHL16C.stop_increment(),
+            &loading,
+            &plan.ccr,
+            &plan.diluents,
+            &plan.diluents_enabled,
+            None,
+            &deco_settings(),
+            false,
+        )
+        .expect("diluent at 55 m");
+        BUEHLMANN_ZHL16C.update_model_state(
+            &mut loading,
+            &gas,
+            msw::new(55.0).to_pa(),
+            &levels[0].duration,
+        );
+
+        let bailout: BailoutPlan<30, 3> = calc_bailout_plan(
+            &BUEHLMANN_ZHL16C,
+            &loading,
+            msw::new(55.0),
+            &[TMX18_45, NX50, NX100],
+            &[true; 3],
+            &deco_settings(),
+            &BAILOUT,
+        )
+        .expect("bailout plan");
+        std::println!("Bailout from 55 m: {:?}", bailout.gas_required_l);
+        assert!(bailout.gas_required_l.iter().all(|l| *l > 0.0));
+        let stops: Duration = bailout.schedule.stops().iter().map(|s| s.duration()).sum();
+        assert!(bailout.time_to_surface > stops);
+    }
+
+    #[test]
+    fn worst_case_bailout_is_at_the_end_of_the_bottom() {
+        let levels = [
+            Level {
+                depth: msw::new(40.0),
+                duration: Duration::from_secs(10 * 60),
+            },
+            Level {
+                depth: msw::new(55.0),
+                duration: Duration::from_secs(20 * 60),
+            },
+            Level {
+                depth: msw::new(30.0),
+                duration: Duration::from_secs(10 * 60),
+            },
+        ];
+        let worst: WorstCaseBailout<30, 3> = worst_case_bailout(
+            &BUEHLMANN_ZHL16C,
+            &TissuesLoading::new(msw::new(0.0).to_pa(), &AIR),
+            &ccr_plan(&levels),
+            &[TMX18_45, NX50, NX100],
+            &[true; 3],
+            &deco_settings(),
+            &BAILOUT,
+        )
+        .expect("worst case bailout");
+        std::println!(
+            "Worst bailout at {:?} from {} msw: {:?}",
+            worst.runtime,
+            worst.plan.depth.to_f32(),
+            worst.gas_required_l
+        );
+        assert_eq!(worst.plan.depth, msw::new(55.0));
+        assert_eq!(worst.runtime, Duration::from_secs(30 * 60));
+        for (carried, needed) in worst.gas_required_l.iter().zip(worst.plan.gas_required_l) {
+            assert!(*carried >= needed);
+        }
+    }
+}
@@ -0,0 +1,216 @@
+#[allow(unused)]
+use num::Float;
+
+use crate::{
+    gas::{
+        AIR, DENSITY_HE, DENSITY_N2, DENSITY_O2, Gas, GasDensitySettings, GasMix, NX50, NX100,
+        NarcosisSettings, TMX10_80, TMX12_65, TMX15_55, TMX18_45, TMX21_35,
+    },
+    pressure_unit::{Bar, Pa, Pressure, ambient_pressure_at_depth, msw},
+    setup::DINC,
+};
+
+/// Bottom mixes a recommendation snaps to when one is close enough.
+pub const STANDARD_BOTTOM_MIXES: [GasMix<f32>; 6] =
+    [AIR, TMX21_35, TMX18_45, TMX15_55, TMX12_65, TMX10_80];
+/// Deco gases considered by `recommend_deco_gases`, deepest first.
+pub const STANDARD_DECO_GASES: [GasMix<f32>; 3] = [TMX21_35, NX50, NX100];
+
+/// Largest FO2 shortfall and He excess for which a standard mix replaces the optimum.
+const SNAP_MAX_FO2_BELOW: f32 = 0.03;
+const SNAP_MAX_FHE_ABOVE: f32 = 0.10;
+/// Fractions are rounded to what a blender can fill.
+const FRACTION_STEP: f32 = 0.01;
+/// Float noise, in steps, ignored when rounding He up.
+const ROUNDING_SLACK: f32 = 1e-3;
+
+/** Limits a recommended mix has to meet at its depth. */
+pub struct MixLimits {
+    pub max_po2: Bar,
+    /// Hypoxic floor: lowest ppO2 the mix may give at its depth.
+    pub min_po2: Bar,
+    pub narcosis: NarcosisSettings,
+    pub gas_density: GasDensitySettings,
+}
+
+impl MixLimits {
+    /// Whether `gas` meets all limits at the ambient pressure `ambient`.
+    pub fn allow(&self, gas: &GasMix<f32>, ambient: Pa) -> bool {
+        let po2 = gas.po2(ambient).to_bar();
+        po2 <= self.max_po2
+            && po2 >= self.min_po2
+            && self.narcosis.no_violation(ambient, gas)
+            && self.gas_density.no_violation(ambient, gas)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecoGasRecommendation {
+    pub gas: GasMix<f32>,
+    /// Deepest stop depth at which the gas meets the limits.
+    pub switch_depth: msw,
+}
+
+/**
+* Richest mix meeting `limits` at `depth` with the least He: FO2 from the
+* ppO2 limit, then as much N2 as the END and density limits leave room for.
+* FO2 is rounded down and FHe up to whole percent.
+*/
+pub fn best_mix(
+    depth: msw,
+    surface_pressure: Pa,
+    limits: &MixLimits,
+) -> Result<GasMix<f32>, &'static str> {
+    let ambient = ambient_pressure_at_depth(surface_pressure, depth)
+        .to_bar()
+        .to_f32();
+    let fo2 =
+        ((limits.max_po2.to_f32() / ambient).min(1.0) / FRACTION_STEP).floor() * FRACTION_STEP;
+    if fo2 * ambient < limits.min_po2.to_f32() {
+        return Err("No FO2 meets both ppO2 limits at this depth.");
+    }
+    let mut max_fn2 = 1.0 - fo2;
+    if let NarcosisSettings::Limit {
+        max_end,
+        o2_narcotic,
+    } = limits.narcosis
+    {
+        let end = max_end.to_bar().to_f32() / ambient;
+        max_fn2 = max_fn2.min(if o2_narcotic {
+            end * (AIR.fn2() + AIR.fo2()) - fo2
+        } else {
+            end * AIR.fn2()
+        });
+    }
+    if let GasDensitySettings::Limit { limit_g_l } = limits.gas_density {
+        let o2_and_he = fo2 * DENSITY_O2 + (1.0 - fo2) * DENSITY_HE;
+        max_fn2 =
+            max_fn2.min((limit_g_l.to_f32() / ambient - o2_and_he) / (DENSITY_N2 - DENSITY_HE));
+    }
+    if max_fn2 < 0.0 {
+        return Err("END or density limit cannot be met at this depth.");
+    }
+    let fhe = ((1.0 - fo2 - max_fn2) / FRACTION_STEP - ROUNDING_SLACK).ceil() * FRACTION_STEP;
+    GasMix::new(fo2, fhe.clamp(0.0, 1.0 - fo2))
+}
+
+/**
+* `best_mix`, replaced by the closest of `STANDARD_BOTTOM_MIXES` that meets the
+* limits with at most `SNAP_MAX_FO2_BELOW` less O2 and `SNAP_MAX_FHE_ABOVE` more He.
+*/
+pub fn recommend_bottom_mix(
+    depth: msw,
+    surface_pressure: Pa,
+    limits: &MixLimits,
+) -> Result<GasMix<f32>, &'static str> {
+    let optimum = best_mix(depth, surface_pressure, limits)?;
+    let ambient = ambient_pressure_at_depth(surface_pressure, depth);
+    let distance = |g: &GasMix<f32>| (optimum.fo2() - g.fo2()) + (g.fhe() - optimum.fhe());
+    let snapped = STANDARD_BOTTOM_MIXES
+        .iter()
+        .filter(|g| {
+            let fo2_below = optimum.fo2() - g.fo2();
+            let fhe_above = g.fhe() - optimum.fhe();
+            (-FRACTION_STEP..=SNAP_MAX_FO2_BELOW).contains(&fo2_below)
+                && (-FRACTION_STEP..=SNAP_MAX_FHE_ABOVE).contains(&fhe_above)
+                && limits.allow(g, ambient)
+        })
+        .min_by(|a, b| distance(a).total_cmp(&distance(b)));
+    Ok(snapped.copied().unwrap_or(optimum))
+}
+
+/**
+* Deco gases from `candidates` that are richer than `bottom_gas`, each with the
+* deepest stop shallower than `bottom_depth` at which it meets `limits`
+* (usually with `max_po2` set to `MAX_PO2_DECO`).
+*/
+pub fn recommend_deco_gases<const NUM_CANDIDATES: usize>(
+    bottom_gas: &GasMix<f32>,
+    bottom_depth: msw,
+    candidates: &[GasMix<f32>; NUM_CANDIDATES],
+    surface_pressure: Pa,
+    limits: &MixLimits,
+) -> [Option<DecoGasRecommendation>; NUM_CANDIDATES] {
+    let deepest_stop = ((bottom_depth.to_f32() / DINC.to_f32()).ceil() as usize).saturating_sub(1);
+    candidates.map(|gas| {
+        if gas.fo2() <= bottom_gas.fo2() {
+            return None;
+        }
+        (1..=deepest_stop)
+            .rev()
+            .map(|k| msw::new(DINC.to_f32() * k as f32))
+            .find(|depth| limits.allow(&gas, ambient_pressure_at_depth(surface_pressure, *depth)))
+            .map(|switch_depth| DecoGasRecommendation { gas, switch_depth })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gas::{MAX_GAS_DENSITY_LIMIT, MAX_PO2_DECO, MAX_PO2_WORKING};
+
+    fn limits(max_po2: Bar) -> MixLimits {
+        MixLimits {
+            max_po2,
+            min_po2: Bar::new(0.16),
+            narcosis: NarcosisSettings::limit(msw::new(30.0), true),
+            gas_density: GasDensitySettings::limit_g_l(MAX_GAS_DENSITY_LIMIT),
+        }
+    }
+
+    #[test]
+    fn trimix_for_60m_snaps_to_tmx18_45() {
+        let surface = msw::new(0.0).to_pa();
+        let limits = limits(MAX_PO2_WORKING);
+        let optimum = best_mix(msw::new(60.0), surface, &limits).expect("60 m is reachable");
+        assert!((optimum.fo2() - 0.19).abs() < 1e-4, "{optimum:?}");
+        assert!((optimum.fhe() - 0.43).abs() < 1e-4, "{optimum:?}");
+        assert_eq!(
+            recommend_bottom_mix(msw::new(60.0), surface, &limits),
+            Ok(TMX18_45)
+        );
+    }
+
+    #[test]
+    fn nitrox_for_30m_has_no_standard_match() {
+        let surface = msw::new(0.0).to_pa();
+        let mix = recommend_bottom_mix(msw::new(30.0), surface, &limits(MAX_PO2_WORKING))
+            .expect("30 m is reachable");
+        assert!((mix.fo2() - 0.34).abs() < 1e-4, "{mix:?}");
+        assert_eq!(mix.fhe(), 0.0);
+    }
+
+    #[test]
+    fn unreachable_limits_are_rejected() {
+        let surface = msw::new(0.0).to_pa();
+        let mut limits = limits(Bar::new(0.15));
+        assert!(best_mix(msw::new(10.0), surface, &limits).is_err());
+        limits.max_po2 = MAX_PO2_WORKING;
+        limits.gas_density = GasDensitySettings::limit_g_l(crate::gas::gL::new(0.5));
+        assert!(best_mix(msw::new(100.0), surface, &limits).is_err());
+    }
+
+    #[test]
+    fn deco_gases_with_switch_depths() {
+        let surface = msw::new(0.0).to_pa();
+        let recommended = recommend_deco_gases(
+            &TMX18_45,
+            msw::new(60.0),
+            &STANDARD_DECO_GASES,
+            surface,
+            &limits(MAX_PO2_DECO),
+        );
+        let switch_depths = recommended.map(|r| r.map(|r| r.switch_depth.to_f32()));
+        // 21/35 would be fine for ppO2 at 57 m but exceeds the END limit there
+        assert_eq!(switch_depths, [Some(51.0), Some(21.0), Some(6.0)]);
+
+        let none_richer = recommend_deco_gases(
+            &NX50,
+            msw::new(21.0),
+            &[TMX21_35],
+            surface,
+            &limits(MAX_PO2_DECO),
+        );
+        assert_eq!(none_richer, [None]);
+    }
+}
@@ -0,0 +1,216 @@
+#[allow(unused)]
+use num::Float;
+
+use crate::{
+    cylinder::{Cylinder, gas_content_l, pressure_for_content},
+    gas::GasMix,
+    pressure_unit::Bar,
+};
+
+/// Shortfall, as a fraction of the target content, still treated as a zero addition.
+const BLEND_TOLERANCE: f32 = 1e-4;
+const BLEED_SEARCH_ITER: usize = 30;
+
+/**
+* Fill of one cylinder to a target mix with pure He, pure O2 and a top-off
+* gas. Litres are surface litres and all pressures are settled (room
+* temperature) readings of the filled cylinder.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct BlendPlan {
+    /// Pressure to bleed the start gas down to; the start pressure if no bleed is needed.
+    pub bleed_to: Bar,
+    pub he_l: f32,
+    pub o2_l: f32,
+    pub top_off_l: f32,
+    /// Gauge targets of a partial-pressure fill: He first, then O2, then the top-off.
+    pub after_he: Bar,
+    pub after_o2: Bar,
+    pub after_top_off: Bar,
+}
+
+impl BlendPlan {
+    /// Compressor intake mix of a continuous blend adding the same gases as this plan.
+    pub fn continuous_intake(&self, top_off: &GasMix<f32>) -> Result<GasMix<f32>, &'static str> {
+        let added_l = self.he_l + self.o2_l + self.top_off_l;
+        if added_l <= 0.0 {
+            return Err("Nothing to add.");
+        }
+        mix_of(
+            self.o2_l + self.top_off_l * top_off.fo2(),
+            self.he_l + self.top_off_l * top_off.fhe(),
+            added_l,
+        )
+    }
+}
+
+/// O2, He and N2 surface litres of `content_l` of `gas`.
+fn components_l(gas: &GasMix<f32>, content_l: f32) -> [f32; 3] {
+    [
+        gas.fo2() * content_l,
+        gas.fhe() * content_l,
+        gas.fn2() * content_l,
+    ]
+}
+
+fn mix_of(o2_l: f32, he_l: f32, total_l: f32) -> Result<GasMix<f32>, &'static str> {
+    let fo2 = (o2_l / total_l).clamp(0.0, 1.0);
+    let fhe = (he_l / total_l).clamp(0.0, 1.0 - fo2);
+    GasMix::new(fo2, fhe)
+}
+
+/// He, O2 and top-off litres taking `start` to `target`, in that order.
+fn additions_l(start: [f32; 3], target: [f32; 3], top_off: &GasMix<f32>) -> [f32; 3] {
+    let top_off_l = (target[2] - start[2]) / top_off.fn2();
+    [
+        target[1] - start[1] - top_off_l * top_off.fhe(),
+        target[0] - start[0] - top_off_l * top_off.fo2(),
+        top_off_l,
+    ]
+}
+
+fn is_feasible(additions: &[f32; 3], target_l: f32) -> bool {
+    additions.iter().all(|l| *l >= -BLEND_TOLERANCE * target_l)
+}
+
+/**
+* Plans a fill of `start` to `target_pressure` of `target` with He, O2 and
+* `top_off` (air or a nitrox bank). If the target cannot be reached by adding
+* gas, the start gas is bled down to the highest pressure that makes it reachable.
+*/
+pub fn plan_blend(
+    start: &Cylinder,
+    target: &GasMix<f32>,
+    target_pressure: Bar,
+    top_off: &GasMix<f32>,
+) -> Result<BlendPlan, &'static str> {
+    if top_off.fn2() <= 0.0 {
+        return Err("Top-off gas must contain nitrogen.");
+    }
+    let volume = start.water_volume_l;
+    let start_l = start.content_l();
+    let target_l = gas_content_l(target, volume, target_pressure);
+    let target_components = components_l(target, target_l);
+    let additions_at = |kept: f32| {
+        additions_l(
+            components_l(&start.gas, start_l * kept),
+            target_components,
+            top_off,
+        )
+    };
+
+    let mut kept = 1.0;
+    if !is_feasible(&additions_at(kept), target_l) {
+        if !is_feasible(&additions_at(0.0), target_l) {
+            return Err("Target mix cannot be blended with this top-off gas.");
+        }
+        let (mut low, mut high) = (0.0, 1.0);
+        for _ in 0..BLEED_SEARCH_ITER {
+            let mid = 0.5 * (low + high);
+            if is_feasible(&additions_at(mid), target_l) {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        kept = low;
+    }
+    let [he_l, o2_l, top_off_l] = additions_at(kept).map(|l| l.max(0.0));
+
+    let [mut o2, mut he, _] = components_l(&start.gas, start_l * kept);
+    let mut content = start_l * kept;
+    let mut pressure_after = |o2_l: f32, he_l: f32, added_l: f32| {
+        (o2, he, content) = (o2 + o2_l, he + he_l, content + added_l);
+        if content <= 0.0 {
+            return Ok(Bar::new(0.0));
+        }
+        Ok::<Bar, &'static str>(pressure_for_content(
+            &mix_of(o2, he, content)?,
+            volume,
+            content,
+        ))
+    };
+    let after_he = pressure_after(0.0, he_l, he_l)?;
+    let after_o2 = pressure_after(o2_l, 0.0, o2_l)?;
+    let after_top_off = pressure_after(
+        top_off_l * top_off.fo2(),
+        top_off_l * top_off.fhe(),
+        top_off_l,
+    )?;
+
+    let bleed_to = if kept < 1.0 {
+        pressure_for_content(&start.gas, volume, start_l * kept)
+    } else {
+        start.pressure
+    };
+    Ok(BlendPlan {
+        bleed_to,
+        he_l,
+        o2_l,
+        top_off_l,
+        after_he,
+        after_o2,
+        after_top_off,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cylinder::CylinderRole,
+        gas::{AIR, TMX18_45, TMX21_35},
+        pressure_unit::Pressure,
+    };
+
+    const fn twin12(gas: GasMix<f32>, pressure: f32) -> Cylinder {
+        Cylinder {
+            water_volume_l: 24.0,
+            working_pressure: Bar::new(232.0),
+            pressure: Bar::new(pressure),
+            gas,
+            role: CylinderRole::Bottom,
+        }
+    }
+
+    #[test]
+    fn trimix_from_empty_with_air_top_off() {
+        let plan = plan_blend(&twin12(AIR, 0.0), &TMX21_35, Bar::new(200.0), &AIR)
+            .expect("21/35 from air");
+        std::println!("{plan:?}");
+        assert_eq!(plan.bleed_to, Bar::new(0.0));
+        assert!((plan.after_top_off.to_f32() - 200.0).abs() < 0.5);
+        // Alone and at a lower pressure, the He is less compressed than the final mix
+        assert!(plan.after_he.to_f32() < 0.35 * 200.0);
+        assert!(plan.after_he < plan.after_o2 && plan.after_o2 < plan.after_top_off);
+
+        let mix = plan.continuous_intake(&AIR).expect("gas is added");
+        assert!((mix.fo2() - TMX21_35.fo2()).abs() < 1e-3);
+        assert!((mix.fhe() - TMX21_35.fhe()).abs() < 1e-3);
+    }
+
+    #[test]
+    fn leftover_helium_is_bled_down() {
+        let start = twin12(TMX18_45, 100.0);
+        let nitrox32 = GasMix::new(0.32, 0.0).expect("32 < 100");
+        let plan = plan_blend(&start, &nitrox32, Bar::new(200.0), &AIR).expect("bleed to empty");
+        assert!(plan.bleed_to.to_f32() < 0.5);
+
+        let topped_up = plan_blend(&start, &TMX18_45, Bar::new(200.0), &AIR).expect("top up");
+        assert_eq!(topped_up.bleed_to, Bar::new(100.0));
+        assert!(topped_up.he_l > 0.0 && topped_up.o2_l > 0.0 && topped_up.top_off_l > 0.0);
+
+        let lower = plan_blend(&start, &TMX18_45, Bar::new(80.0), &AIR).expect("bleed");
+        assert!((lower.bleed_to.to_f32() - 80.0).abs() < 0.5);
+        assert!(lower.he_l + lower.o2_l + lower.top_off_l < 1.0);
+    }
+
+    #[test]
+    fn unreachable_targets_are_rejected() {
+        let start = twin12(AIR, 0.0);
+        let nitrox50 = GasMix::new(0.5, 0.0).expect("50 < 100");
+        assert!(plan_blend(&start, &AIR, Bar::new(200.0), &nitrox50).is_err());
+        let heliox = GasMix::new(0.2, 0.8).expect("20 + 80 = 100");
+        assert!(plan_blend(&start, &AIR, Bar::new(200.0), &heliox).is_err());
+    }
+}
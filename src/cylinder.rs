@@ -0,0 +1,186 @@
+#[allow(unused)]
+use num::Float;
+
+use crate::{
+    gas::GasMix,
+    pressure_unit::{Bar, Pressure},
+};
+
+/// Surface pressure the content of a cylinder is expressed at (1 atm).
+pub const SURFACE_BAR: Bar = Bar::new(1.013_25);
+
+/**
+* Z - 1 = c1 P + c2 P^2 + c3 P^3 (P in bar), fitted for each gas at room
+* temperature up to about 500 bar.
+*/
+const Z_O2: [f32; 3] = [-7.180_921e-4, 2.818_526e-6, -1.502_906e-9];
+const Z_N2: [f32; 3] = [-2.192_604e-4, 2.928_448e-6, -2.076_135e-9];
+const Z_HE: [f32; 3] = [4.873_2e-4, -8.836_329e-8, 5.333_045e-11];
+
+const PRESSURE_SEARCH_ITER: usize = 40;
+
+const fn virial_m1(c: &[f32; 3], bar: f32) -> f32 {
+    bar * (c[0] + bar * (c[1] + bar * c[2]))
+}
+
+/// Compressibility factor Z = PV / nRT of `gas` at `pressure`, mixing the components linearly.
+pub const fn compressibility(gas: &GasMix<f32>, pressure: Bar) -> f32 {
+    let bar = pressure.to_f32();
+    1.0 + gas.fo2() * virial_m1(&Z_O2, bar)
+        + gas.fn2() * virial_m1(&Z_N2, bar)
+        + gas.fhe() * virial_m1(&Z_HE, bar)
+}
+
+/// Surface litres of `gas` in `water_volume_l` at `pressure`.
+pub const fn gas_content_l(gas: &GasMix<f32>, water_volume_l: f32, pressure: Bar) -> f32 {
+    let surface = SURFACE_BAR.to_f32() / compressibility(gas, SURFACE_BAR);
+    water_volume_l * pressure.to_f32() / compressibility(gas, pressure) / surface
+}
+
+/**
+* Pressure at which `water_volume_l` holds `content_l` surface litres of `gas`.
+* P / Z(P) grows monotonically over the fitted range, so it is bisected.
+*/
+pub fn pressure_for_content(gas: &GasMix<f32>, water_volume_l: f32, content_l: f32) -> Bar {
+    if content_l <= 0.0 || water_volume_l <= 0.0 {
+        return Bar::new(0.0);
+    }
+    let ideal = content_l / water_volume_l * SURFACE_BAR.to_f32();
+    let (mut low, mut high) = (0.0, 2.0 * ideal);
+    for _ in 0..PRESSURE_SEARCH_ITER {
+        let mid = 0.5 * (low + high);
+        if gas_content_l(gas, water_volume_l, Bar::new(mid)) < content_l {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    Bar::new(0.5 * (low + high))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CylinderRole {
+    Bottom,
+    Travel,
+    Deco,
+    Bailout,
+    Diluent,
+    Oxygen,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Cylinder {
+    pub water_volume_l: f32,
+    pub working_pressure: Bar,
+    pub pressure: Bar,
+    pub gas: GasMix<f32>,
+    pub role: CylinderRole,
+}
+
+impl Cylinder {
+    /// Surface litres at the current pressure.
+    pub const fn content_l(&self) -> f32 {
+        gas_content_l(&self.gas, self.water_volume_l, self.pressure)
+    }
+
+    /// Surface litres when filled to the working pressure.
+    pub const fn capacity_l(&self) -> f32 {
+        gas_content_l(&self.gas, self.water_volume_l, self.working_pressure)
+    }
+
+    /// Pressure left once `used_l` surface litres have been breathed.
+    pub fn pressure_after(&self, used_l: f32) -> Bar {
+        pressure_for_content(&self.gas, self.water_volume_l, self.content_l() - used_l)
+    }
+
+    /// Pressure holding `content_l` surface litres, e.g. a rock-bottom reserve.
+    pub fn pressure_for(&self, content_l: f32) -> Bar {
+        pressure_for_content(&self.gas, self.water_volume_l, content_l)
+    }
+
+    /// `gas_plan::thirds_turn_pressure` on the content above `reserve_l` rather than on bar.
+    pub fn thirds_turn_pressure(&self, reserve_l: f32) -> Bar {
+        let usable_l = (self.content_l() - reserve_l).max(0.0);
+        self.pressure_after(usable_l / 3.0)
+    }
+}
+
+/**
+* Gas array and `gases_enabled` flags for `calc_deco_schedule` and the gas
+* planner. A cylinder is enabled if its role is in `roles` and it is not empty.
+*/
+pub fn gases_from_inventory<const NUM_CYLINDERS: usize>(
+    cylinders: &[Cylinder; NUM_CYLINDERS],
+    roles: &[CylinderRole],
+) -> ([GasMix<f32>; NUM_CYLINDERS], [bool; NUM_CYLINDERS]) {
+    let gases = cylinders.map(|c| c.gas);
+    let enabled = cylinders.map(|c| roles.contains(&c.role) && c.pressure.to_f32() > 0.0);
+    (gases, enabled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gas::{AIR, NX50, NX100, TMX10_80};
+
+    const fn cylinder(gas: GasMix<f32>, role: CylinderRole, pressure: f32) -> Cylinder {
+        Cylinder {
+            water_volume_l: 12.0,
+            working_pressure: Bar::new(232.0),
+            pressure: Bar::new(pressure),
+            gas,
+            role,
+        }
+    }
+
+    #[test]
+    fn compressibility_of_air_and_helium() {
+        let z_air = compressibility(&AIR, Bar::new(200.0));
+        assert!((z_air - 1.036).abs() < 0.005, "{z_air}");
+        let helium = GasMix::new(0.0, 1.0).expect("pure He");
+        let z_he = compressibility(&helium, Bar::new(200.0));
+        assert!((z_he - 1.094).abs() < 0.005, "{z_he}");
+        assert!((compressibility(&AIR, SURFACE_BAR) - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn helium_fill_holds_less_than_ideal_gas() {
+        let trimix = cylinder(TMX10_80, CylinderRole::Bottom, 232.0);
+        let ideal = 12.0 * 232.0 / SURFACE_BAR.to_f32();
+        assert!(trimix.content_l() < 0.92 * ideal, "{}", trimix.content_l());
+        assert_eq!(trimix.content_l(), trimix.capacity_l());
+
+        let used = 1000.0;
+        let after = trimix.pressure_after(used);
+        let left = Cylinder {
+            pressure: after,
+            ..trimix
+        };
+        assert!((left.content_l() - (trimix.content_l() - used)).abs() < 0.5);
+        assert_eq!(trimix.pressure_for(0.0), Bar::new(0.0));
+
+        // The first third of a helium fill costs more than a third of the pressure
+        let turn = trimix.thirds_turn_pressure(0.0).to_f32();
+        assert!(turn < 232.0 * 2.0 / 3.0 - 3.0, "{turn}");
+    }
+
+    #[test]
+    fn inventory_enables_requested_roles() {
+        let cylinders = [
+            cylinder(TMX10_80, CylinderRole::Diluent, 200.0),
+            cylinder(NX50, CylinderRole::Deco, 200.0),
+            cylinder(NX100, CylinderRole::Oxygen, 0.0),
+            cylinder(AIR, CylinderRole::Bailout, 200.0),
+        ];
+        let (gases, enabled) = gases_from_inventory(
+            &cylinders,
+            &[
+                CylinderRole::Bailout,
+                CylinderRole::Deco,
+                CylinderRole::Oxygen,
+            ],
+        );
+        assert_eq!(gases[1], NX50);
+        assert_eq!(enabled, [false, true, false, true]);
+    }
+}
@@ -1,30 +1,16 @@
 use core::time::Duration;
 
-use crate::setup::{NUM_STOP_DEPTHS, NUM_TISSUES};
-
-use crate::depth_utils::{get_depth, get_depth_idx};
+use crate::deco_model::DecoModel;
+use crate::depth_utils::{get_grid_depth, get_grid_depth_idx};
 use crate::dive::{Stop, StopSchedule};
-use crate::gas::{GasDensitySettings, GasMix, TissuesLoading, best_available_mix};
-use crate::mptt;
-#[cfg(feature = "lin_exp")]
-use crate::mptt::Tissue;
-#[cfg(not(feature = "lin_exp"))]
-use crate::mptt_buehlmann::{self, BuehlmannTissue as Tissue};
-#[cfg(feature = "lin_exp")]
-use crate::mptt_thalmann::{self, NUM_STOP_DEPTHS_THALMANN, NUM_TISSUES_THALMANN};
-use crate::pressure_unit::{AbsPressure, Pa, Pressure, msw};
-use crate::pressure_unit::ambient_pressure_at_depth;
-use crate::setup::set_m;
-use crate::update::first_stop_depth_with_gf;
-#[cfg(not(feature = "lin_exp"))]
-pub use crate::update_exp::{
-    compute_stop_time_exp as compute_stop_time, update_model_state_exp as update_model_state,
-};
-#[cfg(feature = "lin_exp")]
-pub use crate::update_exp_lin::{
-    compute_stop_time_lin_exp as compute_stop_time,
-    update_model_state_lin_exp as update_model_state,
+use crate::gas::{
+    CCRGas, Gas, GasDensitySettings, GasMix, IcdSettings, MAX_O2_DILUENT, NarcosisSettings,
+    SCRGas, TissuesLoading,
 };
+use crate::gas_policy::GasSwitchPolicy;
+use crate::pdcs::{RiskIntegral, RiskModel, p_dcs_from_schedule};
+use crate::pressure_unit::{AbsPressure, Pa, Pressure, msw};
+use crate::pressure_unit::{ambient_pressure_at_depth, depth_at_ambient_pressure};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum DecoAlgorithmResult {
@@ -37,28 +23,61 @@ pub enum DecoAlgorithmResult {
     },
 }
 
-#[cfg(feature = "lin_exp")]
-pub const MVALUES: mptt::MValues<Pa, { NUM_TISSUES_THALMANN }, { NUM_STOP_DEPTHS_THALMANN }> =
-    set_m(0);
-#[cfg(not(feature = "lin_exp"))]
-pub const MVALUES: mptt::MValues<Pa, { NUM_TISSUES }, { NUM_STOP_DEPTHS }> = set_m(0);
-#[cfg(feature = "lin_exp")]
-pub const TISSUES: [Tissue; NUM_TISSUES_THALMANN] = mptt_thalmann::TISSUES;
-#[cfg(not(feature = "lin_exp"))]
-pub const TISSUES: [Tissue; NUM_TISSUES] = mptt_buehlmann::TISSUES;
-
-pub type MValues<P: const AbsPressure> = mptt::MValues<P, { NUM_TISSUES }, { NUM_STOP_DEPTHS }>;
-
 const STOP_SAFETY_MARGIN: Duration = Duration::from_secs(5);
+/// Lowest fraction of the configured gradient factors tried when targeting a P_DCS.
+const MIN_GF_SCALE: f32 = 0.1;
+const GF_SEARCH_ITER: usize = 12;
+/// Ascents a model refined by its own schedule (VPM-B) may take to converge.
+const MAX_MODEL_REFINEMENTS: usize = 32;
 
-pub struct DecoSettings<P: const AbsPressure> {
+pub struct DecoSettings<'a, P: const AbsPressure> {
     pub gas_density_settings: GasDensitySettings,
+    pub narcosis_settings: NarcosisSettings,
     pub max_deco_po2: P,
     pub surface_pressure: P,
-    pub ignore_icd: bool,
+    pub icd_settings: IcdSettings,
     pub gf_low: f32,
     pub gf_high: f32,
     pub last_deco_stop: msw,
+    /// Rate of the ascent legs to and between the stops, loaded into the tissues.
+    pub ascent_rate_per_meter: Duration,
+    /// Which usable gas, loop or diluent every schedule switches to.
+    pub gas_switch_policy: GasSwitchPolicy<'a>,
+}
+
+/**
+* Setpoints of a closed-circuit rebreather. The low setpoint is switched to the
+* high one at `switch_up_depth` on descent and back at `switch_down_depth` on
+* ascent; a `switch_down_depth` of 0 msw keeps the high setpoint to the surface.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct CCRSettings<P: const AbsPressure> {
+    pub low_set_point: P,
+    pub high_set_point: P,
+    pub switch_up_depth: msw,
+    pub switch_down_depth: msw,
+}
+
+impl<P: const AbsPressure> CCRSettings<P> {
+    pub fn set_point_at(&self, depth: msw, ascending: bool) -> P {
+        let high = if ascending {
+            depth.to_f32() > self.switch_down_depth.to_f32()
+        } else {
+            depth.to_f32() >= self.switch_up_depth.to_f32()
+        };
+        if high {
+            self.high_set_point
+        } else {
+            self.low_set_point
+        }
+    }
+
+    pub fn loop_gas(&self, diluent: &GasMix<f32>, depth: msw, ascending: bool) -> CCRGas<f32, P> {
+        CCRGas {
+            diluent: *diluent,
+            set_point: self.set_point_at(depth, ascending),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -67,13 +86,16 @@ pub struct GradientFactors {
     pub high: f32,
 }
 
-fn compute_initial_first_stop<P: const AbsPressure>(
+fn compute_initial_first_stop<M, const NUM_TISSUES: usize, P: const AbsPressure>(
+    model: &M,
     loading: &TissuesLoading<NUM_TISSUES, P>,
-    m_values: &MValues<P>,
     surface_pressure: P,
     gf: GradientFactors,
-) -> Option<msw> {
-    first_stop_depth_with_gf(loading, m_values, surface_pressure, gf.low)
+) -> Option<msw>
+where
+    M: DecoModel<NUM_TISSUES, P>,
+{
+    model.first_stop_depth(loading, surface_pressure, gf.low)
 }
 
 fn interpolate_gf_for_depth(initial_first_stop: msw, stop_depth: msw, gf: GradientFactors) -> f32 {
@@ -82,13 +104,7 @@ fn interpolate_gf_for_depth(initial_first_stop: msw, stop_depth: msw, gf: Gradie
     } else {
         let t = (initial_first_stop.to_msw().to_f32() - stop_depth.to_msw().to_f32())
             / initial_first_stop.to_msw().to_f32();
-        let t = if t < 0.0 {
-            0.0
-        } else if t > 1.0 {
-            1.0
-        } else {
-            t
-        };
+        let t = t.clamp(0.0, 1.0);
         gf.low + (gf.high - gf.low) * t
     }
 }
@@ -97,28 +113,32 @@ fn add_stop_safety_margin(stop_duration: Duration) -> Duration {
     stop_duration.saturating_add(STOP_SAFETY_MARGIN)
 }
 
-fn compute_next_stop_depth<P: const AbsPressure>(
+fn compute_next_stop_depth<M, const NUM_TISSUES: usize, P: const AbsPressure>(
+    model: &M,
     loading: &TissuesLoading<NUM_TISSUES, P>,
-    m_values: &MValues<P>,
     surface_pressure: P,
     initial_first_stop: msw,
     current_stop_depth: msw,
     gf: GradientFactors,
     last_deco_stop: msw,
-) -> Option<msw> {
+) -> Option<msw>
+where
+    M: DecoModel<NUM_TISSUES, P>,
+{
     if current_stop_depth.to_msw().to_f32() <= last_deco_stop.to_msw().to_f32() {
         return None;
     }
 
-    let current_depth_idx = get_depth_idx(current_stop_depth);
+    let stop_increment = model.stop_increment();
+    let current_depth_idx = get_grid_depth_idx(current_stop_depth, stop_increment);
     if current_depth_idx <= 1 {
         return None;
     }
 
-    let next_depth = get_depth(current_depth_idx - 1).to_msw();
+    let next_depth = get_grid_depth(current_depth_idx - 1, stop_increment);
     let next_gf = interpolate_gf_for_depth(initial_first_stop, next_depth, gf);
 
-    match first_stop_depth_with_gf(loading, m_values, surface_pressure, next_gf) {
+    match model.first_stop_depth(loading, surface_pressure, next_gf) {
         Some(depth) if depth.to_msw().to_f32() < last_deco_stop.to_msw().to_f32() => {
             Some(last_deco_stop)
         }
@@ -137,62 +157,445 @@ fn compute_next_stop_depth<P: const AbsPressure>(
     }
 }
 
-#[cfg(feature = "lin_exp")]
-type TissuesLoadingNumTissues<P> = TissuesLoading<{ NUM_TISSUES_THALMANN }, P>;
-#[cfg(not(feature = "lin_exp"))]
-type TissuesLoadingNumTissues<P> = TissuesLoading<{ NUM_TISSUES }, P>;
-
-pub fn calc_deco_schedule<const NUM_STOPS: usize, const NUM_GASES: usize>(
-    loading: &TissuesLoadingNumTissues<Pa>,
+pub fn calc_deco_schedule<
+    M,
+    const NUM_TISSUES: usize,
+    const NUM_STOPS: usize,
+    const NUM_GASES: usize,
+>(
+    model: &M,
+    loading: &TissuesLoading<NUM_TISSUES, Pa>,
+    depth: msw,
+    bottom_gas: usize,
     gases: &[GasMix<f32>; NUM_GASES],
     gases_enabled: &[bool; NUM_GASES],
     deco_settings: &DecoSettings<Pa>,
-) -> Result<StopSchedule<NUM_STOPS>, &'static str> {
+) -> Result<StopSchedule<NUM_STOPS>, &'static str>
+where
+    M: DecoModel<NUM_TISSUES, Pa>,
+{
+    let start = (
+        depth,
+        Some(bottom_gas),
+        *gases
+            .get(bottom_gas)
+            .ok_or("Bottom gas is not among the gases.")?,
+    );
     let gf = GradientFactors {
         low: deco_settings.gf_low,
         high: deco_settings.gf_high,
     };
-    calc_deco_schedule_intern(
+    let stop_increment = model.stop_increment();
+    calc_deco_schedule_intern(model, loading, start, deco_settings, gf, |depth, loading, current| {
+        open_circuit_gas(
+            depth,
+            stop_increment,
+            loading,
+            current,
+            gases,
+            gases_enabled,
+            deco_settings,
+        )
+    })
+}
+
+/**
+* Schedule for a closed-circuit ascent from `depth` on `diluents[bottom_diluent]`.
+* At every stop the loop holds the ascent setpoint of `ccr` (see
+* `ccr_breathing_gas`); `max_deco_po2` is not used.
+*/
+#[allow(clippy::too_many_arguments)]
+pub fn calc_ccr_deco_schedule<
+    M,
+    const NUM_TISSUES: usize,
+    const NUM_STOPS: usize,
+    const NUM_DILUENTS: usize,
+>(
+    model: &M,
+    loading: &TissuesLoading<NUM_TISSUES, Pa>,
+    depth: msw,
+    bottom_diluent: usize,
+    ccr: &CCRSettings<Pa>,
+    diluents: &[GasMix<f32>; NUM_DILUENTS],
+    diluents_enabled: &[bool; NUM_DILUENTS],
+    deco_settings: &DecoSettings<Pa>,
+) -> Result<StopSchedule<NUM_STOPS>, &'static str>
+where
+    M: DecoModel<NUM_TISSUES, Pa>,
+{
+    let start = (
+        depth,
+        Some(bottom_diluent),
+        ccr.loop_gas(
+            diluents
+                .get(bottom_diluent)
+                .ok_or("Bottom diluent is not among the diluents.")?,
+            depth,
+            true,
+        ),
+    );
+    let gf = GradientFactors {
+        low: deco_settings.gf_low,
+        high: deco_settings.gf_high,
+    };
+    let stop_increment = model.stop_increment();
+    calc_deco_schedule_intern(model, loading, start, deco_settings, gf, |depth, loading, current| {
+        let idx = ccr_diluent(
+            depth,
+            stop_increment,
+            loading,
+            diluents,
+            diluents_enabled,
+            current,
+            deco_settings,
+        )?;
+        Ok((idx, ccr.loop_gas(&diluents[idx], depth, true)))
+    })
+}
+
+/**
+* Schedule for a semi-closed ascent from `depth` on `loops[bottom_loop]`: at every
+* stop the loop `gas_switch_policy` picks among those whose PO2 stays within `max_deco_po2`.
+*/
+pub fn calc_scr_deco_schedule<
+    M,
+    const NUM_TISSUES: usize,
+    const NUM_STOPS: usize,
+    const NUM_LOOPS: usize,
+>(
+    model: &M,
+    loading: &TissuesLoading<NUM_TISSUES, Pa>,
+    depth: msw,
+    bottom_loop: usize,
+    loops: &[SCRGas<f32>; NUM_LOOPS],
+    loops_enabled: &[bool; NUM_LOOPS],
+    deco_settings: &DecoSettings<Pa>,
+) -> Result<StopSchedule<NUM_STOPS>, &'static str>
+where
+    M: DecoModel<NUM_TISSUES, Pa>,
+{
+    let gf = GradientFactors {
+        low: deco_settings.gf_low,
+        high: deco_settings.gf_high,
+    };
+    let start = (
+        depth,
+        Some(bottom_loop),
+        *loops
+            .get(bottom_loop)
+            .ok_or("Bottom loop is not among the loops.")?,
+    );
+    calc_deco_schedule_intern(model, loading, start, deco_settings, gf, |depth, loading, current| {
+        let ambient = ambient_pressure_at_depth(deco_settings.surface_pressure, depth);
+        let idx = deco_settings
+            .gas_switch_policy
+            .select(
+                current,
+                depth,
+                model.stop_increment(),
+                deco_settings.max_deco_po2,
+                &loops.map(|l| l.to_fixed_gas_mix(ambient)),
+                loops_enabled,
+                loading,
+                deco_settings,
+            )
+            .ok_or("No gas for depth.")?;
+        Ok((idx, loops[idx]))
+    })
+}
+
+/**
+* Open-circuit equivalent of the loop at `depth`, over the diluent of
+* `ccr_diluent`.
+*/
+#[allow(clippy::too_many_arguments)]
+pub fn ccr_breathing_gas<const NUM_TISSUES: usize, const NUM_DILUENTS: usize>(
+    depth: msw,
+    stop_increment: msw,
+    loading: &TissuesLoading<NUM_TISSUES, Pa>,
+    ccr: &CCRSettings<Pa>,
+    diluents: &[GasMix<f32>; NUM_DILUENTS],
+    diluents_enabled: &[bool; NUM_DILUENTS],
+    current: Option<usize>,
+    deco_settings: &DecoSettings<Pa>,
+    ascending: bool,
+) -> Result<GasMix<f32>, &'static str> {
+    let ambient = ambient_pressure_at_depth(deco_settings.surface_pressure, depth);
+    let idx = ccr_diluent(
+        depth,
+        stop_increment,
         loading,
-        &TISSUES,
-        gases,
-        gases_enabled,
-        &MVALUES,
+        diluents,
+        diluents_enabled,
+        current,
         deco_settings,
-        gf,
-    )
+    )?;
+    Ok(ccr
+        .loop_gas(&diluents[idx], depth, ascending)
+        .to_fixed_gas_mix(ambient))
+}
+
+/**
+* Index of the diluent `gas_switch_policy` picks at `depth` (on a stop grid of
+* `stop_increment`) when coming from `diluents[current]` (`None` before the
+* first diluent), among those that can still be flushed there (PO2 at most
+* `MAX_O2_DILUENT`).
+*/
+pub fn ccr_diluent<const NUM_TISSUES: usize, const NUM_DILUENTS: usize>(
+    depth: msw,
+    stop_increment: msw,
+    loading: &TissuesLoading<NUM_TISSUES, Pa>,
+    diluents: &[GasMix<f32>; NUM_DILUENTS],
+    diluents_enabled: &[bool; NUM_DILUENTS],
+    current: Option<usize>,
+    deco_settings: &DecoSettings<Pa>,
+) -> Result<usize, &'static str> {
+    deco_settings
+        .gas_switch_policy
+        .select(
+            current,
+            depth,
+            stop_increment,
+            MAX_O2_DILUENT.to_pa(),
+            diluents,
+            diluents_enabled,
+            loading,
+            deco_settings,
+        )
+    .ok_or("No diluent for depth.")
+}
+
+fn open_circuit_gas<const NUM_TISSUES: usize, const NUM_GASES: usize, P: const AbsPressure>(
+    depth: msw,
+    stop_increment: msw,
+    loading: &TissuesLoading<NUM_TISSUES, P>,
+    current: Option<usize>,
+    gases: &[GasMix<f32>; NUM_GASES],
+    gases_enabled: &[bool; NUM_GASES],
+    deco_settings: &DecoSettings<P>,
+) -> Result<(usize, GasMix<f32>), &'static str> {
+    deco_settings
+        .gas_switch_policy
+        .select(
+            current,
+            depth,
+            stop_increment,
+            deco_settings.max_deco_po2,
+            gases,
+            gases_enabled,
+            loading,
+            deco_settings,
+        )
+        .map(|idx| (idx, gases[idx]))
+    .ok_or("No gas for depth.")
+}
+
+/** Risk model state at the start of the ascent and the P_DCS it must stay below. */
+pub struct RiskTarget<'a, const NUM_RISK_TISSUES: usize, P: const AbsPressure> {
+    pub model: &'a RiskModel<NUM_RISK_TISSUES>,
+    pub integral: &'a RiskIntegral<NUM_RISK_TISSUES, P>,
+    pub bottom_depth: P,
+    pub bottom_gas: &'a GasMix<f32>,
+    pub max_p_dcs: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct RiskTargetedSchedule<const NUM_STOPS: usize> {
+    pub schedule: StopSchedule<NUM_STOPS>,
+    pub p_dcs: f32,
+    pub gradient_factors: GradientFactors,
 }
 
-fn calc_deco_schedule_intern<
+/**
+* Gradient factors at `t` in [0, 2] along the searched pairs: from `configured`
+* scaled by MIN_GF_SCALE (t = 0) through `configured` (t = 1) to 100/100 (t = 2).
+*/
+fn searched_gradient_factors(configured: GradientFactors, t: f32) -> GradientFactors {
+    if t <= 1.0 {
+        let scale = MIN_GF_SCALE + (1.0 - MIN_GF_SCALE) * t;
+        GradientFactors {
+            low: configured.low * scale,
+            high: configured.high * scale,
+        }
+    } else {
+        let towards_m_values = t - 1.0;
+        GradientFactors {
+            low: configured.low + (1.0 - configured.low) * towards_m_values,
+            high: configured.high + (1.0 - configured.high) * towards_m_values,
+        }
+    }
+}
+
+/**
+* Shortest schedule whose estimated P_DCS stays below `target.max_p_dcs`.
+* The gradient factor pairs run from the configured ones scaled by MIN_GF_SCALE,
+* through the configured ones, up to the M-values (100/100), and are bisected
+* for the most liberal pair meeting the target.
+* Models that ignore gradient factors (VPM-B) yield the same schedule for every pair.
+*/
+pub fn calc_deco_schedule_for_p_dcs<
+    M,
+    const NUM_TISSUES: usize,
     const NUM_STOPS: usize,
     const NUM_GASES: usize,
-    P: const AbsPressure,
+    const NUM_RISK_TISSUES: usize,
 >(
-    loading: &TissuesLoading<NUM_TISSUES, P>,
-    tissues: &[Tissue; NUM_TISSUES],
+    model: &M,
+    loading: &TissuesLoading<NUM_TISSUES, Pa>,
     gases: &[GasMix<f32>; NUM_GASES],
     gases_enabled: &[bool; NUM_GASES],
-    m_values: &MValues<P>,
+    deco_settings: &DecoSettings<Pa>,
+    target: &RiskTarget<NUM_RISK_TISSUES, Pa>,
+) -> Result<RiskTargetedSchedule<NUM_STOPS>, &'static str>
+where
+    M: DecoModel<NUM_TISSUES, Pa>,
+{
+    let configured = GradientFactors {
+        low: deco_settings.gf_low,
+        high: deco_settings.gf_high,
+    };
+    let evaluate = |t: f32| -> Result<RiskTargetedSchedule<NUM_STOPS>, &'static str> {
+        let gf = searched_gradient_factors(configured, t);
+        let start = (
+            depth_at_ambient_pressure(deco_settings.surface_pressure, target.bottom_depth),
+            gases.iter().position(|g| g == target.bottom_gas),
+            *target.bottom_gas,
+        );
+        let stop_increment = model.stop_increment();
+        let schedule =
+            calc_deco_schedule_intern(model, loading, start, deco_settings, gf, |d, l, current| {
+                open_circuit_gas(d, stop_increment, l, current, gases, gases_enabled, deco_settings)
+            })?;
+        let p_dcs = p_dcs_from_schedule(
+            target.model,
+            target.integral,
+            target.bottom_depth,
+            target.bottom_gas,
+            &schedule,
+            deco_settings.surface_pressure,
+            &deco_settings.ascent_rate_per_meter,
+        );
+        Ok(RiskTargetedSchedule {
+            schedule,
+            p_dcs,
+            gradient_factors: gf,
+        })
+    };
+
+    let most_liberal = evaluate(2.0)?;
+    if most_liberal.p_dcs <= target.max_p_dcs {
+        return Ok(most_liberal);
+    }
+    let mut best = evaluate(0.0)?;
+    if best.p_dcs > target.max_p_dcs {
+        return Err("Target P_DCS not reachable with the searched gradient factors.");
+    }
+
+    let (mut low, mut high) = (0.0, 2.0);
+    for _ in 0..GF_SEARCH_ITER {
+        let mid = 0.5 * (low + high);
+        let candidate = evaluate(mid)?;
+        if candidate.p_dcs <= target.max_p_dcs {
+            low = mid;
+            best = candidate;
+        } else {
+            high = mid;
+        }
+    }
+    Ok(best)
+}
+
+/**
+* Ascent from the depth, gas index and gas (or loop) of `start`; `select_gas`
+* returns the index and gas of a stop from the index breathed before it. Every
+* leg, from the bottom to the first stop and between the stops, is loaded at
+* `ascent_rate_per_meter` on the gas breathed before it.
+* Models refined by the ascent (VPM-B) are iterated until they converge.
+*/
+fn calc_deco_schedule_intern<M, const NUM_TISSUES: usize, const NUM_STOPS: usize, P, G, S>(
+    model: &M,
+    loading: &TissuesLoading<NUM_TISSUES, P>,
+    start: (msw, Option<usize>, G),
     deco_settings: &DecoSettings<P>,
     gf: GradientFactors,
-) -> Result<StopSchedule<NUM_STOPS>, &'static str> {
-    assert!(NUM_STOPS < NUM_STOP_DEPTHS);
+    select_gas: S,
+) -> Result<StopSchedule<NUM_STOPS>, &'static str>
+where
+    M: DecoModel<NUM_TISSUES, P>,
+    P: const AbsPressure,
+    G: const Gas + Copy,
+    S: Fn(msw, &TissuesLoading<NUM_TISSUES, P>, Option<usize>) -> Result<(usize, G), &'static str>,
+{
+    assert!(NUM_STOPS < model.m_values().len());
+
+    let surface_pressure = deco_settings.surface_pressure;
+    let mut model = model.with_surface_pressure(surface_pressure);
+    for _ in 0..MAX_MODEL_REFINEMENTS {
+        let (schedule, surfaced, ascent_time) =
+            ascend(&model, loading, start, deco_settings, gf, &select_gas)?;
+        match model.refined_by_ascent(&surfaced, ascent_time, surface_pressure) {
+            Some(refined) => model = refined,
+            None => return Ok(schedule),
+        }
+    }
+    Err("Model did not converge on a schedule.")
+}
 
+/// Schedule of one ascent with the tissues on surfacing and the time from `start` to the surface.
+fn ascend<M, const NUM_TISSUES: usize, const NUM_STOPS: usize, P, G, S>(
+    model: &M,
+    loading: &TissuesLoading<NUM_TISSUES, P>,
+    start: (msw, Option<usize>, G),
+    deco_settings: &DecoSettings<P>,
+    gf: GradientFactors,
+    select_gas: &S,
+) -> Result<(StopSchedule<NUM_STOPS>, TissuesLoading<NUM_TISSUES, P>, Duration), &'static str>
+where
+    M: DecoModel<NUM_TISSUES, P>,
+    P: const AbsPressure,
+    G: const Gas + Copy,
+    S: Fn(msw, &TissuesLoading<NUM_TISSUES, P>, Option<usize>) -> Result<(usize, G), &'static str>,
+{
     let mut loading = loading.clone();
     let surface_pressure = deco_settings.surface_pressure;
-    let m_values = surface_pressure_adjusted_mvalues(m_values, surface_pressure);
     let mut stops: [Stop; NUM_STOPS] =
         [Stop::new(msw::new(0.0), Duration::from_millis(0), None); NUM_STOPS];
 
+    let stop_increment = model.stop_increment();
     for i in 0..NUM_STOPS {
         stops[stop_idx_in_stops(NUM_STOPS, i)] =
-            Stop::new(get_depth(i).to_msw(), Duration::from_millis(0), None);
+            Stop::new(get_grid_depth(i, stop_increment), Duration::from_millis(0), None);
     }
-    // Determine initial first stop using GFLow; if none, return empty schedule
-    let initial_first_stop = match compute_initial_first_stop(&loading, &m_values, surface_pressure, gf) {
+    let (mut depth, mut current, mut gas) = start;
+    let mut ascent_time = Duration::ZERO;
+    let travel = |model: &M,
+                  loading: &mut TissuesLoading<NUM_TISSUES, P>,
+                  from: msw,
+                  to: msw,
+                  gas: &G| {
+        let leg = deco_settings.ascent_rate_per_meter.mul_f32(from.to_f32() - to.to_f32());
+        model.update_model_state_travel(
+            loading,
+            gas,
+            ambient_pressure_at_depth(surface_pressure, from),
+            ambient_pressure_at_depth(surface_pressure, to),
+            &leg,
+        );
+        leg
+    };
+
+    // Determine initial first stop using GFLow; if none, ascend directly
+    let first_stop = compute_initial_first_stop(model, &loading, surface_pressure, gf);
+    let initial_first_stop = match first_stop {
+        // No stop is shallower than the last one
+        Some(d) if d < deco_settings.last_deco_stop => deco_settings.last_deco_stop,
         Some(d) => d,
-        None => return Ok(StopSchedule::new(stops)),
+        None => {
+            ascent_time += travel(model, &mut loading, depth, msw::new(0.0), &gas);
+            return Ok((StopSchedule::new(stops), loading, ascent_time));
+        }
     };
+    let model = model.for_ascent_from(initial_first_stop, surface_pressure);
 
     let mut iterations: usize = 0;
     const MAX_ITER: usize = 1024;
@@ -202,110 +605,65 @@ fn calc_deco_schedule_intern<
         if iterations > MAX_ITER {
             return Err("Exceeded max iterations building schedule");
         }
-        let mix = best_available_mix(
-            deco_settings.max_deco_po2,
-            ambient_pressure_at_depth(surface_pressure, stop_depth),
-            gases,
-            gases_enabled,
-            &loading,
-            deco_settings.ignore_icd,
-            &deco_settings.gas_density_settings,
-        );
-        if mix.is_none() {
-            return Err("No gas for depth.");
+        if stop_depth < depth {
+            ascent_time += travel(&model, &mut loading, depth, stop_depth, &gas);
+            depth = stop_depth;
         }
-        let (_gas_idx, breathing_gas) = mix.unwrap();
+        let (idx, stop_gas) = select_gas(stop_depth, &loading, current)?;
+        (current, gas) = (Some(idx), stop_gas);
+        let breathing_gas =
+            &gas.to_fixed_gas_mix(ambient_pressure_at_depth(surface_pressure, stop_depth));
 
-        let depth_idx = get_depth_idx(stop_depth);
-        #[cfg(feature = "lin_exp")]
-        let mvalue_idx = depth_idx
-            .checked_sub(1)
-            .ok_or("Thalmann stop depths start at 3m.")?;
+        let depth_idx = get_grid_depth_idx(stop_depth, stop_increment);
         if depth_idx > NUM_STOPS {
             return Err("Not enough space to store stops for this dive.");
         }
         let depth_idx = stop_idx_in_stops(NUM_STOPS, depth_idx);
-        #[cfg(feature = "lin_exp")]
-        assert!(stop_depth == m_values[mvalue_idx].depth);
         let gf_stop = interpolate_gf_for_depth(initial_first_stop, stop_depth, gf);
 
-        let stop_duration = compute_stop_time(
+        let stop_duration = model.compute_stop_time(
             &loading,
-            tissues,
             breathing_gas,
-            &m_values,
             stop_depth,
             gf_stop,
             surface_pressure,
             deco_settings.last_deco_stop,
-        );
-        if stop_duration.is_zero() {
-            // nothing to add and no progress — stop scheduling further stops
-            break;
-        }
-        let stop_duration = add_stop_safety_margin(stop_duration);
-        update_model_state(
-            &mut loading,
-            tissues,
-            &m_values,
-            breathing_gas,
-            ambient_pressure_at_depth(surface_pressure, stop_depth),
-            &stop_duration,
-        );
+        )?;
+        let cleared = stop_duration.is_zero();
+        if !cleared {
+            let stop_duration = add_stop_safety_margin(stop_duration);
+            model.update_model_state(
+                &mut loading,
+                breathing_gas,
+                ambient_pressure_at_depth(surface_pressure, stop_depth),
+                &stop_duration,
+            );
+            ascent_time += stop_duration;
 
-        // Merge repeated chunks at the same depth into a single scheduled stop.
-        let existing = stops[depth_idx].duration();
-        if existing.is_zero() {
-            stops[depth_idx] = Stop::new(stop_depth, stop_duration, Some(*breathing_gas));
-        } else {
-            let new_total = existing.saturating_add(stop_duration);
-            stops[depth_idx] = Stop::new(stop_depth, new_total, Some(*breathing_gas));
+            // Merge repeated chunks at the same depth into a single scheduled stop.
+            let existing = stops[depth_idx].duration();
+            if existing.is_zero() {
+                stops[depth_idx] = Stop::new(stop_depth, stop_duration, Some(*breathing_gas));
+            } else {
+                let new_total = existing.saturating_add(stop_duration);
+                stops[depth_idx] = Stop::new(stop_depth, new_total, Some(*breathing_gas));
+            }
         }
 
         next_stop = compute_next_stop_depth(
+            &model,
             &loading,
-            &m_values,
             surface_pressure,
             initial_first_stop,
             stop_depth,
             gf,
             deco_settings.last_deco_stop,
-        );
-    }
-    Ok(StopSchedule::new(stops))
-}
-
-fn surface_pressure_adjusted_mvalues<P: const AbsPressure>(
-    m_values: &MValues<P>,
-    surface_pressure: P,
-) -> MValues<P> {
-    if m_values.len() < 2 {
-        return *m_values;
+        )
+        // The ascent leg can clear a stop; then only shallower ones are left to try
+        .filter(|next| !cleared || *next < stop_depth);
     }
-
-    let sea_level_surface = msw::new(0.0).to_pa();
-    let surface_delta_pa = surface_pressure.to_pa() - sea_level_surface;
-    if surface_delta_pa == Pa::new(0.0) {
-        return *m_values;
-    }
-
-    let depth_delta_pa = m_values[1].depth.to_pa() - m_values[0].depth.to_pa();
-    if depth_delta_pa == Pa::new(0.0) {
-        return *m_values;
-    }
-
-    let mut result = *m_values;
-    for tissue_idx in 0..NUM_TISSUES {
-        let saturation_delta_per_pa =
-            (m_values[1].max_saturation[tissue_idx].to_pa() - m_values[0].max_saturation[tissue_idx].to_pa())
-                / depth_delta_pa;
-        let saturation_shift: P = (surface_delta_pa * saturation_delta_per_pa).into();
-        for row in result.iter_mut() {
-            row.max_saturation[tissue_idx] = row.max_saturation[tissue_idx] + saturation_shift;
-        }
-    }
-
-    result
+    ascent_time += travel(&model, &mut loading, depth, msw::new(0.0), &gas);
+    Ok((StopSchedule::new(stops), loading, ascent_time))
 }
 
 const fn stop_idx_in_stops(num_stops: usize, i: usize) -> usize {
@@ -0,0 +1,1036 @@
+use core::time::Duration;
+
+#[allow(unused)]
+use num::Float;
+
+use crate::{
+    gas::{Gas, GasMix, TissuesLoading},
+    mptt::{MValues, ThalmannTissue, Tissue, TissueRow},
+    mptt_buehlmann::{
+        BUEHLMANN_16C, BuehlmannCoefficients, BuehlmannTissue, NUM_STOP_DEPTHS_BUEHLMANN,
+        NUM_TISSUES_BUEHLMANN,
+    },
+    mptt_thalmann::{
+        NUM_STOP_DEPTHS_THALMANN, NUM_TISSUES_THALMANN, NUM_TISSUES_VVAL18,
+        THALMANN_DEFAULT_SETTINGS, ThalmannSettings, VVAL18_F32_VARIABLE, VVAL18_TISSUES,
+        XVAL_HE9_040_F32_VARIABLE, vval_mptt,
+    },
+    mptt_vpm::{NUM_STOP_DEPTHS_VPM, VPM_B_DEFAULT, VpmSettings},
+    pressure_unit::{AbsPressure, Pa, ambient_pressure_at_depth, msw},
+    setup::{DINC, set_m},
+    update::first_stop_depth_with_gf,
+    update_common::surface_pressure_adjusted_mvalues,
+    update_exp::{compute_stop_time_exp, first_stop_depth_exp, update_model_state_exp},
+    update_exp_lin::{compute_stop_time_lin_exp, update_model_state_lin_exp},
+    update_vpm::{
+        compute_stop_time_vpm, critical_volume_gradients_vpm, first_stop_depth_vpm,
+        initial_gradients_vpm, record_crushing_pressure_vpm, regenerated_gradients_vpm,
+        surface_phase_volume_time_vpm, tolerance_rows_vpm,
+    },
+};
+
+/// Longest constant-depth step a depth change is split into.
+const TRAVEL_STEP: Duration = Duration::from_secs(10);
+/// VPM-B critical volume iterations stop once the ascent time changes less than this.
+const VPM_PHASE_VOLUME_TIME_TOLERANCE: Duration = Duration::from_secs(60);
+
+/// A decompression model that can be selected at runtime.
+///
+/// Implementors own their tissue parameters and M-value table, so the schedule
+/// builder only has to thread the tissue state (`TissuesLoading`) through the
+/// model's kinetics and stop-time computations.
+pub trait DecoModel<const NUM_TISSUES: usize, P: const AbsPressure>: Clone {
+    /// Per-compartment kinetic parameters of the model.
+    type Tissue;
+
+    fn tissues(&self) -> &[Self::Tissue; NUM_TISSUES];
+
+    /// M-values per stop depth, ordered from shallow to deep.
+    fn m_values(&self) -> &[TissueRow<NUM_TISSUES, P>];
+
+    /// Spacing of the stop grid the schedule builder ascends on.
+    fn stop_increment(&self) -> msw {
+        DINC
+    }
+
+    /// Returns a copy of the model with its M-values shifted for a non sea-level surface.
+    fn with_surface_pressure(&self, surface_pressure: P) -> Self;
+
+    /// Returns a copy of the model prepared for an ascent whose first stop is
+    /// `first_stop`. Models without ascent-dependent limits return themselves.
+    fn for_ascent_from(&self, _first_stop: msw, _surface_pressure: P) -> Self {
+        self.clone()
+    }
+
+    /**
+     * Returns a copy of the model refined by an ascent that took `ascent_time`
+     * and surfaced with `surfaced`, or `None` once the ascent no longer changes
+     * it. Models whose limits do not depend on the ascent return `None`.
+     */
+    fn refined_by_ascent(
+        &self,
+        _surfaced: &TissuesLoading<NUM_TISSUES, P>,
+        _ascent_time: Duration,
+        _surface_pressure: P,
+    ) -> Option<Self> {
+        None
+    }
+
+    fn update_model_state(
+        &self,
+        loading: &mut TissuesLoading<NUM_TISSUES, P>,
+        breathing_gas: &GasMix<f32>,
+        current_depth: P,
+        delta_time: &Duration,
+    );
+
+    /**
+     * `update_model_state` while the ambient pressure changes linearly from `from`
+     * to `to`. Loops are breathed as their open-circuit equivalent of every step.
+     */
+    fn update_model_state_travel<G: const Gas>(
+        &self,
+        loading: &mut TissuesLoading<NUM_TISSUES, P>,
+        breathing_gas: &G,
+        from: P,
+        to: P,
+        delta_time: &Duration,
+    ) {
+        let steps = (delta_time.as_secs_f32() / TRAVEL_STEP.as_secs_f32()).ceil().max(1.0) as u32;
+        let step = *delta_time / steps;
+        for i in 0..steps {
+            let ambient = from + (to - from) * ((i as f32 + 0.5) / steps as f32);
+            self.update_model_state_loop(loading, breathing_gas, ambient, &step);
+        }
+    }
+
+    /// `update_model_state` on the open-circuit equivalent of a CCR or SCR loop at `current_depth`.
+    fn update_model_state_loop<G: const Gas>(
+        &self,
+        loading: &mut TissuesLoading<NUM_TISSUES, P>,
+        loop_gas: &G,
+        current_depth: P,
+        delta_time: &Duration,
+    ) {
+        let breathing_gas = loop_gas.to_fixed_gas_mix(current_depth);
+        self.update_model_state(loading, &breathing_gas, current_depth, delta_time);
+    }
+
+    /// Time at `stop_depth` until the next stop is allowed; errors if the stop never clears.
+    fn compute_stop_time(
+        &self,
+        loading: &TissuesLoading<NUM_TISSUES, P>,
+        breathing_gas: &GasMix<f32>,
+        stop_depth: msw,
+        gf: f32,
+        surface_pressure: P,
+        last_deco_stop: msw,
+    ) -> Result<Duration, &'static str>;
+
+    /// Deepest stop depth (the ceiling rounded down to the stop grid), or `None` if
+    /// a direct ascent is allowed.
+    fn first_stop_depth(
+        &self,
+        loading: &TissuesLoading<NUM_TISSUES, P>,
+        surface_pressure: P,
+        gf: f32,
+    ) -> Option<msw>;
+}
+
+/** Thalmann linear-exponential kinetics with a tabulated MPTT. */
+#[derive(Clone, Copy)]
+pub struct ThalmannModel<const NUM_TISSUES: usize, P: const AbsPressure> {
+    pub tissues: [ThalmannTissue; NUM_TISSUES],
+    pub m_values: MValues<P, NUM_TISSUES, NUM_STOP_DEPTHS_THALMANN>,
+    pub settings: ThalmannSettings,
+    pub stop_increment: msw,
+}
+
+impl<const NUM_TISSUES: usize, P: const AbsPressure> ThalmannModel<NUM_TISSUES, P> {
+    pub const fn new(
+        tissues: [Tissue; NUM_TISSUES],
+        m_values: MValues<P, NUM_TISSUES, NUM_STOP_DEPTHS_THALMANN>,
+    ) -> Self {
+        let mut kinetics = [ThalmannTissue::uniform(Tissue {
+            half_time: 0.0,
+            sdr: 0.0,
+        }); NUM_TISSUES];
+        let mut i = 0;
+        while i < NUM_TISSUES {
+            kinetics[i] = ThalmannTissue::uniform(tissues[i]);
+            i += 1;
+        }
+        ThalmannModel {
+            tissues: kinetics,
+            m_values,
+            settings: THALMANN_DEFAULT_SETTINGS,
+            stop_increment: DINC,
+        }
+    }
+
+    /// Same model with He half-times and SDRs of its own; `new` uses the N2 ones.
+    pub const fn with_helium_kinetics(mut self, he: [Tissue; NUM_TISSUES]) -> Self {
+        let mut i = 0;
+        while i < NUM_TISSUES {
+            self.tissues[i].he = he[i];
+            i += 1;
+        }
+        self
+    }
+
+    /// Same model with other venous / overpressure constants.
+    pub const fn with_settings(self, settings: ThalmannSettings) -> Self {
+        ThalmannModel { settings, ..self }
+    }
+
+    /// Same model on another stop grid; `m_values` must hold one row per increment.
+    pub const fn with_stop_increment(self, stop_increment: msw) -> Self {
+        ThalmannModel {
+            stop_increment,
+            ..self
+        }
+    }
+}
+
+impl<const NUM_TISSUES: usize, P: const AbsPressure> DecoModel<NUM_TISSUES, P>
+    for ThalmannModel<NUM_TISSUES, P>
+{
+    type Tissue = ThalmannTissue;
+
+    fn tissues(&self) -> &[ThalmannTissue; NUM_TISSUES] {
+        &self.tissues
+    }
+
+    fn m_values(&self) -> &[TissueRow<NUM_TISSUES, P>] {
+        &self.m_values
+    }
+
+    fn stop_increment(&self) -> msw {
+        self.stop_increment
+    }
+
+    fn with_surface_pressure(&self, surface_pressure: P) -> Self {
+        let mut result = *self;
+        surface_pressure_adjusted_mvalues(&mut result.m_values, surface_pressure);
+        result
+    }
+
+    fn update_model_state(
+        &self,
+        loading: &mut TissuesLoading<NUM_TISSUES, P>,
+        breathing_gas: &GasMix<f32>,
+        current_depth: P,
+        delta_time: &Duration,
+    ) {
+        update_model_state_lin_exp(
+            loading,
+            &self.tissues,
+            &self.settings,
+            breathing_gas,
+            current_depth,
+            delta_time,
+        )
+    }
+
+    fn compute_stop_time(
+        &self,
+        loading: &TissuesLoading<NUM_TISSUES, P>,
+        breathing_gas: &GasMix<f32>,
+        stop_depth: msw,
+        gf: f32,
+        surface_pressure: P,
+        last_deco_stop: msw,
+    ) -> Result<Duration, &'static str> {
+        Ok(compute_stop_time_lin_exp(
+            loading,
+            &self.tissues,
+            &self.settings,
+            breathing_gas,
+            &self.m_values,
+            self.stop_increment,
+            stop_depth,
+            gf,
+            surface_pressure,
+            last_deco_stop,
+        ))
+    }
+
+    fn first_stop_depth(
+        &self,
+        loading: &TissuesLoading<NUM_TISSUES, P>,
+        surface_pressure: P,
+        gf: f32,
+    ) -> Option<msw> {
+        first_stop_depth_with_gf(loading, &self.m_values, surface_pressure, gf)
+    }
+}
+
+/** Buehlmann ZH-L16 exponential kinetics with mixed-gas a/b M-values. */
+#[derive(Clone, Copy)]
+pub struct BuehlmannModel<const NUM_TISSUES: usize, P: const AbsPressure> {
+    pub tissues: [BuehlmannTissue; NUM_TISSUES],
+    pub m_values: MValues<P, NUM_TISSUES, NUM_STOP_DEPTHS_BUEHLMANN>,
+}
+
+impl<const NUM_TISSUES: usize, P: const AbsPressure> BuehlmannModel<NUM_TISSUES, P> {
+    pub const fn new(
+        tissues: [BuehlmannTissue; NUM_TISSUES],
+        m_values: MValues<P, NUM_TISSUES, NUM_STOP_DEPTHS_BUEHLMANN>,
+    ) -> Self {
+        BuehlmannModel { tissues, m_values }
+    }
+}
+
+impl BuehlmannModel<NUM_TISSUES_BUEHLMANN, Pa> {
+    /// Model with one of the published ZH-L16 / ZH-L12 coefficient sets.
+    pub const fn from_coefficients(coefficients: BuehlmannCoefficients) -> Self {
+        BuehlmannModel::new(coefficients.tissues(), set_m(coefficients.m_values(), 0))
+    }
+}
+
+impl<const NUM_TISSUES: usize, P: const AbsPressure> DecoModel<NUM_TISSUES, P>
+    for BuehlmannModel<NUM_TISSUES, P>
+{
+    type Tissue = BuehlmannTissue;
+
+    fn tissues(&self) -> &[BuehlmannTissue; NUM_TISSUES] {
+        &self.tissues
+    }
+
+    fn m_values(&self) -> &[TissueRow<NUM_TISSUES, P>] {
+        &self.m_values
+    }
+
+    fn with_surface_pressure(&self, surface_pressure: P) -> Self {
+        let mut result = *self;
+        surface_pressure_adjusted_mvalues(&mut result.m_values, surface_pressure);
+        result
+    }
+
+    fn update_model_state(
+        &self,
+        loading: &mut TissuesLoading<NUM_TISSUES, P>,
+        breathing_gas: &GasMix<f32>,
+        current_depth: P,
+        delta_time: &Duration,
+    ) {
+        update_model_state_exp(
+            loading,
+            &self.tissues,
+            breathing_gas,
+            current_depth,
+            delta_time,
+        )
+    }
+
+    fn compute_stop_time(
+        &self,
+        loading: &TissuesLoading<NUM_TISSUES, P>,
+        breathing_gas: &GasMix<f32>,
+        stop_depth: msw,
+        gf: f32,
+        surface_pressure: P,
+        last_deco_stop: msw,
+    ) -> Result<Duration, &'static str> {
+        compute_stop_time_exp(
+            loading,
+            &self.tissues,
+            breathing_gas,
+            stop_depth,
+            gf,
+            surface_pressure,
+            last_deco_stop,
+        )
+        .ok_or("Deco stop does not clear on this gas.")
+    }
+
+    fn first_stop_depth(
+        &self,
+        loading: &TissuesLoading<NUM_TISSUES, P>,
+        surface_pressure: P,
+        gf: f32,
+    ) -> Option<msw> {
+        first_stop_depth_exp(loading, &self.tissues, &self.m_values, surface_pressure, gf)
+    }
+}
+
+/**
+* VPM-B bubble model on ZH-L16 compartment kinetics (only the half-times of
+* `tissues` are used). Gradient factors are ignored; conservatism comes from
+* the critical radii in `settings`. The schedule builder iterates the critical
+* volume algorithm through `refined_by_ascent`.
+*/
+#[derive(Clone, Copy)]
+pub struct VpmBModel<const NUM_TISSUES: usize, P: const AbsPressure> {
+    pub tissues: [BuehlmannTissue; NUM_TISSUES],
+    pub settings: VpmSettings,
+    /// Largest crushing pressure per tissue seen during the dive.
+    pub max_crushing_pressure: [Pa; NUM_TISSUES],
+    /// Time the nuclei regenerated since they were crushed.
+    pub regeneration_time: Duration,
+    /// N2 and He gradients per tissue, initial or from the critical volume algorithm.
+    pub allowable_gradients: [(Pa, Pa); NUM_TISSUES],
+    /// Ascent time the critical volume gradients were computed for.
+    pub phase_volume_time: Option<Duration>,
+    /// First stop of the ascent, kept while the critical volume algorithm iterates.
+    pub first_stop: Option<msw>,
+    /// Ambient pressure at the first stop, set by `for_ascent_from`.
+    pub first_stop_ambient: Option<Pa>,
+    pub m_values: MValues<P, NUM_TISSUES, NUM_STOP_DEPTHS_VPM>,
+}
+
+impl<const NUM_TISSUES: usize> VpmBModel<NUM_TISSUES, Pa> {
+    pub const fn new(tissues: [BuehlmannTissue; NUM_TISSUES], settings: VpmSettings) -> Self {
+        let max_crushing_pressure = [Pa::new(0.0); NUM_TISSUES];
+        let allowable_gradients =
+            [initial_gradients_vpm(&settings, Pa::new(0.0)); NUM_TISSUES];
+        VpmBModel {
+            tissues,
+            settings,
+            max_crushing_pressure,
+            regeneration_time: Duration::ZERO,
+            allowable_gradients,
+            phase_volume_time: None,
+            first_stop: None,
+            first_stop_ambient: None,
+            m_values: tolerance_rows_vpm(&settings, &allowable_gradients),
+        }
+    }
+
+    /// Records the crushing pressure of the current descent/bottom state. Call
+    /// this alongside `update_model_state` while the dive is in progress.
+    pub fn record_crushing_pressure(
+        &mut self,
+        loading: &TissuesLoading<NUM_TISSUES, Pa>,
+        ambient: Pa,
+    ) {
+        record_crushing_pressure_vpm(
+            &mut self.max_crushing_pressure,
+            loading,
+            &self.settings,
+            ambient,
+        );
+        self.reset_gradients();
+    }
+
+    /// Lets the nuclei regenerate for `elapsed`, typically the time since leaving the surface.
+    pub fn regenerate_nuclei(&mut self, elapsed: &Duration) {
+        self.regeneration_time += *elapsed;
+        self.reset_gradients();
+    }
+
+    fn reset_gradients(&mut self) {
+        for (gradients, crushing) in self
+            .allowable_gradients
+            .iter_mut()
+            .zip(self.max_crushing_pressure)
+        {
+            *gradients =
+                regenerated_gradients_vpm(&self.settings, crushing, self.regeneration_time);
+        }
+        self.phase_volume_time = None;
+        self.m_values = tolerance_rows_vpm(&self.settings, &self.allowable_gradients);
+    }
+}
+
+impl<const NUM_TISSUES: usize, P: const AbsPressure> DecoModel<NUM_TISSUES, P>
+    for VpmBModel<NUM_TISSUES, P>
+{
+    type Tissue = BuehlmannTissue;
+
+    fn tissues(&self) -> &[BuehlmannTissue; NUM_TISSUES] {
+        &self.tissues
+    }
+
+    fn m_values(&self) -> &[TissueRow<NUM_TISSUES, P>] {
+        &self.m_values
+    }
+
+    fn with_surface_pressure(&self, surface_pressure: P) -> Self {
+        let mut result = *self;
+        surface_pressure_adjusted_mvalues(&mut result.m_values, surface_pressure);
+        result
+    }
+
+    fn for_ascent_from(&self, first_stop: msw, surface_pressure: P) -> Self {
+        let mut result = *self;
+        result.first_stop = Some(first_stop);
+        result.first_stop_ambient =
+            Some(ambient_pressure_at_depth(surface_pressure, first_stop).to_pa());
+        result
+    }
+
+    /**
+     * Critical volume algorithm: the gradients of every tissue are recomputed
+     * for bubbles growing during the ascent and the tissue's surface phase. The
+     * next ascent starts at the same first stop; the iterations end once the
+     * ascent time changes by less than a minute.
+     */
+    fn refined_by_ascent(
+        &self,
+        surfaced: &TissuesLoading<NUM_TISSUES, P>,
+        ascent_time: Duration,
+        surface_pressure: P,
+    ) -> Option<Self> {
+        if !self.settings.critical_volume_algorithm {
+            return None;
+        }
+        if let Some(previous) = self.phase_volume_time
+            && previous.abs_diff(ascent_time) < VPM_PHASE_VOLUME_TIME_TOLERANCE
+        {
+            return None;
+        }
+        let surface_phase =
+            surface_phase_volume_time_vpm(surfaced, &self.tissues, surface_pressure);
+        let mut result = *self;
+        for (i, gradients) in result.allowable_gradients.iter_mut().enumerate() {
+            *gradients = critical_volume_gradients_vpm(
+                &self.settings,
+                self.max_crushing_pressure[i],
+                self.regeneration_time,
+                ascent_time + surface_phase[i],
+            );
+        }
+        result.phase_volume_time = Some(ascent_time);
+        result.first_stop_ambient = None;
+        Some(result)
+    }
+
+    fn update_model_state(
+        &self,
+        loading: &mut TissuesLoading<NUM_TISSUES, P>,
+        breathing_gas: &GasMix<f32>,
+        current_depth: P,
+        delta_time: &Duration,
+    ) {
+        update_model_state_exp(
+            loading,
+            &self.tissues,
+            breathing_gas,
+            current_depth,
+            delta_time,
+        )
+    }
+
+    fn compute_stop_time(
+        &self,
+        loading: &TissuesLoading<NUM_TISSUES, P>,
+        breathing_gas: &GasMix<f32>,
+        stop_depth: msw,
+        _gf: f32,
+        surface_pressure: P,
+        last_deco_stop: msw,
+    ) -> Result<Duration, &'static str> {
+        Ok(compute_stop_time_vpm(
+            loading,
+            &self.tissues,
+            breathing_gas,
+            &self.settings,
+            &self.allowable_gradients,
+            self.first_stop_ambient,
+            stop_depth,
+            surface_pressure,
+            last_deco_stop,
+        ))
+    }
+
+    fn first_stop_depth(
+        &self,
+        loading: &TissuesLoading<NUM_TISSUES, P>,
+        surface_pressure: P,
+        _gf: f32,
+    ) -> Option<msw> {
+        // The critical volume iterations keep the first stop of the first ascent
+        if let (Some(first_stop), None) = (self.first_stop, self.first_stop_ambient) {
+            return Some(first_stop);
+        }
+        first_stop_depth_vpm(
+            loading,
+            &self.settings,
+            &self.allowable_gradients,
+            self.first_stop_ambient,
+            &self.m_values,
+            surface_pressure,
+        )
+    }
+}
+
+/// Thalmann model with the XVal-He-9_040 parameter set.
+pub const THALMANN_XVAL_HE9_040: ThalmannModel<NUM_TISSUES_THALMANN, Pa> = ThalmannModel::new(
+    crate::mptt_thalmann::TISSUES,
+    set_m(XVAL_HE9_040_F32_VARIABLE, 0),
+);
+
+/// Thalmann model with the VVal-18 air parameter set.
+pub const THALMANN_VVAL18: ThalmannModel<NUM_TISSUES_VVAL18, Pa> =
+    ThalmannModel::new(VVAL18_TISSUES, set_m(VVAL18_F32_VARIABLE, 0));
+
+/// Thalmann model with a VVal-family air set given by its surfacing MPTTs in fsw (see `vval_mptt`).
+pub const fn thalmann_vval(
+    surfacing_mptt_fsw: [f32; NUM_TISSUES_VVAL18],
+) -> ThalmannModel<NUM_TISSUES_VVAL18, Pa> {
+    ThalmannModel::new(VVAL18_TISSUES, set_m(vval_mptt(surfacing_mptt_fsw), 0))
+}
+
+/// Buehlmann model with the ZH-L16C coefficient set.
+pub const BUEHLMANN_ZHL16C: BuehlmannModel<NUM_TISSUES_BUEHLMANN, Pa> =
+    BuehlmannModel::new(crate::mptt_buehlmann::TISSUES, set_m(BUEHLMANN_16C, 0));
+
+/// VPM-B with Baker's default critical radii (conservatism +0).
+pub const VPM_B: VpmBModel<NUM_TISSUES_BUEHLMANN, Pa> =
+    VpmBModel::new(crate::mptt_buehlmann::TISSUES, VPM_B_DEFAULT);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        deco_algorithm::{
+            CCRSettings, DecoSettings, calc_ccr_deco_schedule, calc_deco_schedule,
+            calc_scr_deco_schedule,
+        },
+        dive::StopSchedule,
+        gas::{
+            GasDensitySettings, IcdSettings, MAX_PO2_DECO, NX50, NX100, NarcosisSettings, SCRGas,
+            SCRSupply, TMX18_45,
+        },
+        gas_policy::GasSwitchPolicy,
+        pressure_unit::{Bar, Pressure, msw},
+    };
+
+    fn bottom_loading<M: DecoModel<N, Pa>, const N: usize>(model: &M) -> TissuesLoading<N, Pa> {
+        let mut loading = TissuesLoading::new(msw::new(0.0).to_pa(), &crate::gas::AIR);
+        model.update_model_state(
+            &mut loading,
+            &TMX18_45,
+            msw::new(55.0).to_pa(),
+            &Duration::from_secs(25 * 60),
+        );
+        loading
+    }
+
+    fn deco_settings() -> DecoSettings<'static, Pa> {
+        DecoSettings {
+            gas_density_settings: GasDensitySettings::Ignore,
+            narcosis_settings: NarcosisSettings::Ignore,
+            max_deco_po2: MAX_PO2_DECO.to_pa(),
+            surface_pressure: msw::new(0.0).to_pa(),
+            icd_settings: IcdSettings::Ignore,
+            gf_low: 0.5,
+            gf_high: 0.85,
+            last_deco_stop: msw::new(3.0),
+            ascent_rate_per_meter: Duration::from_secs(6),
+            gas_switch_policy: GasSwitchPolicy::default(),
+        }
+    }
+
+    #[test]
+    fn thalmann_and_buehlmann_schedules_side_by_side() {
+        let gases = [TMX18_45, NX50, NX100];
+        let enabled = [true; 3];
+        let settings = deco_settings();
+
+        let thalmann_loading = bottom_loading(&THALMANN_XVAL_HE9_040);
+        let buehlmann_loading = bottom_loading(&BUEHLMANN_ZHL16C);
+
+        let thalmann: StopSchedule<30> = calc_deco_schedule(
+            &THALMANN_XVAL_HE9_040,
+            &thalmann_loading,
+            msw::new(55.0),
+            0,
+            &gases,
+            &enabled,
+            &settings,
+        )
+        .expect("Thalmann schedule");
+        let buehlmann: StopSchedule<30> = calc_deco_schedule(
+            &BUEHLMANN_ZHL16C,
+            &buehlmann_loading,
+            msw::new(55.0),
+            0,
+            &gases,
+            &enabled,
+            &settings,
+        )
+        .expect("Buehlmann schedule");
+
+        assert!(thalmann.first_stop().is_some());
+        assert!(buehlmann.first_stop().is_some());
+    }
+
+    #[test]
+    fn ascent_legs_are_loaded_into_the_tissues() {
+        let loading = bottom_loading(&BUEHLMANN_ZHL16C);
+        let total = |ascent_rate_per_meter: Duration| {
+            let schedule: StopSchedule<30> = calc_deco_schedule(
+                &BUEHLMANN_ZHL16C,
+                &loading,
+                msw::new(55.0),
+                0,
+                &[TMX18_45, NX50, NX100],
+                &[true; 3],
+                &DecoSettings {
+                    ascent_rate_per_meter,
+                    ..deco_settings()
+                },
+            )
+            .expect("Buehlmann schedule");
+            schedule
+                .stops()
+                .iter()
+                .map(|s| s.duration())
+                .sum::<Duration>()
+        };
+
+        let instant = total(Duration::ZERO);
+        let nine_m_min = total(Duration::from_secs(6));
+        let three_m_min = total(Duration::from_secs(20));
+        std::println!(
+            "Stops: instant {instant:?}, 9 m/min {nine_m_min:?}, 3 m/min {three_m_min:?}"
+        );
+        // The slow tissues keep taking up gas on the way to and between the stops
+        assert!(instant < nine_m_min && nine_m_min < three_m_min);
+    }
+
+    fn vpm_schedule(model: &VpmBModel<NUM_TISSUES_BUEHLMANN, Pa>) -> StopSchedule<30> {
+        let mut model = *model;
+        let loading = bottom_loading(&model);
+        model.record_crushing_pressure(&loading, msw::new(55.0).to_pa());
+        model.regenerate_nuclei(&Duration::from_secs(25 * 60));
+        calc_deco_schedule(
+            &model,
+            &loading,
+            msw::new(55.0),
+            0,
+            &[TMX18_45, NX50, NX100],
+            &[true; 3],
+            &deco_settings(),
+        )
+        .expect("VPM-B schedule")
+    }
+
+    fn vpm_total_deco(model: &VpmBModel<NUM_TISSUES_BUEHLMANN, Pa>) -> Duration {
+        let schedule = vpm_schedule(model);
+        assert!(schedule.first_stop().is_some());
+        schedule.stops().iter().map(|s| s.duration()).sum()
+    }
+
+    #[test]
+    fn vpm_b_critical_volume_shortens_the_initial_schedule() {
+        let initial = VpmBModel::new(
+            crate::mptt_buehlmann::TISSUES,
+            VpmSettings {
+                critical_volume_algorithm: false,
+                ..VPM_B_DEFAULT
+            },
+        );
+        let with_cva = vpm_schedule(&VPM_B);
+        let without_cva = vpm_schedule(&initial);
+        let total =
+            |s: &StopSchedule<30>| -> Duration { s.stops().iter().map(|s| s.duration()).sum() };
+        let first = |s: &StopSchedule<30>| s.first_stop().map(|s| s.depth());
+        std::println!(
+            "VPM-B initial gradients {:?} from {:?}, critical volume {:?} from {:?}",
+            total(&without_cva),
+            first(&without_cva),
+            total(&with_cva),
+            first(&with_cva)
+        );
+        assert!(total(&with_cva) < total(&without_cva));
+        // The ascent starts at the same first stop, which the relaxed gradients may clear
+        assert!(first(&with_cva) <= first(&without_cva));
+    }
+
+    #[test]
+    fn vpm_b_first_stop_is_never_the_surface() {
+        let surface = msw::new(0.0).to_pa();
+        // Air saturation at 10 m only exceeds the surface tolerance
+        let loading: TissuesLoading<NUM_TISSUES_BUEHLMANN, Pa> =
+            TissuesLoading::new(msw::new(10.0).to_pa(), &crate::gas::AIR);
+        assert_eq!(
+            VPM_B.first_stop_depth(&loading, surface, 1.0),
+            Some(msw::new(3.0))
+        );
+    }
+
+    #[test]
+    fn vpm_b_schedule_grows_with_conservatism() {
+        let plus0 = vpm_total_deco(&VPM_B);
+        let plus4 = vpm_total_deco(&VpmBModel::new(
+            crate::mptt_buehlmann::TISSUES,
+            VPM_B_DEFAULT.with_conservatism(4),
+        ));
+        std::println!("VPM-B +0: {plus0:?}, +4: {plus4:?}");
+        assert!(plus4 > plus0);
+    }
+
+    #[test]
+    fn vval18_air_schedule_has_stops_for_long_air_dive() {
+        // Checked against published air limits in validation.rs
+        let mut loading = TissuesLoading::new(msw::new(0.0).to_pa(), &crate::gas::AIR);
+        THALMANN_VVAL18.update_model_state(
+            &mut loading,
+            &crate::gas::AIR,
+            msw::new(30.0).to_pa(),
+            &Duration::from_secs(60 * 60),
+        );
+        let schedule: StopSchedule<30> = calc_deco_schedule(
+            &THALMANN_VVAL18,
+            &loading,
+            msw::new(30.0),
+            0,
+            &[crate::gas::AIR],
+            &[true],
+            &deco_settings(),
+        )
+        .expect("VVal-18 schedule");
+        let first = schedule.first_stop().expect("decompression dive");
+        let total: Duration = schedule.stops().iter().map(|s| s.duration()).sum();
+        std::println!("VVal-18 30 m / 60 min air first stop {:?}, total {total:?}", first.depth());
+        assert!(!total.is_zero());
+    }
+
+    #[test]
+    fn vval_sets_differ_only_in_their_mptts() {
+        let air_total = |model: &ThalmannModel<NUM_TISSUES_VVAL18, Pa>| {
+            let mut loading = TissuesLoading::new(msw::new(0.0).to_pa(), &crate::gas::AIR);
+            model.update_model_state(
+                &mut loading,
+                &crate::gas::AIR,
+                msw::new(30.0).to_pa(),
+                &Duration::from_secs(60 * 60),
+            );
+            let schedule: StopSchedule<30> = calc_deco_schedule(
+                model,
+                &loading,
+                msw::new(30.0),
+                0,
+                &[crate::gas::AIR],
+                &[true],
+                &deco_settings(),
+            )
+            .expect("VVal schedule");
+            schedule
+                .stops()
+                .iter()
+                .map(|s| s.duration())
+                .sum::<Duration>()
+        };
+        let vval18 = thalmann_vval([120.0, 93.0, 76.0, 58.0, 55.0, 51.2, 50.0, 50.0, 50.0]);
+        let stricter = thalmann_vval([115.0, 88.0, 71.0, 53.0, 50.0, 46.2, 45.0, 45.0, 45.0]);
+        assert_eq!(air_total(&vval18), air_total(&THALMANN_VVAL18));
+        assert!(air_total(&stricter) > air_total(&vval18));
+    }
+
+    #[test]
+    fn thalmann_settings_move_the_crossover() {
+        let gases = [TMX18_45, NX50, NX100];
+        let enabled = [true; 3];
+        let settings = deco_settings();
+        let loading = bottom_loading(&THALMANN_XVAL_HE9_040);
+        let total = |model: &ThalmannModel<NUM_TISSUES_THALMANN, Pa>| {
+            let schedule: StopSchedule<30> =
+                calc_deco_schedule(model, &loading, msw::new(55.0), 0, &gases, &enabled, &settings)
+                    .expect("Thalmann schedule");
+            schedule
+                .stops()
+                .iter()
+                .map(|s| s.duration())
+                .sum::<Duration>()
+        };
+
+        let default = total(&THALMANN_XVAL_HE9_040);
+        let with_pbovp = total(
+            &THALMANN_XVAL_HE9_040.with_settings(ThalmannSettings::from_fsw(2.0, 2.3, 1.0, 5.0)),
+        );
+        std::println!("Thalmann default {default:?}, PH2O 1 fsw / PBOVP 5 fsw {with_pbovp:?}");
+        assert_ne!(default, with_pbovp);
+    }
+
+    #[test]
+    fn thalmann_helium_kinetics_clear_the_stop() {
+        let he = crate::mptt_thalmann::TISSUES.map(|t| Tissue {
+            half_time: t.half_time / 2.65,
+            sdr: t.sdr,
+        });
+        let model = THALMANN_XVAL_HE9_040.with_helium_kinetics(he);
+        let surface = msw::new(0.0).to_pa();
+        let mut loading = bottom_loading(&model);
+        let stop = model
+            .first_stop_depth(&loading, surface, 1.0)
+            .expect("deco dive");
+
+        let duration = model
+            .compute_stop_time(&loading, &NX50, stop, 1.0, surface, msw::new(3.0))
+            .unwrap();
+        let uniform = THALMANN_XVAL_HE9_040
+            .compute_stop_time(
+                &bottom_loading(&THALMANN_XVAL_HE9_040),
+                &NX50,
+                stop,
+                1.0,
+                surface,
+                msw::new(3.0),
+            )
+            .unwrap();
+        assert!(!duration.is_zero());
+        assert_ne!(duration, uniform);
+
+        model.update_model_state(
+            &mut loading,
+            &NX50,
+            ambient_pressure_at_depth(surface, stop),
+            &duration,
+        );
+        let next = model.first_stop_depth(&loading, surface, 1.0);
+        assert!(
+            next.is_none_or(|d| d.to_f32() < stop.to_f32()),
+            "{next:?} after {stop:?}"
+        );
+    }
+
+    #[test]
+    fn buehlmann_coefficient_sets_order_by_conservatism() {
+        let surface = msw::new(0.0).to_pa();
+        let first_stop = |coefficients: BuehlmannCoefficients| {
+            let model = BuehlmannModel::from_coefficients(coefficients);
+            model
+                .first_stop_depth(&bottom_loading(&model), surface, 0.5)
+                .map_or(0.0, |d| d.to_f32())
+        };
+
+        let a = first_stop(BuehlmannCoefficients::ZhL16A);
+        let b = first_stop(BuehlmannCoefficients::ZhL16B);
+        let c = first_stop(BuehlmannCoefficients::ZhL16C);
+        let zhl12 = first_stop(BuehlmannCoefficients::ZhL12);
+        std::println!("First stops: ZH-L16A {a}, ZH-L16B {b}, ZH-L16C {c}, ZH-L12 {zhl12} msw");
+        assert!(a <= b && b <= c);
+        assert!(a > 0.0 && zhl12 > 0.0);
+    }
+
+    fn ccr_total_deco(ccr: &CCRSettings<Pa>) -> (StopSchedule<30>, Duration) {
+        let bottom = msw::new(55.0);
+        let mut loading = TissuesLoading::new(msw::new(0.0).to_pa(), &crate::gas::AIR);
+        BUEHLMANN_ZHL16C.update_model_state_loop(
+            &mut loading,
+            &ccr.loop_gas(&TMX18_45, bottom, false),
+            bottom.to_pa(),
+            &Duration::from_secs(25 * 60),
+        );
+        let schedule: StopSchedule<30> = calc_ccr_deco_schedule(
+            &BUEHLMANN_ZHL16C,
+            &loading,
+            bottom,
+            0,
+            ccr,
+            &[TMX18_45],
+            &[true],
+            &deco_settings(),
+        )
+        .expect("CCR schedule");
+        let total = schedule.stops().iter().map(|s| s.duration()).sum();
+        (schedule, total)
+    }
+
+    #[test]
+    fn ccr_schedule_holds_the_set_point() {
+        let high_to_surface = CCRSettings {
+            low_set_point: Bar::new(0.7).to_pa(),
+            high_set_point: Bar::new(1.3).to_pa(),
+            switch_up_depth: msw::new(10.0),
+            switch_down_depth: msw::new(0.0),
+        };
+        let (schedule, ccr_total) = ccr_total_deco(&high_to_surface);
+        let first = schedule.first_stop().expect("CCR dive needs stops");
+        let po2 = first.gas().expect("stop gas").fo2() * first.depth().to_pa().to_bar().to_f32();
+        assert!((po2 - 1.3).abs() < 0.01, "{po2} bar at the first stop");
+
+        let oc: StopSchedule<30> = calc_deco_schedule(
+            &BUEHLMANN_ZHL16C,
+            &bottom_loading(&BUEHLMANN_ZHL16C),
+            msw::new(55.0),
+            0,
+            &[TMX18_45],
+            &[true],
+            &deco_settings(),
+        )
+        .expect("OC schedule");
+        let oc_total: Duration = oc.stops().iter().map(|s| s.duration()).sum();
+        assert!(ccr_total < oc_total, "{ccr_total:?} vs {oc_total:?}");
+
+        let (_, low_shallow_total) = ccr_total_deco(&CCRSettings {
+            switch_down_depth: msw::new(9.0),
+            ..high_to_surface
+        });
+        assert!(ccr_total < low_shallow_total);
+    }
+
+    #[test]
+    fn scr_schedule_is_longer_than_open_circuit_on_the_supply() {
+        let scr = SCRGas {
+            supply_gas: TMX18_45,
+            supply: SCRSupply::Active { flow_l_min: 12.0 },
+            o2_consumption_l_min: 1.0,
+        };
+        let mut loading = TissuesLoading::new(msw::new(0.0).to_pa(), &crate::gas::AIR);
+        BUEHLMANN_ZHL16C.update_model_state_loop(
+            &mut loading,
+            &scr,
+            msw::new(55.0).to_pa(),
+            &Duration::from_secs(25 * 60),
+        );
+        let deco_loop = SCRGas {
+            supply_gas: NX50,
+            ..scr
+        };
+        let semi_closed: StopSchedule<30> = calc_scr_deco_schedule(
+            &BUEHLMANN_ZHL16C,
+            &loading,
+            msw::new(55.0),
+            0,
+            &[scr, deco_loop],
+            &[true; 2],
+            &deco_settings(),
+        )
+        .expect("SCR schedule");
+        let open_circuit: StopSchedule<30> = calc_deco_schedule(
+            &BUEHLMANN_ZHL16C,
+            &bottom_loading(&BUEHLMANN_ZHL16C),
+            msw::new(55.0),
+            0,
+            &[TMX18_45, NX50],
+            &[true; 2],
+            &deco_settings(),
+        )
+        .expect("OC schedule");
+
+        let total =
+            |s: &StopSchedule<30>| -> Duration { s.stops().iter().map(|s| s.duration()).sum() };
+        assert!(total(&semi_closed) > total(&open_circuit));
+        let shallowest = semi_closed
+            .stops()
+            .iter()
+            .rev()
+            .find(|s| !s.duration().is_zero());
+        let loop_fo2 = shallowest.and_then(|s| s.gas()).expect("stop gas").fo2();
+        assert!((loop_fo2 - deco_loop.fio2(msw::new(3.0).to_pa())).abs() < 1e-5);
+    }
+
+    #[test]
+    fn first_stop_depth_is_none_at_surface_saturation() {
+        let surface = msw::new(0.0).to_pa();
+        let thalmann_loading: TissuesLoading<NUM_TISSUES_THALMANN, Pa> =
+            TissuesLoading::new(surface, &crate::gas::AIR);
+        let buehlmann_loading: TissuesLoading<NUM_TISSUES_BUEHLMANN, Pa> =
+            TissuesLoading::new(surface, &crate::gas::AIR);
+
+        assert_eq!(
+            THALMANN_XVAL_HE9_040.first_stop_depth(&thalmann_loading, surface, 1.0),
+            None
+        );
+        assert_eq!(
+            BUEHLMANN_ZHL16C.first_stop_depth(&buehlmann_loading, surface, 1.0),
+            None
+        );
+        assert_eq!(VPM_B.first_stop_depth(&buehlmann_loading, surface, 1.0), None);
+    }
+}
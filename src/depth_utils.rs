@@ -21,6 +21,23 @@ pub fn get_depth_idx<P: Pressure>(d: P) -> usize {
     (d.to_msw().to_f32() / DINC.to_msw().to_f32()).ceil() as usize
 }
 
+/// Fraction of a grid step below which a depth still counts as on the grid.
+const GRID_ROUNDING: f32 = 1E-4;
+
+/// Depth of grid index `d_idx` on a stop grid spaced by `increment`.
+pub fn get_grid_depth(d_idx: usize, increment: msw) -> msw {
+    msw::new(increment.to_f32() * d_idx as f32)
+}
+
+/**
+* Index of the shallowest grid depth at or below `d` on a stop grid spaced by
+* `increment`. Depths within rounding of a grid depth map onto it.
+*/
+pub fn get_grid_depth_idx(d: msw, increment: msw) -> usize {
+    assert!(d.to_f32() >= 0.0 && increment.to_f32() > 0.0);
+    (d.to_f32() / increment.to_f32() - GRID_ROUNDING).ceil().max(0.0) as usize
+}
+
 pub fn get_ascent_rate_per_meter(meters: u64) -> Duration {
     Duration::new(
         60 / meters,
@@ -47,6 +64,15 @@ mod tests {
         }
     }
 
+    #[test]
+    fn get_grid_depth_idx_test() {
+        let ten_fsw = msw::new(3.048);
+        for i in 0..50 {
+            assert_eq!(get_grid_depth_idx(get_grid_depth(i, ten_fsw), ten_fsw), i);
+        }
+        assert_eq!(get_grid_depth_idx(msw::new(7.0), ten_fsw), 3);
+    }
+
     #[test]
     fn get_ascent_time_test() {
         assert_eq!(
@@ -0,0 +1,402 @@
+use core::time::Duration;
+
+use crate::{
+    deco_algorithm::{DecoSettings, calc_deco_schedule},
+    deco_model::DecoModel,
+    dive::StopSchedule,
+    gas::{Gas, GasMix, TissuesLoading},
+    o2tox::{O2ExposureType, O2ToxCalculation, exposure_percent, otu_per_minute},
+    pressure_unit::{Pa, Pressure, ambient_pressure_at_depth, msw},
+};
+
+/// Level of a plan: `gases[gas]` is breathed on the way to `depth` and for `duration` there.
+#[derive(Debug, Clone, Copy)]
+pub struct PlanSegment {
+    pub depth: msw,
+    /// Time at `depth`, without the travel to it. Zero for a travel gas switch.
+    pub duration: Duration,
+    pub gas: usize,
+}
+
+/** Planned levels of an open-circuit dive; the ascent is planned with `calc_deco_schedule`. */
+pub struct DivePlan<'a, const NUM_GASES: usize> {
+    pub segments: &'a [PlanSegment],
+    pub gases: [GasMix<f32>; NUM_GASES],
+    /// Gases the deco schedule may switch to.
+    pub gases_enabled: [bool; NUM_GASES],
+    pub descent_rate_per_meter: Duration,
+    /// Between levels; the final ascent uses the rate of the `DecoSettings`.
+    pub ascent_rate_per_meter: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LegKind {
+    Descent,
+    Ascent,
+    Level,
+    Stop,
+}
+
+/** Row of the runtime table. TTS, CNS and OTU are taken at the end of the leg. */
+#[derive(Debug, Clone, Copy)]
+pub struct RuntimeLeg {
+    pub kind: LegKind,
+    pub start: Duration,
+    pub end: Duration,
+    pub start_depth: msw,
+    pub depth: msw,
+    pub gas: GasMix<f32>,
+    /// Time spent at `depth`; zero for descents and ascents.
+    pub stop_time: Duration,
+    pub tts: Duration,
+    pub cns_percent: f32,
+    pub otu: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct RuntimeTable<const NUM_LEGS: usize, const NUM_STOPS: usize> {
+    legs: [Option<RuntimeLeg>; NUM_LEGS],
+    /// Deco stops of the final ascent.
+    pub schedule: StopSchedule<NUM_STOPS>,
+}
+
+impl<const NUM_LEGS: usize, const NUM_STOPS: usize> RuntimeTable<NUM_LEGS, NUM_STOPS> {
+    pub fn legs(&self) -> impl Iterator<Item = &RuntimeLeg> {
+        self.legs.iter().map_while(|l| l.as_ref())
+    }
+
+    pub fn runtime(&self) -> Duration {
+        self.legs().last().map_or(Duration::ZERO, |l| l.end)
+    }
+}
+
+/** Appends legs and keeps runtime, CNS and OTU running. */
+struct TableBuilder<'a, const NUM_LEGS: usize> {
+    legs: [Option<RuntimeLeg>; NUM_LEGS],
+    num_legs: usize,
+    surface_pressure: Pa,
+    exposure_type: &'a O2ExposureType,
+    calculation_method: &'a O2ToxCalculation,
+}
+
+impl<const NUM_LEGS: usize> TableBuilder<'_, NUM_LEGS> {
+    fn last(&self) -> Option<&RuntimeLeg> {
+        self.legs[..self.num_legs].last().and_then(|l| l.as_ref())
+    }
+
+    fn depth(&self) -> msw {
+        self.last().map_or(msw::new(0.0), |l| l.depth)
+    }
+
+    /// Breathes `gas` from the current depth to `to` for `duration` and adds the leg.
+    fn breathe<M, const NUM_TISSUES: usize>(
+        &mut self,
+        model: &M,
+        loading: &mut TissuesLoading<NUM_TISSUES, Pa>,
+        kind: LegKind,
+        gas: &GasMix<f32>,
+        to: msw,
+        duration: Duration,
+    ) -> Result<&mut RuntimeLeg, &'static str>
+    where
+        M: DecoModel<NUM_TISSUES, Pa>,
+    {
+        let ambient = |depth: msw| ambient_pressure_at_depth(self.surface_pressure, depth);
+        let from = self.depth();
+        model.update_model_state_travel(loading, gas, ambient(from), ambient(to), &duration);
+
+        let po2 = gas
+            .po2(ambient(msw::new(0.5 * (from.to_f32() + to.to_f32()))))
+            .to_bar();
+        let limit = self.calculation_method.limit(self.exposure_type, po2);
+        let (start, cns_percent, otu) = self.last().map_or((Duration::ZERO, 0.0, 0.0), |l| {
+            (l.end, l.cns_percent, l.otu)
+        });
+        let leg = self
+            .legs
+            .get_mut(self.num_legs)
+            .ok_or("Runtime table has too few legs for the plan.")?;
+        self.num_legs += 1;
+        Ok(leg.insert(RuntimeLeg {
+            kind,
+            start,
+            end: start + duration,
+            start_depth: from,
+            depth: to,
+            gas: *gas,
+            stop_time: match kind {
+                LegKind::Level | LegKind::Stop => duration,
+                LegKind::Descent | LegKind::Ascent => Duration::ZERO,
+            },
+            tts: Duration::ZERO,
+            cns_percent: cns_percent + exposure_percent(limit, duration),
+            otu: otu + otu_per_minute(po2) * duration.as_secs_f32() / 60.0,
+        }))
+    }
+}
+
+impl<const NUM_GASES: usize> DivePlan<'_, NUM_GASES> {
+    /**
+     * Runs the segments through `model` from `loading` at the surface, then
+     * ascends through the stops `calc_deco_schedule` gives at the end of the
+     * last segment. TTS during the segments is the stop time of the schedule
+     * from the end of the leg, ascent legs included.
+     */
+    pub fn runtime_table<
+        M,
+        const NUM_TISSUES: usize,
+        const NUM_STOPS: usize,
+        const NUM_LEGS: usize,
+    >(
+        &self,
+        model: &M,
+        loading: &TissuesLoading<NUM_TISSUES, Pa>,
+        deco_settings: &DecoSettings<Pa>,
+        exposure_type: &O2ExposureType,
+        calculation_method: &O2ToxCalculation,
+    ) -> Result<RuntimeTable<NUM_LEGS, NUM_STOPS>, &'static str>
+    where
+        M: DecoModel<NUM_TISSUES, Pa>,
+    {
+        if self.segments.is_empty() {
+            return Err("A plan needs at least one segment.");
+        }
+        if self.segments.iter().any(|s| s.gas >= NUM_GASES) {
+            return Err("Segment gas is not among the planned gases.");
+        }
+        let schedule = |loading: &TissuesLoading<NUM_TISSUES, Pa>, depth: msw, gas: usize| {
+            calc_deco_schedule::<M, NUM_TISSUES, NUM_STOPS, NUM_GASES>(
+                model,
+                loading,
+                depth,
+                gas,
+                &self.gases,
+                &self.gases_enabled,
+                deco_settings,
+            )
+        };
+        let ascent = |rate: Duration, from: msw, to: msw| {
+            rate.mul_f32((from.to_f32() - to.to_f32()).max(0.0))
+        };
+        let deco_ascent =
+            |from: msw, to: msw| ascent(deco_settings.ascent_rate_per_meter, from, to);
+
+        let mut loading = loading.clone();
+        let mut table = TableBuilder {
+            legs: [None; NUM_LEGS],
+            num_legs: 0,
+            surface_pressure: deco_settings.surface_pressure,
+            exposure_type,
+            calculation_method,
+        };
+        for segment in self.segments {
+            let gas = &self.gases[segment.gas];
+            let from = table.depth();
+            let mut legs = [None, None];
+            if segment.depth > from {
+                let travel = self
+                    .descent_rate_per_meter
+                    .mul_f32(segment.depth.to_f32() - from.to_f32());
+                legs[0] = Some((LegKind::Descent, travel));
+            } else if segment.depth < from {
+                let travel = ascent(self.ascent_rate_per_meter, from, segment.depth);
+                legs[0] = Some((LegKind::Ascent, travel));
+            }
+            if !segment.duration.is_zero() {
+                legs[1] = Some((LegKind::Level, segment.duration));
+            }
+            for (kind, duration) in legs.into_iter().flatten() {
+                let leg = table.breathe(model, &mut loading, kind, gas, segment.depth, duration)?;
+                let stops: Duration = schedule(&loading, segment.depth, segment.gas)?
+                    .stops()
+                    .iter()
+                    .map(|s| s.duration())
+                    .sum();
+                leg.tts = stops + deco_ascent(segment.depth, msw::new(0.0));
+            }
+        }
+
+        let last_segment = self.segments[self.segments.len() - 1];
+        let deco = schedule(&loading, table.depth(), last_segment.gas)?;
+        let first_ascent_leg = table.num_legs;
+        let mut gas = table
+            .last()
+            .map(|l| l.gas)
+            .ok_or("A plan needs at least one leg.")?;
+        for stop in deco.stops().iter().filter(|s| !s.duration().is_zero()) {
+            let leg = deco_ascent(table.depth(), stop.depth());
+            table.breathe(
+                model,
+                &mut loading,
+                LegKind::Ascent,
+                &gas,
+                stop.depth(),
+                leg,
+            )?;
+            gas = stop.gas().ok_or("Stop without a gas.")?;
+            table.breathe(
+                model,
+                &mut loading,
+                LegKind::Stop,
+                &gas,
+                stop.depth(),
+                stop.duration(),
+            )?;
+        }
+        let leg = deco_ascent(table.depth(), msw::new(0.0));
+        table.breathe(
+            model,
+            &mut loading,
+            LegKind::Ascent,
+            &gas,
+            msw::new(0.0),
+            leg,
+        )?;
+
+        let runtime = table.last().map_or(Duration::ZERO, |l| l.end);
+        for leg in table.legs[first_ascent_leg..table.num_legs]
+            .iter_mut()
+            .flatten()
+        {
+            leg.tts = runtime - leg.end;
+        }
+        Ok(RuntimeTable {
+            legs: table.legs,
+            schedule: deco,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        deco_model::BUEHLMANN_ZHL16C,
+        gas::{
+            AIR, GasDensitySettings, IcdSettings, MAX_PO2_DECO, NX50, NX100, NarcosisSettings,
+            TMX18_45,
+        },
+        gas_policy::GasSwitchPolicy,
+    };
+
+    const GASES: [GasMix<f32>; 4] = [AIR, TMX18_45, NX50, NX100];
+
+    fn deco_settings() -> DecoSettings<'static, Pa> {
+        DecoSettings {
+            gas_density_settings: GasDensitySettings::Ignore,
+            narcosis_settings: NarcosisSettings::Ignore,
+            icd_settings: IcdSettings::Ignore,
+            max_deco_po2: MAX_PO2_DECO.to_pa(),
+            surface_pressure: msw::new(0.0).to_pa(),
+            gf_low: 0.5,
+            gf_high: 0.85,
+            last_deco_stop: msw::new(3.0),
+            ascent_rate_per_meter: Duration::from_secs(6),
+            gas_switch_policy: GasSwitchPolicy::default(),
+        }
+    }
+
+    fn plan(segments: &[PlanSegment]) -> DivePlan<'_, 4> {
+        DivePlan {
+            segments,
+            gases: GASES,
+            // Air is only a travel gas
+            gases_enabled: [false, true, true, true],
+            descent_rate_per_meter: Duration::from_secs(3),
+            ascent_rate_per_meter: Duration::from_secs(6),
+        }
+    }
+
+    fn segment(depth: f32, minutes: u64, gas: usize) -> PlanSegment {
+        PlanSegment {
+            depth: msw::new(depth),
+            duration: Duration::from_secs(minutes * 60),
+            gas,
+        }
+    }
+
+    fn runtime_table(segments: &[PlanSegment]) -> Result<RuntimeTable<32, 30>, &'static str> {
+        plan(segments).runtime_table(
+            &BUEHLMANN_ZHL16C,
+            &TissuesLoading::new(msw::new(0.0).to_pa(), &AIR),
+            &deco_settings(),
+            &O2ExposureType::Single,
+            &O2ToxCalculation::NOAA,
+        )
+    }
+
+    #[test]
+    fn multi_level_trimix_dive() {
+        // Air to 21 m, trimix to 60 m, then a second level at 40 m
+        let segments = [
+            segment(21.0, 0, 0),
+            segment(60.0, 20, 1),
+            segment(40.0, 10, 1),
+        ];
+        let table = runtime_table(&segments).expect("runtime table");
+
+        let legs: [&RuntimeLeg; 5] = core::array::from_fn(|i| table.legs().nth(i).unwrap());
+        let kinds = legs.map(|l| l.kind);
+        assert_eq!(
+            kinds,
+            [
+                LegKind::Descent,
+                LegKind::Descent,
+                LegKind::Level,
+                LegKind::Ascent,
+                LegKind::Level
+            ]
+        );
+        assert_eq!(legs[0].gas, AIR);
+        assert_eq!(legs[0].end, Duration::from_secs(63));
+        assert_eq!(legs[1].gas, TMX18_45);
+        assert_eq!(legs[2].stop_time, Duration::from_secs(20 * 60));
+        // Air at a mean 0.43 bar PO2: neither CNS nor OTU below 0.5 bar
+        assert_eq!(legs[0].cns_percent, 0.0);
+        assert_eq!(legs[0].otu, 0.0);
+        // 18/45 at 60 m is 1.27 bar PO2: NOAA 180 min and 1.48 OTU per minute
+        let cns = legs[2].cns_percent - legs[1].cns_percent;
+        let otu = legs[2].otu - legs[1].otu;
+        assert!((cns - 20.0 / 180.0 * 100.0).abs() < 1e-3, "{cns}");
+        assert!((otu - 20.0 * 1.48).abs() < 1e-3, "{otu}");
+
+        let mut previous: Option<&RuntimeLeg> = None;
+        for leg in table.legs() {
+            if let Some(p) = previous {
+                assert_eq!(leg.start, p.end);
+                assert_eq!(leg.start_depth, p.depth);
+                assert!(leg.cns_percent >= p.cns_percent && leg.otu >= p.otu);
+            }
+            previous = Some(leg);
+        }
+        let last = previous.expect("legs");
+        assert_eq!(last.depth, msw::new(0.0));
+        assert_eq!(last.tts, Duration::ZERO);
+        assert_eq!(last.end, table.runtime());
+        // The TTS at the end of the bottom is the ascent that follows
+        assert!(
+            (legs[4].tts.as_secs_f32() - (table.runtime() - legs[4].end).as_secs_f32()).abs() < 1.0
+        );
+
+        let stops = || table.legs().filter(|l| l.kind == LegKind::Stop);
+        assert!(stops().any(|l| l.gas == NX100));
+        let stop_time: Duration = stops().map(|l| l.stop_time).sum();
+        let scheduled: Duration = table.schedule.stops().iter().map(|s| s.duration()).sum();
+        assert_eq!(stop_time, scheduled);
+    }
+
+    #[test]
+    fn invalid_plans_are_rejected() {
+        assert!(runtime_table(&[]).is_err());
+        assert!(runtime_table(&[segment(30.0, 20, 4)]).is_err());
+        let too_small: Result<RuntimeTable<3, 30>, _> = plan(&[segment(60.0, 20, 1)])
+            .runtime_table(
+                &BUEHLMANN_ZHL16C,
+                &TissuesLoading::new(msw::new(0.0).to_pa(), &AIR),
+                &deco_settings(),
+                &O2ExposureType::Single,
+                &O2ToxCalculation::NOAA,
+            );
+        assert!(too_small.is_err());
+    }
+}
@@ -1,25 +1,89 @@
-use core::iter::zip;
-
 #[allow(unused)]
 use num::Float;
 
-use crate::pressure_unit::{AbsPressure, Bar, Pressure};
+use crate::icd::switch_meets_rule_of_fifths;
+use crate::pressure_unit::{AbsPressure, Bar, Pa, Pressure, depth_at_ambient_pressure, msw};
 
 pub const N2_IDX: usize = 0;
 pub const HE_IDX: usize = 1;
+pub const H2_IDX: usize = 2;
+pub const NE_IDX: usize = 3;
+pub const AR_IDX: usize = 4;
+pub const NUM_INERT_GASES: usize = 5;
 
 pub const MAX_PO2_CCR_WORKING: Bar = Bar::new(1.3);
 pub const MAX_PO2_WORKING: Bar = Bar::new(1.4);
 pub const MAX_O2_CCR_DECO: Bar = Bar::new(1.5);
 pub const MAX_PO2_DECO: Bar = Bar::new(1.6);
 pub const MAX_O2_DILUENT: Bar = Bar::new(1.1);
+/// Lowest ppO2 a gas is breathed at unless a plan sets its own floor.
+pub const MIN_PO2_HYPOXIC: Bar = Bar::new(0.16);
 
 pub const DENSITY_O2: f32 = 1.43;
 pub const DENSITY_N2: f32 = 1.2506;
 pub const DENSITY_HE: f32 = 0.1785;
 pub const DENSITY_H2: f32 = 0.0899;
+pub const DENSITY_NE: f32 = 0.9002;
+pub const DENSITY_AR: f32 = 1.784;
 pub const DENSITY_AIR: f32 = 1.205;
 
+/** Inert gases tracked in the tissues, indexed by the `*_IDX` constants. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InertGas {
+    N2,
+    He,
+    H2,
+    Ne,
+    Ar,
+}
+
+impl InertGas {
+    pub const ALL: [InertGas; NUM_INERT_GASES] = [
+        InertGas::N2,
+        InertGas::He,
+        InertGas::H2,
+        InertGas::Ne,
+        InertGas::Ar,
+    ];
+
+    pub const fn idx(self) -> usize {
+        match self {
+            InertGas::N2 => N2_IDX,
+            InertGas::He => HE_IDX,
+            InertGas::H2 => H2_IDX,
+            InertGas::Ne => NE_IDX,
+            InertGas::Ar => AR_IDX,
+        }
+    }
+
+    /// Molar mass in g/mol.
+    pub const fn molar_mass(self) -> f32 {
+        match self {
+            InertGas::N2 => 28.014,
+            InertGas::He => 4.0026,
+            InertGas::H2 => 2.016,
+            InertGas::Ne => 20.180,
+            InertGas::Ar => 39.948,
+        }
+    }
+
+    /**
+     * Gas with published kinetics and M-values this one is derived from: He for
+     * the light H2, N2 for the heavier Ne and Ar.
+     */
+    pub const fn reference(self) -> InertGas {
+        match self {
+            InertGas::N2 | InertGas::Ne | InertGas::Ar => InertGas::N2,
+            InertGas::He | InertGas::H2 => InertGas::He,
+        }
+    }
+
+    /// Half-time relative to `reference()` by Graham's law, sqrt(M / M_reference).
+    pub fn half_time_factor(self) -> f32 {
+        (self.molar_mass() / self.reference().molar_mass()).sqrt()
+    }
+}
+
 #[allow(non_camel_case_types)]
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub struct gL(f32);
@@ -75,6 +139,24 @@ pub const trait Gas {
     fn ph2<D: const AbsPressure>(&self, depth: D) -> D;
     fn pn2_phe_ph2<D: const AbsPressure>(&self, depth: D) -> (D, D, D);
 
+    fn pne<D: const AbsPressure>(&self, depth: D) -> D {
+        depth * 0.0
+    }
+
+    fn par<D: const AbsPressure>(&self, depth: D) -> D {
+        depth * 0.0
+    }
+
+    fn p_inert<D: const AbsPressure>(&self, gas: InertGas, depth: D) -> D {
+        match gas {
+            InertGas::N2 => self.pn2(depth),
+            InertGas::He => self.phe(depth),
+            InertGas::H2 => self.ph2(depth),
+            InertGas::Ne => self.pne(depth),
+            InertGas::Ar => self.par(depth),
+        }
+    }
+
     fn o2_density<P: const AbsPressure>(&self, depth: P) -> gL {
         gL::new(self.po2(depth).to_bar().to_f32() * DENSITY_O2)
     }
@@ -91,16 +173,54 @@ pub const trait Gas {
         gL::new(self.ph2(depth).to_bar().to_f32() * DENSITY_H2)
     }
 
+    fn ne_density<P: const AbsPressure>(&self, depth: P) -> gL {
+        gL::new(self.pne(depth).to_bar().to_f32() * DENSITY_NE)
+    }
+
+    fn ar_density<P: const AbsPressure>(&self, depth: P) -> gL {
+        gL::new(self.par(depth).to_bar().to_f32() * DENSITY_AR)
+    }
+
     fn gas_density<P: const AbsPressure>(&self, depth: P) -> gL {
         self.o2_density(depth)
             + self.n2_density(depth)
             + self.he_density(depth)
             + self.h2_density(depth)
+            + self.ne_density(depth)
+            + self.ar_density(depth)
     }
 
     fn fio2<D: const AbsPressure>(&self, depth: D) -> f32 {
         self.po2(depth) / depth
     }
+
+    /// Ambient pressure at which air has the N2 partial pressure of this gas at `depth`.
+    fn ead<D: const AbsPressure>(&self, depth: D) -> D {
+        self.pn2(depth) * (1.0 / AIR.fn2())
+    }
+
+    /**
+     * Ambient pressure at which air is as narcotic as this gas at `depth`. He is
+     * not narcotic; O2 counts like N2 if `o2_narcotic` is set.
+     */
+    fn end<D: const AbsPressure>(&self, depth: D, o2_narcotic: bool) -> D {
+        if o2_narcotic {
+            (self.pn2(depth) + self.po2(depth)) * (1.0 / (AIR.fn2() + AIR.fo2()))
+        } else {
+            self.ead(depth)
+        }
+    }
+
+    /// Open-circuit mix breathed at `depth`, e.g. the equivalent of a rebreather loop.
+    fn to_fixed_gas_mix<D: const AbsPressure>(&self, depth: D) -> GasMix<f32> {
+        GasMix {
+            o2: self.fio2(depth),
+            he: self.phe(depth) / depth,
+            h2: self.ph2(depth) / depth,
+            ne: self.pne(depth) / depth,
+            ar: self.par(depth) / depth,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -108,6 +228,8 @@ pub struct GasMix<F: Float> {
     o2: F,
     he: F,
     h2: F,
+    ne: F,
+    ar: F,
 }
 
 impl GasMix<f32> {
@@ -115,7 +237,30 @@ impl GasMix<f32> {
         if o2 + he > 1.0 {
             return Err("FO2 + FHe should be <= 1");
         }
-        Ok(GasMix { o2, he, h2: 0.0 })
+        Ok(GasMix {
+            o2,
+            he,
+            h2: 0.0,
+            ne: 0.0,
+            ar: 0.0,
+        })
+    }
+
+    /// Mix with H2, Ne or Ar, e.g. hydrox, hydreliox or an argon drysuit gas. The rest is N2.
+    pub const fn with_inert(
+        o2: f32,
+        he: f32,
+        h2: f32,
+        ne: f32,
+        ar: f32,
+    ) -> Result<GasMix<f32>, &'static str> {
+        if o2 < 0.0 || he < 0.0 || h2 < 0.0 || ne < 0.0 || ar < 0.0 {
+            return Err("Gas fractions should be >= 0");
+        }
+        if o2 + he + h2 + ne + ar > 1.0 {
+            return Err("FO2 + FHe + FH2 + FNe + FAr should be <= 1");
+        }
+        Ok(GasMix { o2, he, h2, ne, ar })
     }
 
     pub const fn fo2(&self) -> f32 {
@@ -123,7 +268,7 @@ impl GasMix<f32> {
     }
 
     pub const fn fn2(&self) -> f32 {
-        1.0 - (self.o2 + self.he + self.h2)
+        1.0 - (self.o2 + self.he + self.h2 + self.ne + self.ar)
     }
 
     pub const fn fhe(&self) -> f32 {
@@ -133,6 +278,35 @@ impl GasMix<f32> {
     pub const fn fh2(&self) -> f32 {
         self.h2
     }
+
+    pub const fn fne(&self) -> f32 {
+        self.ne
+    }
+
+    pub const fn far(&self) -> f32 {
+        self.ar
+    }
+
+    pub const fn f_inert(&self, gas: InertGas) -> f32 {
+        match gas {
+            InertGas::N2 => self.fn2(),
+            InertGas::He => self.he,
+            InertGas::H2 => self.h2,
+            InertGas::Ne => self.ne,
+            InertGas::Ar => self.ar,
+        }
+    }
+
+    /// Deepest depth (MOD) at which the ppO2 stays at or below `max_po2`.
+    pub fn max_operating_depth(&self, max_po2: Bar, surface_pressure: Pa) -> msw {
+        depth_at_ambient_pressure(surface_pressure, max_po2.to_pa() * (1.0 / self.o2))
+    }
+
+    /// Shallowest depth at which the ppO2 reaches `min_po2`; the surface if it does there.
+    pub fn min_operating_depth(&self, min_po2: Bar, surface_pressure: Pa) -> msw {
+        let depth = depth_at_ambient_pressure(surface_pressure, min_po2.to_pa() * (1.0 / self.o2));
+        msw::new(depth.to_f32().max(0.0))
+    }
 }
 
 const impl Gas for GasMix<f32> {
@@ -152,46 +326,67 @@ const impl Gas for GasMix<f32> {
         depth * self.fn2()
     }
 
+    fn pne<D: const AbsPressure>(&self, depth: D) -> D {
+        depth * self.ne
+    }
+
+    fn par<D: const AbsPressure>(&self, depth: D) -> D {
+        depth * self.ar
+    }
+
     fn pn2_phe_ph2<D: const AbsPressure>(&self, depth: D) -> (D, D, D) {
         (self.pn2(depth), self.phe(depth), self.ph2(depth))
     }
 }
 
+/**
+* Closed-circuit loop holding `set_point`. The PO2 is limited to the ambient
+* pressure and cannot fall below that of a diluent flush.
+*/
+#[derive(Debug, Clone, Copy)]
 pub struct CCRGas<F: Float, P: const AbsPressure> {
     pub diluent: GasMix<F>,
     pub set_point: P,
 }
 
 impl<P: const AbsPressure> CCRGas<f32, P> {
-    pub fn to_fixed_gas_mix<D: const AbsPressure>(&self, depth: D) -> GasMix<f32> {
-        let (current_pn2, current_phe, _) = self.pn2_phe_ph2(depth);
-        let current_fn2 = current_pn2 / depth;
-        let current_fhe = current_phe / depth;
-        GasMix {
-            o2: self.fio2(depth),
-            he: current_fn2,
-            h2: current_fhe,
-        }
+    /// Share of the diluent's inert fractions left in the loop at `depth`.
+    const fn inert_scale<D: const AbsPressure>(&self, depth: D) -> f32 {
+        let fo2_loop = self.po2(depth) / depth;
+        let fo2_dil = self.diluent.fo2();
+        1.0 - (fo2_loop - fo2_dil) / (1.0 - fo2_dil)
     }
 }
 
 const impl<P: const AbsPressure> Gas for CCRGas<f32, P> {
     fn po2<D: const AbsPressure>(&self, depth: D) -> D {
         let set_point = D::from(self.set_point.to_pa());
-        if depth < set_point { depth } else { set_point }
+        let diluent_po2 = self.diluent.po2(depth);
+        if depth < set_point {
+            depth
+        } else if set_point < diluent_po2 {
+            diluent_po2
+        } else {
+            set_point
+        }
     }
 
     fn pn2_phe_ph2<D: const AbsPressure>(&self, depth: D) -> (D, D, D) {
-        let po2 = self.po2(depth);
-        let fo2_loop = po2 / depth;
-        let fo2_dil = self.diluent.fo2();
-        let fo2_from_dil = (fo2_loop - fo2_dil) / (1.0 - fo2_dil);
-        let fn2_loop = (1.0 - fo2_from_dil) * self.diluent.fn2();
-        let fhe_loop = (1.0 - fo2_from_dil) * self.diluent.fhe();
-        let fh2_loop = (1.0 - fo2_from_dil) * self.diluent.fh2();
+        let scale = self.inert_scale(depth);
+        let fn2_loop = scale * self.diluent.fn2();
+        let fhe_loop = scale * self.diluent.fhe();
+        let fh2_loop = scale * self.diluent.fh2();
         (depth * fn2_loop, depth * fhe_loop, depth * fh2_loop)
     }
 
+    fn pne<D: const AbsPressure>(&self, depth: D) -> D {
+        depth * (self.inert_scale(depth) * self.diluent.fne())
+    }
+
+    fn par<D: const AbsPressure>(&self, depth: D) -> D {
+        depth * (self.inert_scale(depth) * self.diluent.far())
+    }
+
     fn pn2<D: const AbsPressure>(&self, depth: D) -> D {
         self.pn2_phe_ph2(depth).0
     }
@@ -205,6 +400,89 @@ const impl<P: const AbsPressure> Gas for CCRGas<f32, P> {
     }
 }
 
+/** How supply gas enters a semi-closed loop. Flows are surface l/min. */
+#[derive(Debug, Clone, Copy)]
+pub enum SCRSupply {
+    /// Passive addition: `ratio` of the exhaled volume is dumped (0.1 for 1:10) and
+    /// replaced from the supply. `minute_volume_l_min` is the ventilation at depth.
+    Passive {
+        ratio: f32,
+        minute_volume_l_min: f32,
+    },
+    /// Active addition at a constant mass flow.
+    Active { flow_l_min: f32 },
+}
+
+/** Semi-closed loop in steady state for a supply gas and metabolic O2 consumption. */
+#[derive(Debug, Clone, Copy)]
+pub struct SCRGas<F: Float> {
+    pub supply_gas: GasMix<F>,
+    pub supply: SCRSupply,
+    /// Metabolic O2 consumption in surface l/min.
+    pub o2_consumption_l_min: f32,
+}
+
+impl SCRGas<f32> {
+    /**
+     * Loop FO2 for a supply of Q surface l/min: (Q FO2_supply - VO2) / (Q - VO2).
+     * A passive loop dumps more gas at depth, so its FO2 approaches the supply's there.
+     */
+    pub const fn loop_fo2<D: const AbsPressure>(&self, depth: D) -> f32 {
+        let vo2 = self.o2_consumption_l_min;
+        let supply_l_min = match self.supply {
+            SCRSupply::Passive {
+                ratio,
+                minute_volume_l_min,
+            } => ratio * minute_volume_l_min * depth.to_bar().to_f32() + vo2,
+            SCRSupply::Active { flow_l_min } => flow_l_min,
+        };
+        if supply_l_min <= vo2 {
+            return 0.0;
+        }
+        let fo2 = (supply_l_min * self.supply_gas.fo2() - vo2) / (supply_l_min - vo2);
+        if fo2 < 0.0 { 0.0 } else { fo2 }
+    }
+
+    /// Share of the supply's inert fractions left in the loop at `depth`.
+    const fn inert_scale<D: const AbsPressure>(&self, depth: D) -> f32 {
+        let supply_inert = 1.0 - self.supply_gas.fo2();
+        if supply_inert <= 0.0 {
+            return 0.0;
+        }
+        (1.0 - self.loop_fo2(depth)) / supply_inert
+    }
+}
+
+const impl Gas for SCRGas<f32> {
+    fn po2<D: const AbsPressure>(&self, depth: D) -> D {
+        depth * self.loop_fo2(depth)
+    }
+
+    fn pn2<D: const AbsPressure>(&self, depth: D) -> D {
+        depth * (self.supply_gas.fn2() * self.inert_scale(depth))
+    }
+
+    fn phe<D: const AbsPressure>(&self, depth: D) -> D {
+        depth * (self.supply_gas.fhe() * self.inert_scale(depth))
+    }
+
+    fn ph2<D: const AbsPressure>(&self, depth: D) -> D {
+        depth * (self.supply_gas.fh2() * self.inert_scale(depth))
+    }
+
+    fn pne<D: const AbsPressure>(&self, depth: D) -> D {
+        depth * (self.supply_gas.fne() * self.inert_scale(depth))
+    }
+
+    fn par<D: const AbsPressure>(&self, depth: D) -> D {
+        depth * (self.supply_gas.far() * self.inert_scale(depth))
+    }
+
+    fn pn2_phe_ph2<D: const AbsPressure>(&self, depth: D) -> (D, D, D) {
+        (self.pn2(depth), self.phe(depth), self.ph2(depth))
+    }
+}
+
 pub const AIR: GasMix<f32> = match GasMix::new(0.209, 0.000_005_2) {
     Ok(g) => g,
     Err(_) => unreachable!(),
@@ -240,27 +518,93 @@ pub const TMX10_80: GasMix<f32> = match GasMix::new(0.10, 0.80) {
 
 #[derive(Debug, Clone)]
 pub struct TissuesLoading<const NUM_TISSUES: usize, P: const AbsPressure> {
-    pub n2: [P; NUM_TISSUES],
-    pub he: [P; NUM_TISSUES],
+    /// Tension of every compartment, per inert gas in `InertGas::idx` order.
+    pub tensions: [[P; NUM_TISSUES]; NUM_INERT_GASES],
 }
 
 impl<const NUM_TS: usize, P: const AbsPressure> TissuesLoading<NUM_TS, P> {
     pub const fn new(ambient: P, breathing_gas: &GasMix<f32>) -> TissuesLoading<NUM_TS, P> {
-        TissuesLoading {
-            n2: [ambient * breathing_gas.fn2(); NUM_TS],
-            he: [ambient * breathing_gas.fhe(); NUM_TS],
+        let mut tensions = [[P::from(Pa::new(0.0)); NUM_TS]; NUM_INERT_GASES];
+        let mut i = 0;
+        while i < NUM_INERT_GASES {
+            tensions[i] = [ambient * breathing_gas.f_inert(InertGas::ALL[i]); NUM_TS];
+            i += 1;
         }
+        TissuesLoading { tensions }
+    }
+
+    /// Loading with only N2 and He in the tissues.
+    pub const fn from_n2_he(n2: [P; NUM_TS], he: [P; NUM_TS]) -> TissuesLoading<NUM_TS, P> {
+        let mut tensions = [[P::from(Pa::new(0.0)); NUM_TS]; NUM_INERT_GASES];
+        tensions[N2_IDX] = n2;
+        tensions[HE_IDX] = he;
+        TissuesLoading { tensions }
+    }
+
+    pub const fn tension(&self, gas: InertGas) -> &[P; NUM_TS] {
+        &self.tensions[gas.idx()]
+    }
+
+    pub const fn tension_mut(&mut self, gas: InertGas) -> &mut [P; NUM_TS] {
+        &mut self.tensions[gas.idx()]
+    }
+
+    pub const fn n2(&self) -> &[P; NUM_TS] {
+        self.tension(InertGas::N2)
+    }
+
+    pub const fn he(&self) -> &[P; NUM_TS] {
+        self.tension(InertGas::He)
+    }
+
+    /// Tension of every inert gas in compartment `tissue_idx`.
+    pub fn tissue_tensions(&self, tissue_idx: usize) -> [P; NUM_INERT_GASES] {
+        self.tensions.map(|t| t[tissue_idx])
+    }
+
+    /// Summed inert gas tension of compartment `tissue_idx`.
+    pub fn total_inert(&self, tissue_idx: usize) -> P {
+        self.tensions[1..]
+            .iter()
+            .fold(self.tensions[0][tissue_idx], |sum, t| sum + t[tissue_idx])
     }
 
     pub fn is_isobaric_counterdiffusion<G: Gas>(&self, depth: P, new_gas: &G) -> bool {
-        let new_gas_n2 = new_gas.pn2(depth);
-        let new_gas_he = new_gas.phe(depth);
-        zip(self.n2, self.he).any(|(n2, he)| n2 < new_gas_n2 && he > new_gas_he)
+        self.switch_gradients(depth, new_gas)
+            .iter()
+            .any(|t| t.is_counterdiffusion())
+    }
+
+    /**
+     * Gradients of every compartment right after switching to `new_gas` at
+     * `depth`. Ne and Ar count as N2 and H2 as He, see `InertGas::reference`.
+     */
+    pub fn switch_gradients<G: Gas>(&self, depth: P, new_gas: &G) -> [TissueGradients; NUM_TS] {
+        let mut gradients = [TissueGradients {
+            n2_in: Bar::new(0.0),
+            he_out: Bar::new(0.0),
+        }; NUM_TS];
+        for gas in InertGas::ALL {
+            let inspired = new_gas.p_inert(gas, depth).to_bar().to_f32();
+            for (g, p) in gradients.iter_mut().zip(self.tension(gas)) {
+                let gradient = inspired - p.to_bar().to_f32();
+                match gas.reference() {
+                    InertGas::He => g.he_out = Bar::new(g.he_out.to_f32() - gradient),
+                    _ => g.n2_in = Bar::new(g.n2_in.to_f32() + gradient),
+                }
+            }
+        }
+        gradients
     }
 
     pub fn tick<G: Gas>(&mut self, time_delta_ms: u16, depth: P, gas: &G) {
-        Self::tick_gas(time_delta_ms, gas.pn2(depth), &mut self.n2);
-        Self::tick_gas(time_delta_ms, gas.phe(depth), &mut self.he);
+        for inert in InertGas::ALL {
+            Self::tick_gas(
+                time_delta_ms,
+                gas.p_inert(inert, depth),
+                self.tension_mut(inert),
+            );
+        }
     }
 
     fn tick_gas(time_delta_ms: u16, pp_insp: P, cur: &mut [P; NUM_TS]) {
@@ -271,6 +615,20 @@ impl<const NUM_TS: usize, P: const AbsPressure> TissuesLoading<NUM_TS, P> {
     }
 }
 
+/** Inspired minus tissue N2 and tissue minus inspired He of one compartment. */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TissueGradients {
+    pub n2_in: Bar,
+    pub he_out: Bar,
+}
+
+impl TissueGradients {
+    /// N2 is taken up while He is given off.
+    pub fn is_counterdiffusion(&self) -> bool {
+        self.n2_in.to_f32() > 0.0 && self.he_out.to_f32() > 0.0
+    }
+}
+
 pub fn best_mix_fo2<P: const AbsPressure>(max_po2: P, depth: P) -> f32 {
     max_po2 / depth
 }
@@ -291,7 +649,7 @@ impl GasDensitySettings {
         }
     }
 
-    pub fn no_violation<P: const AbsPressure>(&self, depth: P, gas: &GasMix<f32>) -> bool {
+    pub fn no_violation<P: const AbsPressure, G: Gas>(&self, depth: P, gas: &G) -> bool {
         if let GasDensitySettings::Limit { limit_g_l } = self {
             return gas.gas_density(depth) < *limit_g_l;
         }
@@ -299,22 +657,88 @@ impl GasDensitySettings {
     }
 }
 
+/// Largest N2 uptake per He washout allowed by the rule of fifths.
+pub const RULE_OF_FIFTHS: f32 = 0.2;
+
+pub enum IcdSettings {
+    Ignore,
+    /// No compartment may take up N2 while giving off He.
+    AnyCounterdiffusion,
+    /// A switch raises N2 by at most a fifth of the drop in He, as in
+    /// `IcdAnalysis::meets_rule_of_fifths`.
+    RuleOfFifths,
+}
+
+impl IcdSettings {
+    /// Whether switching from `current` (`None` before the first gas) to `gas` is allowed.
+    pub fn no_violation<const NUM_TS: usize, P: const AbsPressure, G: Gas>(
+        &self,
+        depth: P,
+        current: Option<&G>,
+        gas: &G,
+        tissue_loading: &TissuesLoading<NUM_TS, P>,
+    ) -> bool {
+        match self {
+            IcdSettings::Ignore => true,
+            IcdSettings::AnyCounterdiffusion => {
+                !tissue_loading.is_isobaric_counterdiffusion(depth, gas)
+            }
+            IcdSettings::RuleOfFifths => {
+                current.is_none_or(|current| switch_meets_rule_of_fifths(current, gas, depth))
+            }
+        }
+    }
+}
+
+pub enum NarcosisSettings {
+    Ignore,
+    /// END is measured from a 1 bar surface, like msw.
+    Limit {
+        max_end: msw,
+        o2_narcotic: bool,
+    },
+}
+
+impl NarcosisSettings {
+    pub const fn limit(max_end: msw, o2_narcotic: bool) -> Self {
+        Self::Limit {
+            max_end,
+            o2_narcotic,
+        }
+    }
+
+    pub fn no_violation<P: const AbsPressure, G: Gas>(&self, depth: P, gas: &G) -> bool {
+        if let NarcosisSettings::Limit {
+            max_end,
+            o2_narcotic,
+        } = self
+        {
+            return gas.end(depth.to_pa(), *o2_narcotic) <= max_end.to_pa();
+        }
+        true
+    }
+}
+
 /**
 * Returns None iff available_gases is empty, or no gas fits
 * - o2 requirements or
 * - (optional) isobaric counterdiffusion requirements or
-* - (optional) gas density requirements.
+* - (optional) gas density requirements or
+* - (optional) END requirements.
 *
 * Best performance can be expected if called with P = D = Pa
 */
+#[allow(clippy::too_many_arguments)]
 pub fn best_available_mix<'a, P: const AbsPressure, const G: usize, const NUM_TS: usize>(
     max_po2: P,
     depth: P,
     available_gases: &'a [GasMix<f32>; G],
     gases_enabled: &[bool; G],
+    current_gas: Option<&GasMix<f32>>,
     tissue_loading: &TissuesLoading<NUM_TS, P>,
-    ignore_isobaric_counterdiffusion: bool,
+    icd: &IcdSettings,
     gas_density: &GasDensitySettings,
+    narcosis: &NarcosisSettings,
 ) -> Option<(usize, &'a GasMix<f32>)> {
     let best_mix_fo2 = best_mix_fo2(max_po2, depth);
     available_gases
@@ -322,15 +746,11 @@ pub fn best_available_mix<'a, P: const AbsPressure, const G: usize, const NUM_TS
         .enumerate()
         .filter(|(i, _g)| gases_enabled[*i])
         .filter(|(_i, g)| g.fo2() <= best_mix_fo2)
-        .filter(|(_i, g)| {
-            ignore_isobaric_counterdiffusion
-                || !tissue_loading.is_isobaric_counterdiffusion(depth, *g)
-        })
-        .filter(|(_i, g)| gas_density.no_violation(depth, g))
+        .filter(|(_i, g)| icd.no_violation(depth, current_gas, *g, tissue_loading))
+        .filter(|(_i, g)| gas_density.no_violation(depth, *g))
+        .filter(|(_i, g)| narcosis.no_violation(depth, *g))
         .reduce(|(ai, ag), (bi, bg)| {
-            let better_fo2 = ag.fo2() > bg.fo2();
-            let same_fo2_better_he = ag.fo2() == bg.fo2() && ag.fhe() > bg.fhe();
-            if better_fo2 || same_fo2_better_he {
+            if is_better_mix(ag, bg) {
                 (ai, ag)
             } else {
                 (bi, bg)
@@ -338,6 +758,11 @@ pub fn best_available_mix<'a, P: const AbsPressure, const G: usize, const NUM_TS
         })
 }
 
+/// Ranking of `best_available_mix`: higher FO2 first, then higher FHe.
+pub fn is_better_mix(a: &GasMix<f32>, b: &GasMix<f32>) -> bool {
+    a.fo2() > b.fo2() || (a.fo2() == b.fo2() && a.fhe() > b.fhe())
+}
+
 #[cfg(test)]
 mod tests {
     use crate::pressure_unit::{Pa, Pressure, msw};
@@ -350,25 +775,112 @@ mod tests {
             best_mix_fo2(Bar::new(1.6).to_pa(), msw::new(0.0).to_pa()),
             1.6
         );
-        assert_eq!(
-            best_mix_fo2(Bar::new(1.6).to_pa(), msw::new(6.0).to_pa()) - 1.0 < 0.01,
-            true
+        assert!(
+            best_mix_fo2(Bar::new(1.6).to_pa(), msw::new(6.0).to_pa()) - 1.0 < 0.01
         );
-        assert_eq!(
-            best_mix_fo2(Bar::new(1.6).to_pa(), msw::new(21.0).to_pa()) - 0.5 < 0.1,
-            true
+        assert!(
+            best_mix_fo2(Bar::new(1.6).to_pa(), msw::new(21.0).to_pa()) - 0.5 < 0.1
         );
         assert_eq!(
             best_mix_fo2(Bar::new(1.4).to_pa(), msw::new(0.0).to_pa()),
             1.4
         );
+        assert!(
+            best_mix_fo2(Bar::new(1.4).to_pa(), msw::new(4.0).to_pa()) - 1.0 < 0.01
+        );
+        assert!(
+            best_mix_fo2(Bar::new(1.4).to_pa(), msw::new(18.0).to_pa()) - 0.5 < 0.1
+        );
+    }
+
+    #[test]
+    fn ccr_fixed_mix_keeps_diluent_inert_ratio() {
+        let diluent = GasMix::new(0.10, 0.50).expect("10 + 50 < 100");
+        let ccr = CCRGas {
+            diluent,
+            set_point: Bar::new(1.3).to_pa(),
+        };
+        let depth = msw::new(60.0).to_pa();
+        let mix = ccr.to_fixed_gas_mix(depth);
+        assert!((mix.po2(depth).to_bar().to_f32() - 1.3).abs() < 1e-3);
+        assert!((mix.fhe() / mix.fn2() - diluent.fhe() / diluent.fn2()).abs() < 1e-4);
+
+        let shallow = msw::new(2.0).to_pa();
+        assert!((ccr.to_fixed_gas_mix(shallow).fo2() - 1.0).abs() < 1e-6);
+        let low = CCRGas {
+            diluent: AIR,
+            set_point: Bar::new(0.7).to_pa(),
+        };
+        let deep = msw::new(40.0).to_pa();
+        assert!((low.to_fixed_gas_mix(deep).fo2() - AIR.fo2()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn scr_loop_fo2_from_supply_and_consumption() {
+        let active = SCRGas {
+            supply_gas: GasMix::new(0.40, 0.0).expect("40 < 100"),
+            supply: SCRSupply::Active { flow_l_min: 15.0 },
+            o2_consumption_l_min: 1.0,
+        };
+        let surface = msw::new(0.0).to_pa();
+        let depth = msw::new(30.0).to_pa();
+        assert!((active.fio2(surface) - 5.0 / 14.0).abs() < 1e-5);
+        assert!((active.fio2(depth) - 5.0 / 14.0).abs() < 1e-5);
+        let (pn2, phe, ph2) = active.pn2_phe_ph2(depth);
+        assert!(((active.po2(depth) + pn2 + phe + ph2) / depth - 1.0).abs() < 1e-5);
+
+        let passive = SCRGas {
+            supply_gas: NX50,
+            supply: SCRSupply::Passive {
+                ratio: 0.1,
+                minute_volume_l_min: 20.0,
+            },
+            o2_consumption_l_min: 1.0,
+        };
+        // 3 l/min supplied at 1 bar: (1.5 - 1) / 2
+        assert!((passive.loop_fo2(Bar::new(1.0)) - 0.25).abs() < 1e-5);
+        assert!(passive.fio2(depth) > passive.fio2(surface));
+        assert!(passive.fio2(depth) < NX50.fo2());
+    }
+
+    #[test]
+    fn narcotic_depths() {
+        let depth = msw::new(30.0).to_pa();
+        assert!((AIR.ead(depth) - depth).to_f32().abs() < 1.0);
+        assert!((AIR.end(depth, true) - depth).to_f32().abs() < 1.0);
+        let nitrox32 = GasMix::new(0.32, 0.0).expect("32 < 100");
+        assert!((nitrox32.ead(depth).to_msw().to_f32() - 24.4).abs() < 0.1);
+
+        let depth = msw::new(60.0).to_pa();
+        assert!((TMX18_45.end(depth, false).to_msw().to_f32() - 22.8).abs() < 0.1);
+        assert!((TMX18_45.end(depth, true).to_msw().to_f32() - 28.5).abs() < 0.1);
+    }
+
+    #[test]
+    fn best_available_mix_with_max_end_skips_narcotic_gas() {
+        let (gases, gases_enabled, empty_tissues) = best_available_mix_fixture();
+        let select = |narcosis: &NarcosisSettings| {
+            best_available_mix(
+                Bar::new(1.4).to_pa(),
+                msw::new(40.0).to_pa(),
+                &gases,
+                &gases_enabled,
+                None,
+                &empty_tissues,
+                &IcdSettings::Ignore,
+                &GasDensitySettings::Ignore,
+                narcosis,
+            )
+            .map(|(i, _g)| i)
+        };
+        assert_eq!(select(&NarcosisSettings::Ignore), Some(1));
         assert_eq!(
-            best_mix_fo2(Bar::new(1.4).to_pa(), msw::new(4.0).to_pa()) - 1.0 < 0.01,
-            true
+            select(&NarcosisSettings::limit(msw::new(30.0), true)),
+            Some(1)
         );
         assert_eq!(
-            best_mix_fo2(Bar::new(1.4).to_pa(), msw::new(18.0).to_pa()) - 0.5 < 0.1,
-            true
+            select(&NarcosisSettings::limit(msw::new(15.0), false)),
+            Some(3)
         );
     }
 
@@ -380,10 +892,8 @@ mod tests {
             GasMix::new(0.10, 0.80).expect("10 + 80 < 100"),
         ];
         let gases_enabled = [true; 4];
-        let empty_tissues = TissuesLoading {
-            n2: [msw::new(0.0).to_pa()],
-            he: [msw::new(0.0).to_pa()],
-        };
+        let empty_tissues =
+            TissuesLoading::from_n2_he([msw::new(0.0).to_pa()], [msw::new(0.0).to_pa()]);
         (gases, gases_enabled, empty_tissues)
     }
 
@@ -396,9 +906,11 @@ mod tests {
                 msw::new(21.0).to_pa(),
                 &gases,
                 &gases_enabled,
+                None,
                 &empty_tissues,
-                true,
-                &GasDensitySettings::Ignore
+                &IcdSettings::Ignore,
+                &GasDensitySettings::Ignore,
+                &NarcosisSettings::Ignore
             )
             .expect("There are gases, so reduce should return a result"),
             (2_usize, &gases[2])
@@ -414,9 +926,11 @@ mod tests {
                 msw::new(21.0).to_pa(),
                 &gases,
                 &gases_enabled,
+                None,
                 &empty_tissues,
-                true,
-                &GasDensitySettings::Ignore
+                &IcdSettings::Ignore,
+                &GasDensitySettings::Ignore,
+                &NarcosisSettings::Ignore
             )
             .expect("There are gases, so reduce should return a result"),
             (1_usize, &gases[1])
@@ -432,9 +946,11 @@ mod tests {
                 msw::new(22.0).to_pa(),
                 &gases,
                 &gases_enabled,
+                None,
                 &empty_tissues,
-                true,
-                &GasDensitySettings::Ignore
+                &IcdSettings::Ignore,
+                &GasDensitySettings::Ignore,
+                &NarcosisSettings::Ignore
             )
             .expect("There are gases, so reduce should return a result"),
             (1_usize, &gases[1])
@@ -450,9 +966,11 @@ mod tests {
                 msw::new(90.0).to_pa(),
                 &gases,
                 &gases_enabled,
+                None,
                 &empty_tissues,
-                true,
-                &GasDensitySettings::Ignore
+                &IcdSettings::Ignore,
+                &GasDensitySettings::Ignore,
+                &NarcosisSettings::Ignore
             ),
             Some((3_usize, &gases[3]))
         );
@@ -467,9 +985,11 @@ mod tests {
                 msw::new(130.0).to_pa(),
                 &gases,
                 &gases_enabled,
+                None,
                 &empty_tissues,
-                true,
-                &GasDensitySettings::limit_from_air_multiplier(3.0)
+                &IcdSettings::Ignore,
+                &GasDensitySettings::limit_from_air_multiplier(3.0),
+                &NarcosisSettings::Ignore
             ),
             None,
             // Some((3_usize, &gases[3]))
@@ -485,9 +1005,11 @@ mod tests {
                 msw::new(119.0).to_pa(),
                 &gases,
                 &gases_enabled,
+                None,
                 &empty_tissues,
-                true,
-                &GasDensitySettings::limit_g_l(gL::new(6.3))
+                &IcdSettings::Ignore,
+                &GasDensitySettings::limit_g_l(gL::new(6.3)),
+                &NarcosisSettings::Ignore
             ),
             Some((3_usize, &gases[3]))
         );
@@ -502,9 +1024,11 @@ mod tests {
                 msw::new(119.0).to_pa(),
                 &gases,
                 &gases_enabled,
+                None,
                 &empty_tissues,
-                true,
-                &GasDensitySettings::limit_g_l(gL::new(2.0))
+                &IcdSettings::Ignore,
+                &GasDensitySettings::limit_g_l(gL::new(2.0)),
+                &NarcosisSettings::Ignore
             ),
             None,
         );
@@ -519,9 +1043,11 @@ mod tests {
                 msw::new(200.0).to_pa(),
                 &gases,
                 &gases_enabled,
+                None,
                 &empty_tissues,
-                true,
-                &GasDensitySettings::Ignore
+                &IcdSettings::Ignore,
+                &GasDensitySettings::Ignore,
+                &NarcosisSettings::Ignore
             ),
             None
         );
@@ -530,37 +1056,62 @@ mod tests {
     #[test]
     fn is_isobaric_counterdiffusion_true() {
         let depth = msw::new(30.0).to_pa();
-        let tissues: TissuesLoading<1, Pa> = TissuesLoading {
-            n2: [depth * 0.1],
-            he: [depth * 0.7],
-        };
+        let tissues: TissuesLoading<1, Pa> =
+            TissuesLoading::from_n2_he([depth * 0.1], [depth * 0.7]);
         // AIR has relatively high N2 and negligible He compared to the tissue above
         let new_gas = AIR;
-        assert_eq!(tissues.is_isobaric_counterdiffusion(depth, &new_gas), true);
+        assert!(tissues.is_isobaric_counterdiffusion(depth, &new_gas));
     }
 
     #[test]
     fn is_isobaric_counterdiffusion_false_when_no_match() {
         let depth = msw::new(30.0).to_pa();
-        let tissues: TissuesLoading<1, Pa> = TissuesLoading {
-            n2: [depth * 0.5],
-            he: [depth * 0.1],
-        };
+        let tissues: TissuesLoading<1, Pa> =
+            TissuesLoading::from_n2_he([depth * 0.5], [depth * 0.1]);
         // TMX10_80 is helium rich; this should not trigger the check (he > new_he false)
         let new_gas = TMX10_80;
-        assert_eq!(tissues.is_isobaric_counterdiffusion(depth, &new_gas), false);
+        assert!(!tissues.is_isobaric_counterdiffusion(depth, &new_gas));
     }
 
     #[test]
     fn is_isobaric_counterdiffusion_any_tissue_true() {
         let depth = msw::new(30.0).to_pa();
-        let tissues: TissuesLoading<2, Pa> = TissuesLoading {
-            n2: [depth * 0.5, depth * 0.1],
-            he: [depth * 0.1, depth * 0.7],
-        };
+        let tissues: TissuesLoading<2, Pa> = TissuesLoading::from_n2_he(
+            [depth * 0.5, depth * 0.1],
+            [depth * 0.1, depth * 0.7],
+        );
         let new_gas = AIR;
         // second tissue should trigger the condition
-        assert_eq!(tissues.is_isobaric_counterdiffusion(depth, &new_gas), true);
+        assert!(tissues.is_isobaric_counterdiffusion(depth, &new_gas));
+    }
+
+    #[test]
+    fn best_available_mix_with_rule_of_fifths() {
+        let (gases, gases_enabled, _) = best_available_mix_fixture();
+        let depth = msw::new(21.0).to_pa();
+        let tissues: TissuesLoading<1, Pa> =
+            TissuesLoading::from_n2_he([Bar::new(1.4).to_pa()], [Bar::new(1.4).to_pa()]);
+        let select = |current: Option<&GasMix<f32>>, icd: &IcdSettings| {
+            best_available_mix(
+                Bar::new(1.6).to_pa(),
+                depth,
+                &gases,
+                &gases_enabled,
+                current,
+                &tissues,
+                icd,
+                &GasDensitySettings::Ignore,
+                &NarcosisSettings::Ignore,
+            )
+            .map(|(i, _g)| i)
+        };
+        assert_eq!(select(None, &IcdSettings::Ignore), Some(2));
+        assert_eq!(select(None, &IcdSettings::RuleOfFifths), Some(2));
+        // 21/35 to nitrox 50 adds 6 % N2 for 35 % He
+        assert_eq!(select(Some(&gases[1]), &IcdSettings::RuleOfFifths), Some(2));
+        // 10/80 to nitrox 50 adds 40 % N2 for 80 % He, every other switch is worse
+        assert_eq!(select(Some(&gases[3]), &IcdSettings::RuleOfFifths), Some(3));
+        assert_eq!(select(None, &IcdSettings::AnyCounterdiffusion), Some(1));
     }
 
     #[test]
@@ -569,11 +1120,11 @@ mod tests {
         // TMX10_80 is helium rich and generally light
         let light_gas = TMX10_80;
         let settings_ok = GasDensitySettings::limit_g_l(gL::new(10.0));
-        assert_eq!(settings_ok.no_violation(depth, &light_gas), true);
+        assert!(settings_ok.no_violation(depth, &light_gas));
 
         let deep = msw::new(100.0).to_pa();
         let heavy_gas = AIR;
         let settings_strict = GasDensitySettings::limit_g_l(gL::new(1.0));
-        assert_eq!(settings_strict.no_violation(deep, &heavy_gas), false);
+        assert!(!settings_strict.no_violation(deep, &heavy_gas));
     }
 }
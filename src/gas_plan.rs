@@ -0,0 +1,266 @@
+use core::time::Duration;
+
+#[allow(unused)]
+use num::Float;
+
+use crate::{
+    dive::StopSchedule,
+    gas::GasMix,
+    pressure_unit::{Bar, Pa, Pressure, ambient_pressure_at_depth, msw},
+};
+
+/** Surface respiratory minute volumes (SAC/RMV) per phase of the dive, in l/min. */
+#[derive(Debug, Clone, Copy)]
+pub struct ConsumptionRates {
+    pub bottom_l_min: f32,
+    /// Ascent legs and stops.
+    pub deco_l_min: f32,
+    /// Multiplier on `bottom_l_min` for an emergency such as a shared ascent.
+    pub stress_factor: f32,
+}
+
+/// Leg of the plan at constant depth on `gases[gas]`.
+#[derive(Debug, Clone, Copy)]
+pub struct BottomSegment {
+    pub depth: msw,
+    pub duration: Duration,
+    pub gas: usize,
+}
+
+/** Out-of-gas situation at the deepest point that the bottom gas reserve must cover. */
+#[derive(Debug, Clone, Copy)]
+pub struct RockBottomSettings {
+    /// Time spent at depth sorting out the problem before the ascent starts.
+    pub problem_solving_time: Duration,
+    /// Divers breathing from the cylinder during the shared ascent.
+    pub divers: u8,
+}
+
+/// Surface litres breathed at `rmv_l_min` for `duration` while going from `from` to `to`.
+pub fn leg_volume_l(
+    from: msw,
+    to: msw,
+    duration: Duration,
+    rmv_l_min: f32,
+    surface_pressure: Pa,
+) -> f32 {
+    let mean_depth = msw::new(0.5 * (from.to_f32() + to.to_f32()));
+    let ambient = ambient_pressure_at_depth(surface_pressure, mean_depth).to_bar();
+    rmv_l_min * ambient.to_f32() * duration.as_secs_f32() / 60.0
+}
+
+/**
+* Litres of each gas and the time needed to ascend from `depth` through the
+* stops of `schedule`. Each ascent leg is breathed on the gas of the depth it
+* starts from, `gases[gas]` for the first one.
+*/
+pub fn ascent_gas_l<const NUM_STOPS: usize, const NUM_GASES: usize>(
+    depth: msw,
+    gas: usize,
+    schedule: &StopSchedule<NUM_STOPS>,
+    gases: &[GasMix<f32>; NUM_GASES],
+    rmv_l_min: f32,
+    ascent_rate_per_meter: &Duration,
+    surface_pressure: Pa,
+) -> Result<([f32; NUM_GASES], Duration), &'static str> {
+    let mut required_l = [0.0; NUM_GASES];
+    let mut time = Duration::ZERO;
+    let mut breathe = |gas_idx: usize, from: msw, to: msw, duration: Duration| {
+        required_l[gas_idx] += leg_volume_l(from, to, duration, rmv_l_min, surface_pressure);
+        time += duration;
+    };
+    let ascent =
+        |from: msw, to: msw| ascent_rate_per_meter.mul_f32((from.to_f32() - to.to_f32()).max(0.0));
+
+    let (mut current_depth, mut current_gas) = (depth, gas);
+    for stop in schedule.stops().iter().filter(|s| !s.duration().is_zero()) {
+        let stop_gas = gases
+            .iter()
+            .position(|g| Some(*g) == stop.gas())
+            .ok_or("Stop gas is not among the planned gases.")?;
+        let leg = ascent(current_depth, stop.depth());
+        breathe(current_gas, current_depth, stop.depth(), leg);
+        breathe(stop_gas, stop.depth(), stop.depth(), stop.duration());
+        (current_depth, current_gas) = (stop.depth(), stop_gas);
+    }
+    let surface = msw::new(0.0);
+    let leg = ascent(current_depth, surface);
+    breathe(current_gas, current_depth, surface, leg);
+    Ok((required_l, time))
+}
+
+/**
+* Litres of each gas for the whole plan: the bottom segments at the bottom
+* rate, then the ascent from the last segment at the deco rate.
+*/
+pub fn plan_gas_required<const NUM_STOPS: usize, const NUM_GASES: usize>(
+    segments: &[BottomSegment],
+    schedule: &StopSchedule<NUM_STOPS>,
+    gases: &[GasMix<f32>; NUM_GASES],
+    rates: &ConsumptionRates,
+    ascent_rate_per_meter: &Duration,
+    surface_pressure: Pa,
+) -> Result<[f32; NUM_GASES], &'static str> {
+    let last = segments
+        .last()
+        .ok_or("A plan needs at least one segment.")?;
+    if segments.iter().any(|s| s.gas >= NUM_GASES) {
+        return Err("Segment gas is not among the planned gases.");
+    }
+    let (mut required_l, _) = ascent_gas_l(
+        last.depth,
+        last.gas,
+        schedule,
+        gases,
+        rates.deco_l_min,
+        ascent_rate_per_meter,
+        surface_pressure,
+    )?;
+    for segment in segments {
+        required_l[segment.gas] += leg_volume_l(
+            segment.depth,
+            segment.depth,
+            segment.duration,
+            rates.bottom_l_min,
+            surface_pressure,
+        );
+    }
+    Ok(required_l)
+}
+
+/**
+* Minimum gas (rock bottom) in `gases[bottom_gas]`: all divers at the stressed
+* bottom rate, solving the problem at `depth` and then ascending with `schedule`
+* at the deco ascent rate until they switch away from the bottom gas.
+*/
+#[allow(clippy::too_many_arguments)]
+pub fn rock_bottom_l<const NUM_STOPS: usize, const NUM_GASES: usize>(
+    depth: msw,
+    bottom_gas: usize,
+    schedule: &StopSchedule<NUM_STOPS>,
+    gases: &[GasMix<f32>; NUM_GASES],
+    rates: &ConsumptionRates,
+    settings: &RockBottomSettings,
+    ascent_rate_per_meter: &Duration,
+    surface_pressure: Pa,
+) -> Result<f32, &'static str> {
+    if bottom_gas >= NUM_GASES {
+        return Err("Bottom gas is not among the planned gases.");
+    }
+    let rmv_l_min = rates.bottom_l_min * rates.stress_factor * settings.divers as f32;
+    let (ascent_l, _) = ascent_gas_l(
+        depth,
+        bottom_gas,
+        schedule,
+        gases,
+        rmv_l_min,
+        ascent_rate_per_meter,
+        surface_pressure,
+    )?;
+    let problem_l = leg_volume_l(
+        depth,
+        depth,
+        settings.problem_solving_time,
+        rmv_l_min,
+        surface_pressure,
+    );
+    Ok(problem_l + ascent_l[bottom_gas])
+}
+
+/**
+* Rule of thirds on the gas above `reserve`: a third for the way in, a third
+* for the way out and a third for the buddy. A zero `reserve` is the plain rule.
+*/
+pub fn thirds_turn_pressure(start: Bar, reserve: Bar) -> Bar {
+    let usable = (start.to_f32() - reserve.to_f32()).max(0.0);
+    Bar::new(start.to_f32() - usable / 3.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dive::Stop,
+        gas::{NX50, NX100, TMX18_45},
+    };
+
+    const RATES: ConsumptionRates = ConsumptionRates {
+        bottom_l_min: 20.0,
+        deco_l_min: 15.0,
+        stress_factor: 2.0,
+    };
+    const ASCENT: Duration = Duration::from_secs(6);
+
+    fn schedule() -> StopSchedule<30> {
+        let mut stops = *StopSchedule::<30>::default().stops();
+        for (depth, minutes, gas) in [(21.0, 1, NX50), (9.0, 2, NX50), (6.0, 5, NX100)] {
+            let idx = 29 - (depth / 3.0) as usize;
+            stops[idx] = Stop::new(
+                msw::new(depth),
+                Duration::from_secs(minutes * 60),
+                Some(gas),
+            );
+        }
+        StopSchedule::new(stops)
+    }
+
+    #[test]
+    fn plan_gas_is_split_by_stop_gas() {
+        let surface = msw::new(0.0).to_pa();
+        let segments = [BottomSegment {
+            depth: msw::new(40.0),
+            duration: Duration::from_secs(20 * 60),
+            gas: 0,
+        }];
+        let gases = [TMX18_45, NX50, NX100];
+        let required = plan_gas_required(&segments, &schedule(), &gases, &RATES, &ASCENT, surface)
+            .expect("planned gases");
+        // 20 l/min at 5.05 bar for 20 min, plus 19 m of ascent at 30.5 m mean depth
+        let bottom = 20.0 * 5.052 * 20.0 + 15.0 * 4.0895 * 1.9;
+        assert!((required[0] - bottom).abs() < 1.0, "{}", required[0]);
+        // 1 min at 21 m, 12 m at 15 m, 2 min at 9 m and 3 m at 7.5 m, all at 15 l/min
+        let nx50 = 15.0 * (3.127 + 2.52 * 1.2 + 2.0 * 1.912 + 1.76 * 0.3);
+        assert!((required[1] - nx50).abs() < 1.0, "{}", required[1]);
+        assert!(required[2] > 15.0 * 5.0 * 1.6);
+    }
+
+    #[test]
+    fn rock_bottom_covers_the_shared_ascent_on_bottom_gas() {
+        let surface = msw::new(0.0).to_pa();
+        let settings = RockBottomSettings {
+            problem_solving_time: Duration::from_secs(60),
+            divers: 2,
+        };
+        let gases = [TMX18_45, NX50, NX100];
+        let rock_bottom = rock_bottom_l(
+            msw::new(40.0),
+            0,
+            &schedule(),
+            &gases,
+            &RATES,
+            &settings,
+            &ASCENT,
+            surface,
+        )
+        .expect("rock bottom");
+        // 80 l/min: 1 min at 40 m, then 1.9 min of ascent to the switch at 21 m
+        let expected = 80.0 * (5.052 + 4.0895 * 1.9);
+        assert!((rock_bottom - expected).abs() < 2.0, "{rock_bottom}");
+    }
+
+    #[test]
+    fn thirds_turn_pressure_test() {
+        assert_eq!(
+            thirds_turn_pressure(Bar::new(210.0), Bar::new(0.0)),
+            Bar::new(140.0)
+        );
+        assert_eq!(
+            thirds_turn_pressure(Bar::new(210.0), Bar::new(60.0)),
+            Bar::new(160.0)
+        );
+        assert_eq!(
+            thirds_turn_pressure(Bar::new(50.0), Bar::new(60.0)),
+            Bar::new(50.0)
+        );
+    }
+}
@@ -0,0 +1,292 @@
+#[allow(unused)]
+use num::Float;
+
+use crate::{
+    deco_algorithm::DecoSettings,
+    gas::{GasMix, TissuesLoading, best_mix_fo2, is_better_mix},
+    pressure_unit::{AbsPressure, Pressure, ambient_pressure_at_depth, msw},
+};
+
+/// How far off the stop grid a depth may be and still count as a stop depth.
+const STOP_DEPTH_TOLERANCE: msw = msw::new(0.3);
+
+/// Gas breathed on descent until `until_depth`, e.g. air or nitrox for a hypoxic bottom mix.
+#[derive(Debug, Clone, Copy)]
+pub struct TravelGas {
+    pub gas: usize,
+    pub until_depth: msw,
+}
+
+/**
+* Rules for switching gas on top of the limits of `DecoSettings`. The default
+* policy switches like `best_available_mix`. Indices are into the gases, loops
+* or diluents the schedule switches between.
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GasSwitchPolicy<'a> {
+    /// Gas indices, most preferred first; gases not listed are never switched to.
+    /// `None` ranks like `best_available_mix`.
+    pub priority: Option<&'a [usize]>,
+    /// FO2 a richer gas has to add before it is worth a switch.
+    pub min_fo2_gain: f32,
+    /// Only switch on the stop grid (multiples of the model's stop increment).
+    pub at_stop_depths_only: bool,
+    pub travel_gas: Option<TravelGas>,
+    /// Never switch back to a gas with less O2, unless the current one becomes unusable.
+    pub no_leaner_switch: bool,
+}
+
+fn on_stop_grid(depth: msw, stop_increment: msw) -> bool {
+    let k = (depth.to_f32() / stop_increment.to_f32()).round();
+    (depth.to_f32() - k * stop_increment.to_f32()).abs() <= STOP_DEPTH_TOLERANCE.to_f32()
+}
+
+impl GasSwitchPolicy<'_> {
+    /**
+     * Index of the gas to breathe at `depth` while breathing `gases[current]`
+     * (`None` before the first gas is chosen), on a stop grid of `stop_increment`
+     * (the model's `stop_increment()`). The current gas is kept unless a
+     * rule allows a switch or it violates the ppO2, density or END limits.
+     * Returns None iff no gas is usable at `depth`.
+     */
+    #[allow(clippy::too_many_arguments)]
+    pub fn select<const NUM_TISSUES: usize, const NUM_GASES: usize, P: const AbsPressure>(
+        &self,
+        current: Option<usize>,
+        depth: msw,
+        stop_increment: msw,
+        max_po2: P,
+        gases: &[GasMix<f32>; NUM_GASES],
+        gases_enabled: &[bool; NUM_GASES],
+        loading: &TissuesLoading<NUM_TISSUES, P>,
+        deco_settings: &DecoSettings<P>,
+    ) -> Option<usize> {
+        let ambient = ambient_pressure_at_depth(deco_settings.surface_pressure, depth);
+        let max_fo2 = best_mix_fo2(max_po2, ambient);
+        let usable = |i: usize| {
+            let g = &gases[i];
+            gases_enabled[i]
+                && g.fo2() <= max_fo2
+                && deco_settings.gas_density_settings.no_violation(ambient, g)
+                && deco_settings.narcosis_settings.no_violation(ambient, g)
+        };
+        let current = current.filter(|c| *c < NUM_GASES && usable(*c));
+
+        if let Some(c) = current {
+            let on_travel_gas = self
+                .travel_gas
+                .is_some_and(|t| t.gas == c && depth < t.until_depth);
+            if on_travel_gas || (self.at_stop_depths_only && !on_stop_grid(depth, stop_increment)) {
+                return Some(c);
+            }
+        }
+
+        let candidate = |i: usize| {
+            let past_travel_depth = self
+                .travel_gas
+                .is_some_and(|t| t.gas == i && depth >= t.until_depth);
+            usable(i)
+                && !past_travel_depth
+                && (Some(i) == current
+                    || deco_settings
+                        .icd_settings
+                        .no_violation(ambient, current.map(|c| &gases[c]), &gases[i], loading))
+        };
+        let best = match self.priority {
+            Some(order) => order
+                .iter()
+                .copied()
+                .find(|i| *i < NUM_GASES && candidate(*i)),
+            None => (0..NUM_GASES).filter(|i| candidate(*i)).reduce(|a, b| {
+                if is_better_mix(&gases[a], &gases[b]) {
+                    a
+                } else {
+                    b
+                }
+            }),
+        };
+
+        let (Some(c), Some(b)) = (current, best) else {
+            return best.or(current);
+        };
+        let gain = gases[b].fo2() - gases[c].fo2();
+        if (gain > 0.0 && gain < self.min_fo2_gain) || (gain < 0.0 && self.no_leaner_switch) {
+            Some(c)
+        } else {
+            Some(b)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::*;
+    use crate::{
+        deco_algorithm::calc_deco_schedule,
+        deco_model::{BUEHLMANN_ZHL16C, DecoModel},
+        dive::StopSchedule,
+        gas::{
+            AIR, GasDensitySettings, IcdSettings, MAX_PO2_DECO, NX50, NX100, NarcosisSettings,
+            TMX18_45, best_available_mix,
+        },
+        pressure_unit::Pa,
+        setup::DINC,
+    };
+
+    const GASES: [GasMix<f32>; 4] = [TMX18_45, AIR, NX50, NX100];
+
+    fn deco_settings() -> DecoSettings<'static, Pa> {
+        DecoSettings {
+            gas_density_settings: GasDensitySettings::Ignore,
+            narcosis_settings: NarcosisSettings::Ignore,
+            icd_settings: IcdSettings::Ignore,
+            max_deco_po2: MAX_PO2_DECO.to_pa(),
+            surface_pressure: msw::new(0.0).to_pa(),
+            gf_low: 0.5,
+            gf_high: 0.85,
+            last_deco_stop: msw::new(3.0),
+            ascent_rate_per_meter: Duration::from_secs(6),
+            gas_switch_policy: GasSwitchPolicy::default(),
+        }
+    }
+
+    fn select(policy: &GasSwitchPolicy, current: Option<usize>, depth: f32) -> Option<usize> {
+        select_on_grid(policy, current, depth, DINC)
+    }
+
+    fn select_on_grid(
+        policy: &GasSwitchPolicy,
+        current: Option<usize>,
+        depth: f32,
+        stop_increment: msw,
+    ) -> Option<usize> {
+        let settings = deco_settings();
+        let loading = TissuesLoading::<1, Pa>::new(settings.surface_pressure, &AIR);
+        policy.select(
+            current,
+            msw::new(depth),
+            stop_increment,
+            settings.max_deco_po2,
+            &GASES,
+            &[true; 4],
+            &loading,
+            &settings,
+        )
+    }
+
+    #[test]
+    fn default_policy_matches_best_available_mix() {
+        let settings = deco_settings();
+        let loading = TissuesLoading::<1, Pa>::new(settings.surface_pressure, &AIR);
+        for depth in [0.0, 3.0, 6.0, 9.0, 21.0, 40.0, 60.0] {
+            let ambient = ambient_pressure_at_depth(settings.surface_pressure, msw::new(depth));
+            let expected = best_available_mix(
+                settings.max_deco_po2,
+                ambient,
+                &GASES,
+                &[true; 4],
+                None,
+                &loading,
+                &settings.icd_settings,
+                &settings.gas_density_settings,
+                &settings.narcosis_settings,
+            )
+            .map(|(i, _g)| i);
+            assert_eq!(
+                select(&GasSwitchPolicy::default(), Some(0), depth),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn small_gains_and_off_grid_depths_keep_the_gas() {
+        let picky = GasSwitchPolicy {
+            min_fo2_gain: 0.35,
+            ..Default::default()
+        };
+        // Air to nitrox 50 adds 29 % O2, air to O2 at 6 m adds 78 %
+        assert_eq!(select(&picky, Some(1), 21.0), Some(1));
+        assert_eq!(select(&picky, Some(1), 6.0), Some(3));
+
+        let on_stops = GasSwitchPolicy {
+            at_stop_depths_only: true,
+            ..Default::default()
+        };
+        assert_eq!(select(&on_stops, Some(1), 19.5), Some(1));
+        assert_eq!(select(&on_stops, Some(1), 18.1), Some(2));
+
+        // The grid is the model's stop increment: 21 m is a stop on a 3 m grid only
+        let six_m = msw::new(6.0);
+        assert_eq!(select(&on_stops, Some(1), 21.0), Some(2));
+        assert_eq!(select_on_grid(&on_stops, Some(1), 21.0, six_m), Some(1));
+        assert_eq!(select_on_grid(&on_stops, Some(1), 18.0, six_m), Some(2));
+    }
+
+    #[test]
+    fn travel_gas_priority_and_no_leaner_switch() {
+        let travel = GasSwitchPolicy {
+            travel_gas: Some(TravelGas {
+                gas: 1,
+                until_depth: msw::new(30.0),
+            }),
+            ..Default::default()
+        };
+        assert_eq!(select(&travel, Some(1), 15.0), Some(1));
+        assert_eq!(select(&travel, Some(1), 30.0), Some(0));
+        assert_eq!(select(&travel, Some(0), 40.0), Some(0));
+
+        let order = [2, 3];
+        let prefer_nx50 = GasSwitchPolicy {
+            priority: Some(&order),
+            ..Default::default()
+        };
+        assert_eq!(select(&prefer_nx50, Some(3), 6.0), Some(2));
+        let no_leaner = GasSwitchPolicy {
+            no_leaner_switch: true,
+            ..prefer_nx50
+        };
+        assert_eq!(select(&no_leaner, Some(3), 6.0), Some(3));
+        // O2 is unusable at 9 m, so the leaner gas is the only way out
+        assert_eq!(select(&no_leaner, Some(3), 9.0), Some(2));
+    }
+
+    #[test]
+    fn schedule_builder_honours_the_policy() {
+        let bottom = msw::new(60.0);
+        let mut loading = TissuesLoading::new(msw::new(0.0).to_pa(), &AIR);
+        BUEHLMANN_ZHL16C.update_model_state(
+            &mut loading,
+            &TMX18_45,
+            bottom.to_pa(),
+            &Duration::from_secs(25 * 60),
+        );
+        let uses_o2 = |s: &StopSchedule<30>| s.stops().iter().any(|s| s.gas() == Some(NX100));
+
+        let schedule = |policy: GasSwitchPolicy| -> StopSchedule<30> {
+            calc_deco_schedule(
+                &BUEHLMANN_ZHL16C,
+                &loading,
+                bottom,
+                0,
+                &GASES,
+                &[true; 4],
+                &DecoSettings {
+                    gas_switch_policy: policy,
+                    ..deco_settings()
+                },
+            )
+            .expect("schedule")
+        };
+        assert!(uses_o2(&schedule(GasSwitchPolicy::default())));
+
+        let order = [2, 0];
+        let no_o2 = schedule(GasSwitchPolicy {
+            priority: Some(&order),
+            ..Default::default()
+        });
+        assert!(!uses_o2(&no_o2));
+    }
+}
@@ -0,0 +1,313 @@
+use core::time::Duration;
+
+#[allow(unused)]
+use num::Float;
+
+use crate::{
+    deco_model::DecoModel,
+    gas::{Gas, GasMix, InertGas, RULE_OF_FIFTHS, TissueGradients, TissuesLoading},
+    pressure_unit::{AbsPressure, Bar, Pa, Pressure, ambient_pressure_at_depth, msw},
+    setup::DINC,
+};
+
+/// Time after a switch over which the transient of the total inert gas tension is followed.
+const ICD_WINDOW: Duration = Duration::from_secs(20 * 60);
+const ICD_STEP: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TissueIcd {
+    pub gradients: TissueGradients,
+    /**
+     * Largest overshoot of the inert gas tension above both its value at the
+     * switch and the inspired inert gas. Plain uptake or washout never overshoots.
+     */
+    pub supersaturation: Bar,
+}
+
+impl TissueIcd {
+    /// Supersaturation, or the N2 uptake beyond the rule of fifths if that is larger.
+    pub fn excess(&self) -> Bar {
+        let fifths = if self.gradients.is_counterdiffusion() {
+            self.gradients.n2_in.to_f32() - RULE_OF_FIFTHS * self.gradients.he_out.to_f32()
+        } else {
+            0.0
+        };
+        Bar::new(self.supersaturation.to_f32().max(fifths).max(0.0))
+    }
+}
+
+/** Isobaric counterdiffusion caused by switching from one gas to another at `depth`. */
+#[derive(Debug, Clone, Copy)]
+pub struct IcdAnalysis<const NUM_TISSUES: usize> {
+    pub depth: msw,
+    /// Rise of FN2 from the old to the new gas.
+    pub delta_fn2: f32,
+    /// Drop of FHe from the old to the new gas.
+    pub delta_fhe: f32,
+    pub tissues: [TissueIcd; NUM_TISSUES],
+}
+
+impl<const NUM_TISSUES: usize> IcdAnalysis<NUM_TISSUES> {
+    /// ΔN2 / ΔHe of the switch; infinite if N2 rises without He dropping.
+    pub fn n2_he_ratio(&self) -> f32 {
+        n2_he_switch_ratio(self.delta_fn2, self.delta_fhe)
+    }
+
+    /// The N2 fraction rises by at most a fifth of the drop in He.
+    pub fn meets_rule_of_fifths(&self) -> bool {
+        self.n2_he_ratio() <= RULE_OF_FIFTHS
+    }
+
+    pub fn is_counterdiffusion(&self) -> bool {
+        self.tissues
+            .iter()
+            .any(|t| t.gradients.is_counterdiffusion())
+    }
+
+    pub fn max_supersaturation(&self) -> Bar {
+        Bar::new(
+            self.tissues
+                .iter()
+                .map(|t| t.supersaturation.to_f32())
+                .fold(0.0, f32::max),
+        )
+    }
+
+    /// Worst `TissueIcd::excess` of all compartments.
+    pub fn max_excess(&self) -> Bar {
+        Bar::new(
+            self.tissues
+                .iter()
+                .map(|t| t.excess().to_f32())
+                .fold(0.0, f32::max),
+        )
+    }
+}
+
+fn n2_he_switch_ratio(delta_n2: f32, delta_he: f32) -> f32 {
+    if delta_n2 <= 0.0 {
+        0.0
+    } else if delta_he <= 0.0 {
+        f32::INFINITY
+    } else {
+        delta_n2 / delta_he
+    }
+}
+
+/// `IcdAnalysis::meets_rule_of_fifths` of a switch from `old_gas` to `new_gas` at `depth`.
+pub fn switch_meets_rule_of_fifths<P: const AbsPressure, G: Gas>(
+    old_gas: &G,
+    new_gas: &G,
+    depth: P,
+) -> bool {
+    let delta_n2 = (new_gas.pn2(depth) - old_gas.pn2(depth)).to_f32();
+    let delta_he = (old_gas.phe(depth) - new_gas.phe(depth)).to_f32();
+    n2_he_switch_ratio(delta_n2, delta_he) <= RULE_OF_FIFTHS
+}
+
+/**
+* Analyses a switch from `old_gas` to `new_gas` at `depth` with the tissue state
+* `loading`. The supersaturation follows the model's own kinetics per gas on
+* `new_gas` at `depth` for `ICD_WINDOW`.
+*/
+pub fn analyse_gas_switch<M, const NUM_TISSUES: usize>(
+    model: &M,
+    loading: &TissuesLoading<NUM_TISSUES, Pa>,
+    old_gas: &GasMix<f32>,
+    new_gas: &GasMix<f32>,
+    depth: msw,
+    surface_pressure: Pa,
+) -> IcdAnalysis<NUM_TISSUES>
+where
+    M: DecoModel<NUM_TISSUES, Pa>,
+{
+    let ambient = ambient_pressure_at_depth(surface_pressure, depth);
+    let total = |l: &TissuesLoading<NUM_TISSUES, Pa>, i: usize| l.total_inert(i).to_bar().to_f32();
+
+    let inspired: f32 = InertGas::ALL
+        .iter()
+        .map(|gas| new_gas.p_inert(*gas, ambient).to_bar().to_f32())
+        .sum();
+
+    let mut peak = [0.0_f32; NUM_TISSUES];
+    let mut after = loading.clone();
+    let mut elapsed = Duration::ZERO;
+    while elapsed < ICD_WINDOW {
+        model.update_model_state(&mut after, new_gas, ambient, &ICD_STEP);
+        elapsed += ICD_STEP;
+        for (i, p) in peak.iter_mut().enumerate() {
+            *p = p.max(total(&after, i) - total(loading, i).max(inspired));
+        }
+    }
+
+    let gradients = loading.switch_gradients(ambient, new_gas);
+    let mut tissues = [TissueIcd {
+        gradients: gradients[0],
+        supersaturation: Bar::new(0.0),
+    }; NUM_TISSUES];
+    for (i, t) in tissues.iter_mut().enumerate() {
+        t.gradients = gradients[i];
+        t.supersaturation = Bar::new(peak[i]);
+    }
+    IcdAnalysis {
+        depth,
+        delta_fn2: new_gas.fn2() - old_gas.fn2(),
+        delta_fhe: old_gas.fhe() - new_gas.fhe(),
+        tissues,
+    }
+}
+
+/**
+* Stop depth between `shallowest` and `deepest` where the switch has the
+* smallest `IcdAnalysis::max_excess`, preferring the deeper of equal ones.
+* `loading` is used for every depth, so the ascent to a shallower switch is
+* not accounted for. `deepest` is usually the MOD of `new_gas`.
+*/
+pub fn best_switch_depth<M, const NUM_TISSUES: usize>(
+    model: &M,
+    loading: &TissuesLoading<NUM_TISSUES, Pa>,
+    old_gas: &GasMix<f32>,
+    new_gas: &GasMix<f32>,
+    deepest: msw,
+    shallowest: msw,
+    surface_pressure: Pa,
+) -> Option<IcdAnalysis<NUM_TISSUES>>
+where
+    M: DecoModel<NUM_TISSUES, Pa>,
+{
+    let first = (shallowest.to_f32() / DINC.to_f32()).ceil() as usize;
+    let last = (deepest.to_f32() / DINC.to_f32()).floor() as usize;
+    (first..=last)
+        .rev()
+        .map(|k| {
+            let depth = msw::new(DINC.to_f32() * k as f32);
+            analyse_gas_switch(model, loading, old_gas, new_gas, depth, surface_pressure)
+        })
+        .reduce(|best, a| {
+            if a.max_excess().to_f32() < best.max_excess().to_f32() {
+                a
+            } else {
+                best
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        deco_model::BUEHLMANN_ZHL16C,
+        gas::{AIR, IcdSettings, NX50, TMX18_45, TMX21_35},
+    };
+
+    fn trimix_loading() -> TissuesLoading<16, Pa> {
+        let surface = msw::new(0.0).to_pa();
+        let mut loading = TissuesLoading::new(surface, &AIR);
+        BUEHLMANN_ZHL16C.update_model_state(
+            &mut loading,
+            &TMX18_45,
+            msw::new(60.0).to_pa(),
+            &Duration::from_secs(25 * 60),
+        );
+        loading
+    }
+
+    #[test]
+    fn rule_of_fifths_of_deco_switches() {
+        let surface = msw::new(0.0).to_pa();
+        let loading = trimix_loading();
+        let to_nx50 = analyse_gas_switch(
+            &BUEHLMANN_ZHL16C,
+            &loading,
+            &TMX18_45,
+            &NX50,
+            msw::new(21.0),
+            surface,
+        );
+        // 37 % N2 to 50 % N2 while dropping 45 % He
+        assert!((to_nx50.n2_he_ratio() - 0.13 / 0.45).abs() < 1e-3);
+        assert!(!to_nx50.meets_rule_of_fifths());
+        assert!(to_nx50.is_counterdiffusion());
+
+        let trimix50_15 = GasMix::new(0.5, 0.15).expect("50 + 15 < 100");
+        let to_50_15 = analyse_gas_switch(
+            &BUEHLMANN_ZHL16C,
+            &loading,
+            &TMX18_45,
+            &trimix50_15,
+            msw::new(21.0),
+            surface,
+        );
+        assert!(to_50_15.meets_rule_of_fifths());
+        assert!(to_50_15.max_excess() < to_nx50.max_excess());
+
+        // Gas selection applies the same rule
+        let ambient = ambient_pressure_at_depth(surface, msw::new(21.0));
+        let allowed = |new_gas: &GasMix<f32>| {
+            IcdSettings::RuleOfFifths.no_violation(ambient, Some(&TMX18_45), new_gas, &loading)
+        };
+        assert_eq!(allowed(&NX50), to_nx50.meets_rule_of_fifths());
+        assert_eq!(allowed(&trimix50_15), to_50_15.meets_rule_of_fifths());
+    }
+
+    #[test]
+    fn helium_after_nitrogen_supersaturates_the_fast_tissues() {
+        let surface = msw::new(0.0).to_pa();
+        let mut loading = TissuesLoading::new(surface, &AIR);
+        BUEHLMANN_ZHL16C.update_model_state(
+            &mut loading,
+            &AIR,
+            msw::new(30.0).to_pa(),
+            &Duration::from_secs(60 * 60),
+        );
+        let analysis = analyse_gas_switch(
+            &BUEHLMANN_ZHL16C,
+            &loading,
+            &AIR,
+            &TMX21_35,
+            msw::new(30.0),
+            surface,
+        );
+        assert!(!analysis.is_counterdiffusion());
+        assert!(analysis.max_supersaturation().to_f32() > 0.05);
+        // He enters faster than N2 leaves in the fast compartments only
+        assert!(analysis.tissues[0].supersaturation > analysis.tissues[15].supersaturation);
+    }
+
+    #[test]
+    fn shallower_switch_reduces_the_excess() {
+        let surface = msw::new(0.0).to_pa();
+        let loading = trimix_loading();
+        let best = best_switch_depth(
+            &BUEHLMANN_ZHL16C,
+            &loading,
+            &TMX18_45,
+            &NX50,
+            msw::new(21.0),
+            msw::new(9.0),
+            surface,
+        )
+        .expect("depths in range");
+        let deepest = analyse_gas_switch(
+            &BUEHLMANN_ZHL16C,
+            &loading,
+            &TMX18_45,
+            &NX50,
+            msw::new(21.0),
+            surface,
+        );
+        assert!(best.depth < msw::new(21.0));
+        assert!(best.max_excess().to_f32() < deepest.max_excess().to_f32());
+
+        let ambient = ambient_pressure_at_depth(surface, best.depth);
+        assert_eq!(
+            IcdSettings::AnyCounterdiffusion.no_violation(
+                ambient,
+                Some(&TMX18_45),
+                &NX50,
+                &loading
+            ),
+            !best.is_counterdiffusion()
+        );
+    }
+}
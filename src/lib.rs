@@ -10,31 +10,42 @@
 #![feature(const_convert)]
 #![feature(const_cmp)]
 #![feature(derive_const)]
-// Only required for buehlmann / non-"lin_exp"
 #![feature(const_array)]
 #![feature(const_option_ops)]
 
 #[cfg(test)]
 extern crate std;
 
+pub mod bailout;
+pub mod best_mix;
+pub mod blending;
+pub mod cylinder;
 pub mod deco_algorithm;
+pub mod deco_model;
 pub mod depth_utils;
 pub mod display_utils;
 pub mod dive;
+pub mod dive_plan;
 pub mod gas;
+pub mod gas_plan;
+pub mod gas_policy;
+pub mod icd;
+pub mod model_parameters;
 pub mod mptt;
-#[cfg(not(feature = "lin_exp"))]
-mod mptt_buehlmann;
-#[cfg(feature = "lin_exp")]
-mod mptt_thalmann;
+pub mod mptt_buehlmann;
+pub mod mptt_thalmann;
+pub mod mptt_vpm;
 pub mod o2tox;
+pub mod pdcs;
+pub mod plan_check;
 pub mod pressure_unit;
 pub mod setup;
+pub mod validation;
 mod time_utils;
 mod update;
 mod update_common;
 mod update_exp;
-#[cfg(feature = "lin_exp")]
 mod update_exp_lin;
+mod update_vpm;
 
 pub use update::{loadings_from_dive_profile, tissue_mvalues_with_gf};
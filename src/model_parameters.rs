@@ -0,0 +1,288 @@
+#[allow(unused)]
+use num::Float;
+
+use crate::{
+    deco_model::ThalmannModel,
+    mptt::{MValues, Tissue, TissueRow},
+    mptt_thalmann::{NUM_STOP_DEPTHS_THALMANN, ThalmannSettings, generate_linear_mptt},
+    pressure_unit::{Pa, Pressure, msw},
+};
+
+const FEET_PER_METER: f32 = 3.280_84;
+
+/** Source of the MPTT (M-values) of a Thalmann parameter set. */
+#[derive(Clone, Copy)]
+pub enum MpttSource<'a, const NUM_TISSUES: usize> {
+    /// M_i = β0_i + β1_i × D, with β0 in fsw and β1 in fsw per fsw of depth.
+    Linear {
+        beta0_fsw: [f32; NUM_TISSUES],
+        beta1: [f32; NUM_TISSUES],
+    },
+    /// Published table rows, one per stop starting at the first stop increment.
+    /// Rows beyond the last one are extrapolated linearly from the last two.
+    Rows(&'a [TissueRow<NUM_TISSUES, Pa>]),
+}
+
+/** Runtime Thalmann parameter set: kinetics, MPTT, crossover constants and stop spacing. */
+#[derive(Clone, Copy)]
+pub struct ModelParameters<'a, const NUM_TISSUES: usize> {
+    pub tissues: [Tissue; NUM_TISSUES],
+    /// He half-times and SDRs; `None` uses `tissues` for He as well.
+    pub he_tissues: Option<[Tissue; NUM_TISSUES]>,
+    pub mptt: MpttSource<'a, NUM_TISSUES>,
+    pub settings: ThalmannSettings,
+    pub stop_increment: msw,
+}
+
+impl<'a, const NUM_TISSUES: usize> ModelParameters<'a, NUM_TISSUES> {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if NUM_TISSUES == 0 {
+            return Err("At least one tissue is required.");
+        }
+        let stop_increment = self.stop_increment.to_f32();
+        if !(stop_increment.is_finite() && stop_increment > 0.0) {
+            return Err("Stop increment must be positive.");
+        }
+        for tissue in self.tissues.iter().chain(self.he_tissues.iter().flatten()) {
+            if !(tissue.half_time.is_finite() && tissue.half_time > 0.0) {
+                return Err("Half-times must be positive.");
+            }
+            if !(tissue.sdr.is_finite() && tissue.sdr > 0.0) {
+                return Err("SDRs must be positive.");
+            }
+        }
+        let settings = &self.settings;
+        if [settings.pvo2, settings.pvco2, settings.ph2o, settings.pbovp]
+            .iter()
+            .any(|p| !(p.to_f32().is_finite() && p.to_f32() >= 0.0))
+        {
+            return Err("Crossover constants must be non-negative.");
+        }
+        match self.mptt {
+            MpttSource::Linear { beta0_fsw, beta1 } => {
+                if beta0_fsw.iter().any(|b| !b.is_finite()) {
+                    return Err("β0 must be finite.");
+                }
+                if beta1.iter().any(|b| !(b.is_finite() && *b >= 0.0)) {
+                    return Err("β1 must be non-negative.");
+                }
+            }
+            MpttSource::Rows(rows) => {
+                if rows.len() < 2 {
+                    return Err("At least two MPTT rows are required.");
+                }
+                if rows.len() > NUM_STOP_DEPTHS_THALMANN {
+                    return Err("Too many MPTT rows.");
+                }
+                for (k, row) in rows.iter().enumerate() {
+                    let expected = self.stop_increment.to_f32() * (k + 1) as f32;
+                    if (row.depth.to_f32() - expected).abs() > 1E-3 {
+                        return Err("MPTT rows must be spaced by the stop increment.");
+                    }
+                }
+            }
+        }
+        let m_values = self.m_values();
+        for row in &m_values {
+            if row.max_saturation.iter().any(|m| m.to_f32() <= 0.0) {
+                return Err("MPTT must be positive at every stop.");
+            }
+        }
+        Ok(())
+    }
+
+    /// Validated Thalmann model, ready for `calc_deco_schedule`.
+    pub fn thalmann_model(&self) -> Result<ThalmannModel<NUM_TISSUES, Pa>, &'static str> {
+        self.validate()?;
+        let model = ThalmannModel::new(self.tissues, self.m_values())
+            .with_settings(self.settings)
+            .with_stop_increment(self.stop_increment);
+        Ok(match self.he_tissues {
+            Some(he) => model.with_helium_kinetics(he),
+            None => model,
+        })
+    }
+
+    fn m_values(&self) -> MValues<Pa, NUM_TISSUES, NUM_STOP_DEPTHS_THALMANN> {
+        let depth_step = self.stop_increment.to_f32();
+        match self.mptt {
+            MpttSource::Linear { beta0_fsw, beta1 } => {
+                let step_fsw = depth_step * FEET_PER_METER;
+                generate_linear_mptt(beta0_fsw, beta1.map(|b| b * step_fsw), depth_step)
+            }
+            MpttSource::Rows(rows) => {
+                let mut table = [TissueRow::empty_pa(); NUM_STOP_DEPTHS_THALMANN];
+                let n = rows.len().min(NUM_STOP_DEPTHS_THALMANN);
+                table[..n].copy_from_slice(&rows[..n]);
+                for k in n..NUM_STOP_DEPTHS_THALMANN {
+                    let (prev, last) = (table[k - 2], table[k - 1]);
+                    let mut max_saturation = last.max_saturation;
+                    for (i, m) in max_saturation.iter_mut().enumerate() {
+                        *m = last.max_saturation[i] + last.max_saturation[i]
+                            - prev.max_saturation[i];
+                    }
+                    table[k] = TissueRow {
+                        depth: msw::new(depth_step * (k + 1) as f32),
+                        max_saturation,
+                    };
+                }
+                table
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::*;
+    use crate::{
+        deco_algorithm::{DecoSettings, calc_deco_schedule},
+        deco_model::{DecoModel, THALMANN_XVAL_HE9_040},
+        dive::StopSchedule,
+        gas::{
+            AIR, GasDensitySettings, IcdSettings, MAX_PO2_DECO, NarcosisSettings, TissuesLoading,
+        },
+        gas_policy::GasSwitchPolicy,
+        mptt_thalmann::{
+            NUM_STOP_DEPTHS_THALMANN_FIXED, THALMANN_DEFAULT_SETTINGS, TISSUES, XVAL_HE9_040_F32,
+        },
+        setup::DINC,
+    };
+
+    const XVAL_HE9_040_LINEAR: ModelParameters<'static, 5> = ModelParameters {
+        tissues: TISSUES,
+        he_tissues: None,
+        mptt: MpttSource::Linear {
+            beta0_fsw: [75.157, 54.157, 73.157, 22.046, 26.579],
+            beta1: [1.0, 1.0, 1.0, 2.0, 1.1882],
+        },
+        settings: THALMANN_DEFAULT_SETTINGS,
+        stop_increment: DINC,
+    };
+
+    #[test]
+    fn linear_parameters_reproduce_builtin_model() {
+        let model = XVAL_HE9_040_LINEAR.thalmann_model().expect("valid");
+        for (row, expected) in model
+            .m_values()
+            .iter()
+            .zip(THALMANN_XVAL_HE9_040.m_values())
+        {
+            assert_eq!(row.depth, expected.depth);
+            for i in 0..5 {
+                let diff = row.max_saturation[i].to_f32() - expected.max_saturation[i].to_f32();
+                assert!(diff.abs() < 100.0, "{diff} Pa at {:?}", row.depth);
+            }
+        }
+    }
+
+    #[test]
+    fn helium_tissues_are_applied() {
+        let mut he_tissues = TISSUES;
+        for tissue in &mut he_tissues {
+            tissue.half_time /= 2.65;
+        }
+        let params = ModelParameters {
+            he_tissues: Some(he_tissues),
+            ..XVAL_HE9_040_LINEAR
+        };
+        let model = params.thalmann_model().expect("valid");
+        for (i, tissue) in model.tissues().iter().enumerate() {
+            assert_eq!(tissue.n2.half_time, TISSUES[i].half_time);
+            assert_eq!(tissue.he.half_time, he_tissues[i].half_time);
+        }
+        let model = XVAL_HE9_040_LINEAR.thalmann_model().expect("valid");
+        assert!(model.tissues().iter().all(|t| t.he.half_time == t.n2.half_time));
+    }
+
+    #[test]
+    fn table_rows_are_extrapolated() {
+        let params = ModelParameters {
+            tissues: TISSUES,
+            he_tissues: None,
+            mptt: MpttSource::Rows(&XVAL_HE9_040_F32),
+            settings: THALMANN_DEFAULT_SETTINGS,
+            stop_increment: DINC,
+        };
+        let model = params.thalmann_model().expect("valid");
+        let m = model.m_values();
+        let last = NUM_STOP_DEPTHS_THALMANN_FIXED - 1;
+        let step = m[last].max_saturation[0] - m[last - 1].max_saturation[0];
+        let next = m[last + 1].max_saturation[0] - m[last].max_saturation[0];
+        assert!((step.to_f32() - next.to_f32()).abs() < 1.0);
+        assert_eq!(m[last + 1].depth, msw::new(3.0 * (last + 2) as f32));
+    }
+
+    #[test]
+    fn schedule_follows_the_stop_increment() {
+        let params = ModelParameters {
+            stop_increment: msw::new(6.0),
+            ..XVAL_HE9_040_LINEAR
+        };
+        let model = params.thalmann_model().expect("valid");
+        assert_eq!(model.m_values()[1].depth, msw::new(12.0));
+
+        let mut loading = TissuesLoading::new(msw::new(0.0).to_pa(), &AIR);
+        model.update_model_state(
+            &mut loading,
+            &AIR,
+            msw::new(40.0).to_pa(),
+            &Duration::from_secs(40 * 60),
+        );
+        let settings = DecoSettings {
+            gas_density_settings: GasDensitySettings::Ignore,
+            narcosis_settings: NarcosisSettings::Ignore,
+            max_deco_po2: MAX_PO2_DECO.to_pa(),
+            surface_pressure: msw::new(0.0).to_pa(),
+            icd_settings: IcdSettings::Ignore,
+            gf_low: 1.0,
+            gf_high: 1.0,
+            last_deco_stop: msw::new(6.0),
+            ascent_rate_per_meter: Duration::from_secs(6),
+            gas_switch_policy: GasSwitchPolicy::default(),
+        };
+        let schedule: StopSchedule<15> =
+            calc_deco_schedule(&model, &loading, msw::new(40.0), 0, &[AIR], &[true], &settings)
+                .expect("schedule");
+
+        assert!(schedule.first_stop().is_some());
+        for stop in schedule.stops() {
+            let k = stop.depth().to_f32() / 6.0;
+            assert!((k - k.round()).abs() < 1E-4, "{:?} is off the 6 msw grid", stop.depth());
+        }
+    }
+
+    #[test]
+    fn invalid_parameters_are_rejected() {
+        let mut params = XVAL_HE9_040_LINEAR;
+        params.tissues[0].half_time = 0.0;
+        assert!(params.validate().is_err());
+
+        let mut params = XVAL_HE9_040_LINEAR;
+        params.stop_increment = msw::new(0.0);
+        assert!(params.validate().is_err());
+
+        let mut he_tissues = TISSUES;
+        he_tissues[1].sdr = 0.0;
+        let params = ModelParameters {
+            he_tissues: Some(he_tissues),
+            ..XVAL_HE9_040_LINEAR
+        };
+        assert!(params.validate().is_err());
+
+        let mut params = XVAL_HE9_040_LINEAR;
+        params.settings.pbovp = Pa::new(-1.0);
+        assert!(params.validate().is_err());
+
+        let params = ModelParameters {
+            tissues: TISSUES,
+            he_tissues: None,
+            mptt: MpttSource::Rows(&XVAL_HE9_040_F32[..1]),
+            settings: THALMANN_DEFAULT_SETTINGS,
+            stop_increment: DINC,
+        };
+        assert!(params.thalmann_model().is_err());
+    }
+}
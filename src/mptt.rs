@@ -1,4 +1,7 @@
-use crate::pressure_unit::{AbsPressure, Pa, msw};
+use crate::{
+    gas::InertGas,
+    pressure_unit::{AbsPressure, Pa, msw},
+};
 
 /** Maximum Permissible Tissue Tension */
 
@@ -8,6 +11,42 @@ pub struct Tissue {
     pub sdr: f32, /* Saturation Desaturation Ration */
 }
 
+/** Thalmann compartment with separate N2 and He kinetics. */
+#[derive(Copy, Clone)]
+pub struct ThalmannTissue {
+    pub n2: Tissue,
+    pub he: Tissue,
+}
+
+impl ThalmannTissue {
+    /// Compartment whose He kinetics equal its N2 kinetics, as in the published sets.
+    pub const fn uniform(tissue: Tissue) -> Self {
+        ThalmannTissue {
+            n2: tissue,
+            he: tissue,
+        }
+    }
+
+    pub fn is_uniform(&self) -> bool {
+        self.n2.half_time == self.he.half_time && self.n2.sdr == self.he.sdr
+    }
+
+    /// Kinetics of the reference gas with the half-time scaled by `InertGas::half_time_factor`.
+    pub fn for_gas(&self, gas: InertGas) -> Tissue {
+        match gas {
+            InertGas::N2 => self.n2,
+            InertGas::He => self.he,
+            _ => {
+                let reference = self.for_gas(gas.reference());
+                Tissue {
+                    half_time: reference.half_time * gas.half_time_factor(),
+                    ..reference
+                }
+            }
+        }
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct TissueRow<const TISSUES: usize, P: const AbsPressure> {
     pub depth: msw,
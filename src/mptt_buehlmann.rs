@@ -1,217 +1,47 @@
 use crate::{
+    gas::InertGas,
     mptt::{MValues, TissueRow},
-    pressure_unit::{AbsPressure, Pa, Pressure, msw},
+    pressure_unit::{AbsPressure, Bar, Pa, Pressure, msw},
 };
 
 pub const NUM_TISSUES_BUEHLMANN: usize = 16;
 pub const NUM_STOP_DEPTHS_BUEHLMANN: usize = 32;
 pub const DEFAULT_BUEHLMANN_HE_RATIO: f32 = 0.0;
 
+#[derive(Clone, Copy)]
 pub struct BuehlmannTissue {
     pub n2: BuehlmannTissueGas,
     pub he: BuehlmannTissueGas,
 }
+#[derive(Clone, Copy)]
 pub struct BuehlmannTissueGas {
     pub half_time: f32,
-    pub a: msw,
+    pub a: Bar,
     pub b: f32,
 }
 
-pub const TISSUES: [BuehlmannTissue; NUM_TISSUES_BUEHLMANN] = [
-    BuehlmannTissue {
-        n2: BuehlmannTissueGas {
-            half_time: 4.0,
-            a: msw::new(1.1696),
-            b: 0.5578,
-        },
-        he: BuehlmannTissueGas {
-            half_time: 4.0,
-            a: msw::new(1.6189),
-            b: 0.4770,
-        },
-    },
-    BuehlmannTissue {
-        n2: BuehlmannTissueGas {
-            half_time: 8.0,
-            a: msw::new(1.0000),
-            b: 0.6514,
-        },
-        he: BuehlmannTissueGas {
-            half_time: 8.0,
-            a: msw::new(1.3830),
-            b: 0.5747,
-        },
-    },
-    BuehlmannTissue {
-        n2: BuehlmannTissueGas {
-            half_time: 12.5,
-            a: msw::new(0.8618),
-            b: 0.7222,
-        },
-        he: BuehlmannTissueGas {
-            half_time: 12.5,
-            a: msw::new(1.1919),
-            b: 0.6527,
-        },
-    },
-    BuehlmannTissue {
-        n2: BuehlmannTissueGas {
-            half_time: 18.5,
-            a: msw::new(0.7562),
-            b: 0.7825,
-        },
-        he: BuehlmannTissueGas {
-            half_time: 18.5,
-            a: msw::new(1.0458),
-            b: 0.7223,
-        },
-    },
-    BuehlmannTissue {
-        n2: BuehlmannTissueGas {
-            half_time: 27.0,
-            a: msw::new(0.6200),
-            b: 0.8126,
-        },
-        he: BuehlmannTissueGas {
-            half_time: 27.0,
-            a: msw::new(0.9220),
-            b: 0.7582,
-        },
-    },
-    BuehlmannTissue {
-        n2: BuehlmannTissueGas {
-            half_time: 38.3,
-            a: msw::new(0.5034),
-            b: 0.8434,
-        },
-        he: BuehlmannTissueGas {
-            half_time: 38.3,
-            a: msw::new(0.8205),
-            b: 0.7957,
-        },
-    },
-    BuehlmannTissue {
-        n2: BuehlmannTissueGas {
-            half_time: 54.3,
-            a: msw::new(0.4410),
-            b: 0.8693,
-        },
-        he: BuehlmannTissueGas {
-            half_time: 54.3,
-            a: msw::new(0.7305),
-            b: 0.8279,
-        },
-    },
-    BuehlmannTissue {
-        n2: BuehlmannTissueGas {
-            half_time: 77.0,
-            a: msw::new(0.4000),
-            b: 0.8910,
-        },
-        he: BuehlmannTissueGas {
-            half_time: 77.0,
-            a: msw::new(0.6502),
-            b: 0.8553,
-        },
-    },
-    BuehlmannTissue {
-        n2: BuehlmannTissueGas {
-            half_time: 109.0,
-            a: msw::new(0.3750),
-            b: 0.9092,
-        },
-        he: BuehlmannTissueGas {
-            half_time: 109.0,
-            a: msw::new(0.5950),
-            b: 0.8757,
-        },
-    },
-    BuehlmannTissue {
-        n2: BuehlmannTissueGas {
-            half_time: 146.0,
-            a: msw::new(0.3500),
-            b: 0.9222,
-        },
-        he: BuehlmannTissueGas {
-            half_time: 146.0,
-            a: msw::new(0.5545),
-            b: 0.8903,
-        },
-    },
-    BuehlmannTissue {
-        n2: BuehlmannTissueGas {
-            half_time: 187.0,
-            a: msw::new(0.3295),
-            b: 0.9319,
-        },
-        he: BuehlmannTissueGas {
-            half_time: 187.0,
-            a: msw::new(0.5333),
-            b: 0.8997,
-        },
-    },
-    BuehlmannTissue {
-        n2: BuehlmannTissueGas {
-            half_time: 239.0,
-            a: msw::new(0.3065),
-            b: 0.9403,
-        },
-        he: BuehlmannTissueGas {
-            half_time: 239.0,
-            a: msw::new(0.5189),
-            b: 0.9073,
-        },
-    },
-    BuehlmannTissue {
-        n2: BuehlmannTissueGas {
-            half_time: 305.0,
-            a: msw::new(0.2835),
-            b: 0.9477,
-        },
-        he: BuehlmannTissueGas {
-            half_time: 305.0,
-            a: msw::new(0.5181),
-            b: 0.9122,
-        },
-    },
-    BuehlmannTissue {
-        n2: BuehlmannTissueGas {
-            half_time: 390.0,
-            a: msw::new(0.2610),
-            b: 0.9544,
-        },
-        he: BuehlmannTissueGas {
-            half_time: 390.0,
-            a: msw::new(0.5176),
-            b: 0.9171,
-        },
-    },
-    BuehlmannTissue {
-        n2: BuehlmannTissueGas {
-            half_time: 498.0,
-            a: msw::new(0.2480),
-            b: 0.9602,
-        },
-        he: BuehlmannTissueGas {
-            half_time: 498.0,
-            a: msw::new(0.5172),
-            b: 0.9217,
-        },
-    },
-    BuehlmannTissue {
-        n2: BuehlmannTissueGas {
-            half_time: 635.0,
-            a: msw::new(0.2327),
-            b: 0.9653,
-        },
-        he: BuehlmannTissueGas {
-            half_time: 635.0,
-            a: msw::new(0.5119),
-            b: 0.9267,
-        },
-    },
-];
+impl BuehlmannTissue {
+    /// Coefficients of the reference gas with the half-time scaled by `InertGas::half_time_factor`.
+    pub fn for_gas(&self, gas: InertGas) -> BuehlmannTissueGas {
+        match gas {
+            InertGas::N2 => self.n2,
+            InertGas::He => self.he,
+            _ => {
+                let reference = self.for_gas(gas.reference());
+                BuehlmannTissueGas {
+                    half_time: reference.half_time * gas.half_time_factor(),
+                    ..reference
+                }
+            }
+        }
+    }
+}
+
+/// ZH-L16C, the default set of the Buehlmann and VPM-B models.
+pub const TISSUES: [BuehlmannTissue; NUM_TISSUES_BUEHLMANN] =
+    BuehlmannCoefficients::ZhL16C.tissues();
 
+#[allow(dead_code)]
 #[derive(Clone, Copy)]
 pub struct TissueRowBuehlmann<const NUM_TISSUES: usize, P: const AbsPressure> {
     depth: msw,
@@ -220,7 +50,8 @@ pub struct TissueRowBuehlmann<const NUM_TISSUES: usize, P: const AbsPressure> {
     n2: TissueRow<NUM_TISSUES, P>,
 }
 
-const fn buehlmann_16c_mvalues_table(
+const fn buehlmann_mvalues_table(
+    tissues: &[BuehlmannTissue; NUM_TISSUES_BUEHLMANN],
     he_ratio: Option<f32>,
 ) -> MValues<Pa, { NUM_TISSUES_BUEHLMANN }, { NUM_STOP_DEPTHS_BUEHLMANN }> {
     let mut result: [TissueRowBuehlmann<NUM_TISSUES_BUEHLMANN, Pa>; NUM_STOP_DEPTHS_BUEHLMANN] =
@@ -234,7 +65,7 @@ const fn buehlmann_16c_mvalues_table(
     let mut depth: f32 = 0.0;
     while i < NUM_STOP_DEPTHS_BUEHLMANN {
         let d = msw(depth);
-        result[i] = buehlmann_16c_depth(d, he_ratio);
+        result[i] = buehlmann_depth(tissues, d, he_ratio);
 
         i += 1;
         depth += 3.0;
@@ -247,7 +78,8 @@ const fn buehlmann_16c_mvalues_table(
     result.map(get_total)
 }
 
-const fn buehlmann_16c_depth(
+const fn buehlmann_depth(
+    tissues: &[BuehlmannTissue; NUM_TISSUES_BUEHLMANN],
     depth: msw,
     he_ratio: Option<f32>,
 ) -> TissueRowBuehlmann<{ NUM_TISSUES_BUEHLMANN }, Pa> {
@@ -258,7 +90,7 @@ const fn buehlmann_16c_depth(
     let mut i = 0;
     let p_amb = depth.to_pa();
     while i < NUM_TISSUES_BUEHLMANN {
-        let tissue = &TISSUES[i];
+        let tissue = &tissues[i];
         (
             max_saturation[i],
             max_saturation_n2[i],
@@ -296,11 +128,202 @@ const fn buehlmann_saturation_tolerance(
     let a_combined = a_n2_pa * (1.0 - r) + a_he_pa * r;
     let b_combined = b_n2 * (1.0 - r) + b_he * r;
     (
-        a_combined + p_amb * b_combined,
-        tissue.n2.a.to_pa() + p_amb * tissue.n2.b,
-        tissue.he.a.to_pa() + p_amb * tissue.he.b,
+        a_combined + p_amb / b_combined,
+        tissue.n2.a.to_pa() + p_amb / tissue.n2.b,
+        tissue.he.a.to_pa() + p_amb / tissue.he.b,
     )
 }
 
 pub const BUEHLMANN_16C: MValues<Pa, { NUM_TISSUES_BUEHLMANN }, { NUM_STOP_DEPTHS_BUEHLMANN }> =
-    buehlmann_16c_mvalues_table(Some(DEFAULT_BUEHLMANN_HE_RATIO));
+    buehlmann_mvalues_table(&TISSUES, Some(DEFAULT_BUEHLMANN_HE_RATIO));
+
+/** Published Bühlmann coefficient sets, selectable at runtime. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuehlmannCoefficients {
+    /// ZH-L16A, the theoretical set derived from experiments.
+    ZhL16A,
+    /// ZH-L16B, reduced middle-compartment a values used for tables.
+    ZhL16B,
+    /// ZH-L16C, further reduced a values used in dive computers.
+    ZhL16C,
+    /// ZH-L12 (1983): 16 compartments sharing 12 a/b pairs.
+    ZhL12,
+}
+
+// ZH-L16 uses compartment 1b (5 min) in the first slot, as dive computers do.
+const ZHL16_N2_HALF_TIMES: [f32; NUM_TISSUES_BUEHLMANN] = [
+    5.0, 8.0, 12.5, 18.5, 27.0, 38.3, 54.3, 77.0, 109.0, 146.0, 187.0, 239.0, 305.0, 390.0, 498.0,
+    635.0,
+];
+const ZHL16_N2_B: [f32; NUM_TISSUES_BUEHLMANN] = [
+    0.5578, 0.6514, 0.7222, 0.7825, 0.8126, 0.8434, 0.8693, 0.8910, 0.9092, 0.9222, 0.9319, 0.9403,
+    0.9477, 0.9544, 0.9602, 0.9653,
+];
+const ZHL16A_N2_A: [f32; NUM_TISSUES_BUEHLMANN] = [
+    1.1696, 1.0000, 0.8618, 0.7562, 0.6667, 0.5933, 0.5282, 0.4701, 0.4187, 0.3798, 0.3497, 0.3223,
+    0.2971, 0.2737, 0.2523, 0.2327,
+];
+const ZHL16B_N2_A: [f32; NUM_TISSUES_BUEHLMANN] = [
+    1.1696, 1.0000, 0.8618, 0.7562, 0.6667, 0.5600, 0.4947, 0.4500, 0.4187, 0.3798, 0.3497, 0.3223,
+    0.2850, 0.2737, 0.2523, 0.2327,
+];
+const ZHL16C_N2_A: [f32; NUM_TISSUES_BUEHLMANN] = [
+    1.1696, 1.0000, 0.8618, 0.7562, 0.6200, 0.5043, 0.4410, 0.4000, 0.3750, 0.3500, 0.3295, 0.3065,
+    0.2835, 0.2610, 0.2480, 0.2327,
+];
+const ZHL16_HE_HALF_TIMES: [f32; NUM_TISSUES_BUEHLMANN] = [
+    1.88, 3.02, 4.72, 6.99, 10.21, 14.48, 20.53, 29.11, 41.20, 55.19, 70.69, 90.34, 115.29, 147.42,
+    188.24, 240.03,
+];
+const ZHL16_HE_A: [f32; NUM_TISSUES_BUEHLMANN] = [
+    1.6189, 1.3830, 1.1919, 1.0458, 0.9220, 0.8205, 0.7305, 0.6502, 0.5950, 0.5545, 0.5333, 0.5189,
+    0.5181, 0.5176, 0.5172, 0.5119,
+];
+const ZHL16_HE_B: [f32; NUM_TISSUES_BUEHLMANN] = [
+    0.4770, 0.5747, 0.6527, 0.7223, 0.7582, 0.7957, 0.8279, 0.8553, 0.8757, 0.8903, 0.8997, 0.9073,
+    0.9122, 0.9171, 0.9217, 0.9267,
+];
+
+const ZHL12_N2_HALF_TIMES: [f32; NUM_TISSUES_BUEHLMANN] = [
+    2.65, 7.94, 12.2, 18.5, 26.5, 37.0, 53.0, 79.0, 114.0, 146.0, 185.0, 238.0, 304.0, 397.0,
+    503.0, 635.0,
+];
+const ZHL12_N2_A: [f32; NUM_TISSUES_BUEHLMANN] = [
+    2.2, 1.5, 1.08, 0.9, 0.75, 0.58, 0.47, 0.455, 0.455, 0.455, 0.455, 0.38, 0.255, 0.255, 0.255,
+    0.255,
+];
+const ZHL12_N2_B: [f32; NUM_TISSUES_BUEHLMANN] = [
+    0.82, 0.82, 0.825, 0.835, 0.845, 0.86, 0.87, 0.89, 0.89, 0.934, 0.934, 0.944, 0.962, 0.962,
+    0.962, 0.962,
+];
+const ZHL12_HE_HALF_TIMES: [f32; NUM_TISSUES_BUEHLMANN] = [
+    1.0, 3.0, 4.6, 7.0, 10.0, 14.0, 20.0, 30.0, 43.0, 55.0, 70.0, 90.0, 115.0, 150.0, 190.0, 240.0,
+];
+const ZHL12_HE_A: [f32; NUM_TISSUES_BUEHLMANN] = [
+    2.2, 1.5, 1.08, 0.9, 0.75, 0.58, 0.47, 0.455, 0.455, 0.515, 0.515, 0.515, 0.515, 0.515, 0.515,
+    0.515,
+];
+const ZHL12_HE_B: [f32; NUM_TISSUES_BUEHLMANN] = [
+    0.82, 0.82, 0.825, 0.835, 0.845, 0.86, 0.87, 0.89, 0.89, 0.926, 0.926, 0.926, 0.926, 0.926,
+    0.926, 0.926,
+];
+
+#[allow(clippy::too_many_arguments)]
+const fn tissue_set(
+    n2_half_times: &[f32; NUM_TISSUES_BUEHLMANN],
+    n2_a: &[f32; NUM_TISSUES_BUEHLMANN],
+    n2_b: &[f32; NUM_TISSUES_BUEHLMANN],
+    he_half_times: &[f32; NUM_TISSUES_BUEHLMANN],
+    he_a: &[f32; NUM_TISSUES_BUEHLMANN],
+    he_b: &[f32; NUM_TISSUES_BUEHLMANN],
+) -> [BuehlmannTissue; NUM_TISSUES_BUEHLMANN] {
+    let unset = BuehlmannTissueGas {
+        half_time: 0.0,
+        a: Bar::new(0.0),
+        b: 0.0,
+    };
+    let mut tissues = [BuehlmannTissue {
+        n2: unset,
+        he: unset,
+    }; NUM_TISSUES_BUEHLMANN];
+    let mut i = 0;
+    while i < NUM_TISSUES_BUEHLMANN {
+        tissues[i] = BuehlmannTissue {
+            n2: BuehlmannTissueGas {
+                half_time: n2_half_times[i],
+                a: Bar::new(n2_a[i]),
+                b: n2_b[i],
+            },
+            he: BuehlmannTissueGas {
+                half_time: he_half_times[i],
+                a: Bar::new(he_a[i]),
+                b: he_b[i],
+            },
+        };
+        i += 1;
+    }
+    tissues
+}
+
+impl BuehlmannCoefficients {
+    pub const fn tissues(self) -> [BuehlmannTissue; NUM_TISSUES_BUEHLMANN] {
+        let n2_a = match self {
+            BuehlmannCoefficients::ZhL16A => &ZHL16A_N2_A,
+            BuehlmannCoefficients::ZhL16B => &ZHL16B_N2_A,
+            BuehlmannCoefficients::ZhL16C => &ZHL16C_N2_A,
+            BuehlmannCoefficients::ZhL12 => {
+                return tissue_set(
+                    &ZHL12_N2_HALF_TIMES,
+                    &ZHL12_N2_A,
+                    &ZHL12_N2_B,
+                    &ZHL12_HE_HALF_TIMES,
+                    &ZHL12_HE_A,
+                    &ZHL12_HE_B,
+                );
+            }
+        };
+        tissue_set(
+            &ZHL16_N2_HALF_TIMES,
+            n2_a,
+            &ZHL16_N2_B,
+            &ZHL16_HE_HALF_TIMES,
+            &ZHL16_HE_A,
+            &ZHL16_HE_B,
+        )
+    }
+
+    /// Pure-N2 M-value table of the set on the 3 msw stop grid.
+    pub const fn m_values(
+        self,
+    ) -> MValues<Pa, { NUM_TISSUES_BUEHLMANN }, { NUM_STOP_DEPTHS_BUEHLMANN }> {
+        buehlmann_mvalues_table(&self.tissues(), Some(DEFAULT_BUEHLMANN_HE_RATIO))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zhl16_sets_differ_only_in_n2_a() {
+        let a = BuehlmannCoefficients::ZhL16A.tissues();
+        let b = BuehlmannCoefficients::ZhL16B.tissues();
+        let c = BuehlmannCoefficients::ZhL16C.tissues();
+        for i in 0..NUM_TISSUES_BUEHLMANN {
+            assert!(a[i].n2.a.to_f32() >= b[i].n2.a.to_f32());
+            assert!(b[i].n2.a.to_f32() >= c[i].n2.a.to_f32());
+            assert_eq!(a[i].n2.b, c[i].n2.b);
+            assert_eq!(a[i].he.half_time, c[i].he.half_time);
+        }
+        assert_eq!(c[4].n2.a.to_f32(), 0.6200);
+        assert_eq!(c[4].n2.a.to_pa().to_f32(), 62_000.0);
+        assert_eq!(b[5].n2.a.to_f32(), 0.5600);
+        assert_eq!(c[0].he.half_time, 1.88);
+    }
+
+    #[test]
+    fn default_tissues_are_zhl16c() {
+        let c = BuehlmannCoefficients::ZhL16C.tissues();
+        for i in 0..NUM_TISSUES_BUEHLMANN {
+            assert_eq!(TISSUES[i].n2.half_time, c[i].n2.half_time);
+            assert_eq!(TISSUES[i].n2.a.to_f32(), c[i].n2.a.to_f32());
+            assert_eq!(TISSUES[i].he.half_time, c[i].he.half_time);
+        }
+        assert_eq!(TISSUES[0].n2.half_time, 5.0);
+        assert_eq!(TISSUES[5].n2.a.to_f32(), 0.5043);
+        assert_ne!(TISSUES[3].he.half_time, TISSUES[3].n2.half_time);
+    }
+
+    #[test]
+    fn mvalue_is_a_plus_ambient_over_b() {
+        // ZH-L16C compartment 5: a = 0.6200 bar, b = 0.8126
+        let m_values = BuehlmannCoefficients::ZhL16C.m_values();
+        let surface = m_values[0].max_saturation[4].to_f32();
+        let thirty_m = m_values[10].max_saturation[4].to_f32();
+        assert_eq!(m_values[10].depth, msw::new(30.0));
+        // 0.62 bar + 1.0 bar / 0.8126; a + P * b with a in msw gave 1.875 bar
+        assert!((surface - 185_061.8).abs() < 1.0, "{surface}");
+        // 0.62 bar + 4.039 bar / 0.8126; a + P * b with a in msw gave 4.345 bar
+        assert!((thirty_m - 559_046.5).abs() < 1.0, "{thirty_m}");
+    }
+}
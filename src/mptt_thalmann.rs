@@ -1,8 +1,48 @@
 use crate::{
     mptt::{MValues, Tissue, TissueRow},
-    pressure_unit::{Pa, Pressure, fsw, msw},
+    pressure_unit::{Pa, msw},
 };
 
+#[allow(clippy::excessive_precision)]
+pub(crate) const THALMANN_FSW_TO_PA: f32 = 3_064.305_931_38;
+
+/**
+* Venous and gas-phase terms of the linear-exponential crossover:
+* PVSAT + PBOVP = PAMB - (PVO2 + PVCO2 + PH2O) + PBOVP.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThalmannSettings {
+    /// Venous O2 tension
+    pub pvo2: Pa,
+    /// Venous CO2 tension
+    pub pvco2: Pa,
+    /// Water vapour pressure
+    pub ph2o: Pa,
+    /// Gas-phase overpressure at which washout turns exponential
+    pub pbovp: Pa,
+}
+
+/// Constants of the XVal-He-9_040 and VVal-18 sets (NEDU TR 18-05).
+pub const THALMANN_DEFAULT_SETTINGS: ThalmannSettings =
+    ThalmannSettings::from_fsw(2.0, 2.3, 0.0, 0.0);
+
+impl ThalmannSettings {
+    /// Settings from values in fsw, as published.
+    pub const fn from_fsw(pvo2: f32, pvco2: f32, ph2o: f32, pbovp: f32) -> Self {
+        ThalmannSettings {
+            pvo2: Pa::new(pvo2 * THALMANN_FSW_TO_PA),
+            pvco2: Pa::new(pvco2 * THALMANN_FSW_TO_PA),
+            ph2o: Pa::new(ph2o * THALMANN_FSW_TO_PA),
+            pbovp: Pa::new(pbovp * THALMANN_FSW_TO_PA),
+        }
+    }
+}
+
+/// MPTT in Pa from its published value, an absolute inert gas tension in fsw.
+pub const fn mptt_fsw(tension: f32) -> Pa {
+    Pa::new(tension * THALMANN_FSW_TO_PA)
+}
+
 /// Generates a linear MPTT table with `N` stop depths at `depth_step_msw` intervals.
 ///
 /// For compartment `i`, the max saturation at stop index `k` (1-indexed) is:
@@ -23,7 +63,7 @@ pub const fn generate_linear_mptt<const N_TISSUES: usize, const N: usize>(
         let mut sat = [Pa::new(0.0); N_TISSUES];
         let mut t = 0usize;
         while t < N_TISSUES {
-            sat[t] = fsw(m0_fsw[t] + stop * increment_fsw[t]).to_pa();
+            sat[t] = mptt_fsw(m0_fsw[t] + stop * increment_fsw[t]);
             t += 1;
         }
         table[k] = TissueRow { depth, max_saturation: sat };
@@ -47,6 +87,56 @@ pub const fn xval_he9_040<const N: usize>() -> MValues<Pa, NUM_TISSUES_THALMANN,
     generate_linear_mptt(XVAL_HE9_040_M0_FSW, XVAL_HE9_040_INCREMENT_FSW, 3.0)
 }
 
+// VVal-18 air parameters (9 compartments, SDR 1, β1 = 1 in all compartments).
+// SURFACING: MPTT evaluated for the ascent from the first (3-msw) stop to the
+// surface, in fsw. Deeper rows add 9.843 fsw per 3-msw step.
+pub const NUM_TISSUES_VVAL18: usize = 9;
+const VVAL18_SURFACING_MPTT_FSW: [f32; NUM_TISSUES_VVAL18] =
+    [120.0, 93.0, 76.0, 58.0, 55.0, 51.2, 50.0, 50.0, 50.0];
+const VVAL_INCREMENT_FSW: [f32; NUM_TISSUES_VVAL18] = [9.843; NUM_TISSUES_VVAL18];
+
+/// Generates a VVal-18 MPTT table with `N` stop depths at 3 msw intervals.
+pub const fn vval18<const N: usize>() -> MValues<Pa, NUM_TISSUES_VVAL18, N> {
+    vval_mptt(VVAL18_SURFACING_MPTT_FSW)
+}
+
+/**
+* Generates a VVal-family MPTT table with `N` stop depths at 3 msw intervals from the
+* surfacing MPTTs of the nine `VVAL18_TISSUES` compartments, in fsw.
+* Deeper rows follow the VVal-18 slope of one fsw per fsw. VVal-79 (NEDU TR 12-01), used for
+* the Rev 7 air tables, is not shipped; its MPTTs are tabulated per stop and have to be passed
+* to `ThalmannModel::new` as a whole table.
+*/
+pub const fn vval_mptt<const N: usize>(
+    surfacing_mptt_fsw: [f32; NUM_TISSUES_VVAL18],
+) -> MValues<Pa, NUM_TISSUES_VVAL18, N> {
+    let mut m0_fsw = surfacing_mptt_fsw;
+    let mut t = 0usize;
+    while t < NUM_TISSUES_VVAL18 {
+        m0_fsw[t] -= VVAL_INCREMENT_FSW[t];
+        t += 1;
+    }
+    generate_linear_mptt(m0_fsw, VVAL_INCREMENT_FSW, 3.0)
+}
+
+pub const VVAL18_TISSUES: [Tissue; NUM_TISSUES_VVAL18] = {
+    const HALF_TIMES: [f32; NUM_TISSUES_VVAL18] =
+        [5.0, 10.0, 20.0, 40.0, 80.0, 120.0, 160.0, 200.0, 240.0];
+    let mut tissues = [Tissue {
+        half_time: 0.0,
+        sdr: 1.0,
+    }; NUM_TISSUES_VVAL18];
+    let mut t = 0usize;
+    while t < NUM_TISSUES_VVAL18 {
+        tissues[t].half_time = HALF_TIMES[t];
+        t += 1;
+    }
+    tissues
+};
+
+pub const VVAL18_F32_VARIABLE: MValues<Pa, NUM_TISSUES_VVAL18, NUM_STOP_DEPTHS_THALMANN> =
+    vval18();
+
 pub const NUM_TISSUES_THALMANN: usize = 5;
 pub const NUM_STOP_DEPTHS_THALMANN: usize = 64;
 pub const NUM_STOP_DEPTHS_THALMANN_FIXED: usize = 32;
@@ -86,321 +176,321 @@ pub const XVAL_HE9_040_F32: MValues<Pa, { NUM_TISSUES_THALMANN }, { NUM_STOP_DEP
     TissueRow {
         depth: msw(3.0),
         max_saturation: [
-            fsw(85.000).to_pa(),
-            fsw(64.000).to_pa(),
-            fsw(83.000).to_pa(),
-            fsw(41.731).to_pa(),
-            fsw(38.274).to_pa(),
+            mptt_fsw(85.000),
+            mptt_fsw(64.000),
+            mptt_fsw(83.000),
+            mptt_fsw(41.731),
+            mptt_fsw(38.274),
         ],
     },
     TissueRow {
         depth: msw(6.0),
         max_saturation: [
-            fsw(94.843).to_pa(),
-            fsw(73.843).to_pa(),
-            fsw(92.843).to_pa(),
-            fsw(61.416).to_pa(),
-            fsw(49.969).to_pa(),
+            mptt_fsw(94.843),
+            mptt_fsw(73.843),
+            mptt_fsw(92.843),
+            mptt_fsw(61.416),
+            mptt_fsw(49.969),
         ],
     },
     TissueRow {
         depth: msw(9.0),
         max_saturation: [
-            fsw(104.685).to_pa(),
-            fsw(83.685).to_pa(),
-            fsw(102.685).to_pa(),
-            fsw(81.101).to_pa(),
-            fsw(61.664).to_pa(),
+            mptt_fsw(104.685),
+            mptt_fsw(83.685),
+            mptt_fsw(102.685),
+            mptt_fsw(81.101),
+            mptt_fsw(61.664),
         ],
     },
     TissueRow {
         depth: msw(12.0),
         max_saturation: [
-            fsw(114.528).to_pa(),
-            fsw(93.528).to_pa(),
-            fsw(112.528).to_pa(),
-            fsw(100.786).to_pa(),
-            fsw(73.359).to_pa(),
+            mptt_fsw(114.528),
+            mptt_fsw(93.528),
+            mptt_fsw(112.528),
+            mptt_fsw(100.786),
+            mptt_fsw(73.359),
         ],
     },
     TissueRow {
         depth: msw(15.0),
         max_saturation: [
-            fsw(124.370).to_pa(),
-            fsw(103.370).to_pa(),
-            fsw(122.370).to_pa(),
-            fsw(120.471).to_pa(),
-            fsw(85.054).to_pa(),
+            mptt_fsw(124.370),
+            mptt_fsw(103.370),
+            mptt_fsw(122.370),
+            mptt_fsw(120.471),
+            mptt_fsw(85.054),
         ],
     },
     TissueRow {
         depth: msw(18.0),
         max_saturation: [
-            fsw(134.213).to_pa(),
-            fsw(113.213).to_pa(),
-            fsw(132.213).to_pa(),
-            fsw(140.156).to_pa(),
-            fsw(96.749).to_pa(),
+            mptt_fsw(134.213),
+            mptt_fsw(113.213),
+            mptt_fsw(132.213),
+            mptt_fsw(140.156),
+            mptt_fsw(96.749),
         ],
     },
     TissueRow {
         depth: msw(21.0),
         max_saturation: [
-            fsw(144.055).to_pa(),
-            fsw(123.055).to_pa(),
-            fsw(142.055).to_pa(),
-            fsw(159.841).to_pa(),
-            fsw(108.444).to_pa(),
+            mptt_fsw(144.055),
+            mptt_fsw(123.055),
+            mptt_fsw(142.055),
+            mptt_fsw(159.841),
+            mptt_fsw(108.444),
         ],
     },
     TissueRow {
         depth: msw(24.0),
         max_saturation: [
-            fsw(153.898).to_pa(),
-            fsw(132.898).to_pa(),
-            fsw(151.898).to_pa(),
-            fsw(179.526).to_pa(),
-            fsw(120.139).to_pa(),
+            mptt_fsw(153.898),
+            mptt_fsw(132.898),
+            mptt_fsw(151.898),
+            mptt_fsw(179.526),
+            mptt_fsw(120.139),
         ],
     },
     TissueRow {
         depth: msw(27.0),
         max_saturation: [
-            fsw(163.740).to_pa(),
-            fsw(142.740).to_pa(),
-            fsw(161.740).to_pa(),
-            fsw(199.211).to_pa(),
-            fsw(131.834).to_pa(),
+            mptt_fsw(163.740),
+            mptt_fsw(142.740),
+            mptt_fsw(161.740),
+            mptt_fsw(199.211),
+            mptt_fsw(131.834),
         ],
     },
     TissueRow {
         depth: msw(30.0),
         max_saturation: [
-            fsw(173.583).to_pa(),
-            fsw(152.583).to_pa(),
-            fsw(171.583).to_pa(),
-            fsw(218.896).to_pa(),
-            fsw(143.529).to_pa(),
+            mptt_fsw(173.583),
+            mptt_fsw(152.583),
+            mptt_fsw(171.583),
+            mptt_fsw(218.896),
+            mptt_fsw(143.529),
         ],
     },
     TissueRow {
         depth: msw(33.0),
         max_saturation: [
-            fsw(183.425).to_pa(),
-            fsw(162.425).to_pa(),
-            fsw(181.425).to_pa(),
-            fsw(238.581).to_pa(),
-            fsw(155.224).to_pa(),
+            mptt_fsw(183.425),
+            mptt_fsw(162.425),
+            mptt_fsw(181.425),
+            mptt_fsw(238.581),
+            mptt_fsw(155.224),
         ],
     },
     TissueRow {
         depth: msw(36.0),
         max_saturation: [
-            fsw(193.268).to_pa(),
-            fsw(172.268).to_pa(),
-            fsw(191.268).to_pa(),
-            fsw(258.266).to_pa(),
-            fsw(166.919).to_pa(),
+            mptt_fsw(193.268),
+            mptt_fsw(172.268),
+            mptt_fsw(191.268),
+            mptt_fsw(258.266),
+            mptt_fsw(166.919),
         ],
     },
     TissueRow {
         depth: msw(39.0),
         max_saturation: [
-            fsw(203.110).to_pa(),
-            fsw(182.110).to_pa(),
-            fsw(201.110).to_pa(),
-            fsw(277.951).to_pa(),
-            fsw(178.614).to_pa(),
+            mptt_fsw(203.110),
+            mptt_fsw(182.110),
+            mptt_fsw(201.110),
+            mptt_fsw(277.951),
+            mptt_fsw(178.614),
         ],
     },
     TissueRow {
         depth: msw(42.0),
         max_saturation: [
-            fsw(212.953).to_pa(),
-            fsw(191.953).to_pa(),
-            fsw(210.953).to_pa(),
-            fsw(297.637).to_pa(),
-            fsw(190.309).to_pa(),
+            mptt_fsw(212.953),
+            mptt_fsw(191.953),
+            mptt_fsw(210.953),
+            mptt_fsw(297.637),
+            mptt_fsw(190.309),
         ],
     },
     TissueRow {
         depth: msw(45.0),
         max_saturation: [
-            fsw(222.795).to_pa(),
-            fsw(201.795).to_pa(),
-            fsw(220.795).to_pa(),
-            fsw(317.322).to_pa(),
-            fsw(202.004).to_pa(),
+            mptt_fsw(222.795),
+            mptt_fsw(201.795),
+            mptt_fsw(220.795),
+            mptt_fsw(317.322),
+            mptt_fsw(202.004),
         ],
     },
     TissueRow {
         depth: msw(48.0),
         max_saturation: [
-            fsw(232.638).to_pa(),
-            fsw(211.638).to_pa(),
-            fsw(230.638).to_pa(),
-            fsw(337.007).to_pa(),
-            fsw(213.699).to_pa(),
+            mptt_fsw(232.638),
+            mptt_fsw(211.638),
+            mptt_fsw(230.638),
+            mptt_fsw(337.007),
+            mptt_fsw(213.699),
         ],
     },
     TissueRow {
         depth: msw(51.0),
         max_saturation: [
-            fsw(242.480).to_pa(),
-            fsw(221.480).to_pa(),
-            fsw(240.480).to_pa(),
-            fsw(356.692).to_pa(),
-            fsw(225.394).to_pa(),
+            mptt_fsw(242.480),
+            mptt_fsw(221.480),
+            mptt_fsw(240.480),
+            mptt_fsw(356.692),
+            mptt_fsw(225.394),
         ],
     },
     TissueRow {
         depth: msw(54.0),
         max_saturation: [
-            fsw(252.323).to_pa(),
-            fsw(231.323).to_pa(),
-            fsw(250.323).to_pa(),
-            fsw(376.377).to_pa(),
-            fsw(237.089).to_pa(),
+            mptt_fsw(252.323),
+            mptt_fsw(231.323),
+            mptt_fsw(250.323),
+            mptt_fsw(376.377),
+            mptt_fsw(237.089),
         ],
     },
     TissueRow {
         depth: msw(57.0),
         max_saturation: [
-            fsw(262.165).to_pa(),
-            fsw(241.165).to_pa(),
-            fsw(260.165).to_pa(),
-            fsw(396.062).to_pa(),
-            fsw(248.784).to_pa(),
+            mptt_fsw(262.165),
+            mptt_fsw(241.165),
+            mptt_fsw(260.165),
+            mptt_fsw(396.062),
+            mptt_fsw(248.784),
         ],
     },
     TissueRow {
         depth: msw(60.0),
         max_saturation: [
-            fsw(272.008).to_pa(),
-            fsw(251.008).to_pa(),
-            fsw(270.008).to_pa(),
-            fsw(415.747).to_pa(),
-            fsw(260.479).to_pa(),
+            mptt_fsw(272.008),
+            mptt_fsw(251.008),
+            mptt_fsw(270.008),
+            mptt_fsw(415.747),
+            mptt_fsw(260.479),
         ],
     },
     TissueRow {
         depth: msw(63.0),
         max_saturation: [
-            fsw(281.850).to_pa(),
-            fsw(260.850).to_pa(),
-            fsw(279.850).to_pa(),
-            fsw(435.432).to_pa(),
-            fsw(272.173).to_pa(),
+            mptt_fsw(281.850),
+            mptt_fsw(260.850),
+            mptt_fsw(279.850),
+            mptt_fsw(435.432),
+            mptt_fsw(272.173),
         ],
     },
     TissueRow {
         depth: msw(66.0),
         max_saturation: [
-            fsw(291.693).to_pa(),
-            fsw(270.693).to_pa(),
-            fsw(289.693).to_pa(),
-            fsw(455.117).to_pa(),
-            fsw(283.868).to_pa(),
+            mptt_fsw(291.693),
+            mptt_fsw(270.693),
+            mptt_fsw(289.693),
+            mptt_fsw(455.117),
+            mptt_fsw(283.868),
         ],
     },
     TissueRow {
         depth: msw(69.0),
         max_saturation: [
-            fsw(301.535).to_pa(),
-            fsw(280.535).to_pa(),
-            fsw(299.535).to_pa(),
-            fsw(474.802).to_pa(),
-            fsw(295.563).to_pa(),
+            mptt_fsw(301.535),
+            mptt_fsw(280.535),
+            mptt_fsw(299.535),
+            mptt_fsw(474.802),
+            mptt_fsw(295.563),
         ],
     },
     TissueRow {
         depth: msw(72.0),
         max_saturation: [
-            fsw(311.378).to_pa(),
-            fsw(290.378).to_pa(),
-            fsw(309.378).to_pa(),
-            fsw(494.487).to_pa(),
-            fsw(307.258).to_pa(),
+            mptt_fsw(311.378),
+            mptt_fsw(290.378),
+            mptt_fsw(309.378),
+            mptt_fsw(494.487),
+            mptt_fsw(307.258),
         ],
     },
     TissueRow {
         depth: msw(75.0),
         max_saturation: [
-            fsw(321.220).to_pa(),
-            fsw(300.220).to_pa(),
-            fsw(319.220).to_pa(),
-            fsw(514.172).to_pa(),
-            fsw(318.953).to_pa(),
+            mptt_fsw(321.220),
+            mptt_fsw(300.220),
+            mptt_fsw(319.220),
+            mptt_fsw(514.172),
+            mptt_fsw(318.953),
         ],
     },
     TissueRow {
         depth: msw(78.0),
         max_saturation: [
-            fsw(331.063).to_pa(),
-            fsw(310.063).to_pa(),
-            fsw(329.063).to_pa(),
-            fsw(533.857).to_pa(),
-            fsw(330.648).to_pa(),
+            mptt_fsw(331.063),
+            mptt_fsw(310.063),
+            mptt_fsw(329.063),
+            mptt_fsw(533.857),
+            mptt_fsw(330.648),
         ],
     },
     TissueRow {
         depth: msw(81.0),
         max_saturation: [
-            fsw(340.906).to_pa(),
-            fsw(319.906).to_pa(),
-            fsw(338.906).to_pa(),
-            fsw(553.542).to_pa(),
-            fsw(342.343).to_pa(),
+            mptt_fsw(340.906),
+            mptt_fsw(319.906),
+            mptt_fsw(338.906),
+            mptt_fsw(553.542),
+            mptt_fsw(342.343),
         ],
     },
     TissueRow {
         depth: msw(84.0),
         max_saturation: [
-            fsw(350.748).to_pa(),
-            fsw(329.748).to_pa(),
-            fsw(348.748).to_pa(),
-            fsw(573.227).to_pa(),
-            fsw(354.038).to_pa(),
+            mptt_fsw(350.748),
+            mptt_fsw(329.748),
+            mptt_fsw(348.748),
+            mptt_fsw(573.227),
+            mptt_fsw(354.038),
         ],
     },
     TissueRow {
         depth: msw(87.0),
         max_saturation: [
-            fsw(360.591).to_pa(),
-            fsw(339.591).to_pa(),
-            fsw(358.591).to_pa(),
-            fsw(592.912).to_pa(),
-            fsw(365.733).to_pa(),
+            mptt_fsw(360.591),
+            mptt_fsw(339.591),
+            mptt_fsw(358.591),
+            mptt_fsw(592.912),
+            mptt_fsw(365.733),
         ],
     },
     TissueRow {
         depth: msw(90.0),
         max_saturation: [
-            fsw(370.433).to_pa(),
-            fsw(349.433).to_pa(),
-            fsw(368.433).to_pa(),
-            fsw(612.597).to_pa(),
-            fsw(377.428).to_pa(),
+            mptt_fsw(370.433),
+            mptt_fsw(349.433),
+            mptt_fsw(368.433),
+            mptt_fsw(612.597),
+            mptt_fsw(377.428),
         ],
     },
     TissueRow {
         depth: msw(93.0),
         max_saturation: [
-            fsw(380.276).to_pa(),
-            fsw(359.276).to_pa(),
-            fsw(378.276).to_pa(),
-            fsw(632.282).to_pa(),
-            fsw(389.123).to_pa(),
+            mptt_fsw(380.276),
+            mptt_fsw(359.276),
+            mptt_fsw(378.276),
+            mptt_fsw(632.282),
+            mptt_fsw(389.123),
         ],
     },
     TissueRow {
         depth: msw(96.0),
         max_saturation: [
-            fsw(390.118).to_pa(),
-            fsw(369.118).to_pa(),
-            fsw(388.118).to_pa(),
-            fsw(651.967).to_pa(),
-            fsw(400.818).to_pa(),
+            mptt_fsw(390.118),
+            mptt_fsw(369.118),
+            mptt_fsw(388.118),
+            mptt_fsw(651.967),
+            mptt_fsw(400.818),
         ],
     },
 ];
@@ -408,6 +498,7 @@ pub const XVAL_HE9_040_F32: MValues<Pa, { NUM_TISSUES_THALMANN }, { NUM_STOP_DEP
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::pressure_unit::Pressure;
     extern crate std;
 
     /// Beyond the 32-row reference table (> 96 msw), verifies:
@@ -420,7 +511,7 @@ mod tests {
 
         let extended = xval_he9_040::<N_EXTENDED>();
 
-        let pa_per_fsw = fsw::new(1.0).to_pa().to_f32() - fsw::new(0.0).to_pa().to_f32();
+        let pa_per_fsw = mptt_fsw(1.0).to_f32();
         let increment_tolerance_fsw = 0.001_f32;
 
         let mut t = 0usize;
@@ -453,11 +544,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn vval18_first_row_is_surfacing_mptt() {
+        let table = vval18::<2>();
+        let mut t = 0usize;
+        while t < NUM_TISSUES_VVAL18 {
+            let expected = mptt_fsw(VVAL18_SURFACING_MPTT_FSW[t]).to_f32();
+            let actual = table[0].max_saturation[t].to_f32();
+            assert!(f32::abs(actual - expected) < 1.0, "Tissue {t}");
+            assert_eq!(table[0].depth, msw::new(3.0));
+            t += 1;
+        }
+    }
+
     #[test]
     fn xval_he9_040_generation_matches_table() {
         let generated = xval_he9_040::<NUM_STOP_DEPTHS_THALMANN_FIXED>();
-        // 1 fsw as a Pa magnitude (fsw(1).to_pa() - fsw(0).to_pa(), no atmospheric offset)
-        let pa_per_fsw = fsw::new(1.0).to_pa().to_f32() - fsw::new(0.0).to_pa().to_f32();
+        let pa_per_fsw = mptt_fsw(1.0).to_f32();
         // Max expected deviation from parameter rounding is ~0.015 fsw; allow 0.05 fsw.
         let threshold_fsw = 0.02_f32;
 
@@ -0,0 +1,262 @@
+#[allow(unused)]
+use num::Float;
+
+use core::time::Duration;
+
+use crate::pressure_unit::{Bar, Pa, Pressure};
+
+pub const NUM_STOP_DEPTHS_VPM: usize = 32;
+
+/// Multipliers applied to the critical radii for conservatism levels +0 to +4.
+pub const VPM_B_CONSERVATISM: [f32; 5] = [1.0, 1.05, 1.12, 1.22, 1.35];
+
+const MICRONS_TO_M: f32 = 1E-6;
+const BOYLE_MAX_ITER: usize = 100;
+const BOYLE_TOLERANCE_M: f32 = 1E-12;
+
+/** Varying Permeability Model bubble parameters (Yount / Baker VPM-B). */
+#[derive(Debug, Clone, Copy)]
+pub struct VpmSettings {
+    pub critical_radius_n2_um: f32,
+    pub critical_radius_he_um: f32,
+    /// Surface tension gamma in N/m
+    pub surface_tension_gamma: f32,
+    /// Skin compression gamma_c in N/m
+    pub skin_compression_gamma_c: f32,
+    /// Crushing gradient above which nuclei become impermeable to gas.
+    pub gradient_onset_of_impermeability: Pa,
+    /// Constant venous O2 + CO2 + H2O tension added to the inert tension.
+    pub pressure_other_gases: Pa,
+    /// Relax the gradients with the critical volume algorithm after the first ascent.
+    pub critical_volume_algorithm: bool,
+    /// Critical volume parameter lambda in Pa min.
+    pub critical_volume_lambda: f32,
+    /// Time constant of the nuclei regenerating to their initial radii.
+    pub regeneration_time_constant: Duration,
+}
+
+pub const VPM_B_DEFAULT: VpmSettings = VpmSettings {
+    critical_radius_n2_um: 0.55,
+    critical_radius_he_um: 0.45,
+    surface_tension_gamma: 0.0179,
+    skin_compression_gamma_c: 0.257,
+    gradient_onset_of_impermeability: Bar::new(8.2).to_pa(),
+    pressure_other_gases: Pa::new(13_598.8),
+    critical_volume_algorithm: true,
+    // 7500 fsw min
+    critical_volume_lambda: 7500.0 / 33.0 * 101_325.0,
+    regeneration_time_constant: Duration::from_secs(20_160 * 60),
+};
+
+impl VpmSettings {
+    /// Scales both critical radii by the VPM-B conservatism factor of `level` (clamped to +4).
+    pub const fn with_conservatism(self, level: usize) -> Self {
+        let idx = if level < VPM_B_CONSERVATISM.len() {
+            level
+        } else {
+            VPM_B_CONSERVATISM.len() - 1
+        };
+        let factor = VPM_B_CONSERVATISM[idx];
+        VpmSettings {
+            critical_radius_n2_um: self.critical_radius_n2_um * factor,
+            critical_radius_he_um: self.critical_radius_he_um * factor,
+            ..self
+        }
+    }
+
+    const fn surface_tension_term(&self) -> f32 {
+        2.0 * self.surface_tension_gamma
+            * (self.skin_compression_gamma_c - self.surface_tension_gamma)
+            / self.skin_compression_gamma_c
+    }
+}
+
+/**
+* Nucleus radius after being squeezed by `crushing_pressure` (Pa):
+* 1 / r = crushing / (2 (gamma_c - gamma)) + 1 / r_0
+*/
+pub const fn adjusted_critical_radius(
+    initial_radius_um: f32,
+    crushing_pressure: Pa,
+    settings: &VpmSettings,
+) -> f32 {
+    let initial_radius = initial_radius_um * MICRONS_TO_M;
+    let crushing = crushing_pressure.to_f32();
+    1.0 / (crushing / (2.0 * (settings.skin_compression_gamma_c - settings.surface_tension_gamma))
+        + 1.0 / initial_radius)
+}
+
+/**
+* Supersaturation gradient a nucleus of radius `radius_m` tolerates:
+* G = 2 gamma (gamma_c - gamma) / (r gamma_c)
+*/
+pub const fn allowable_gradient(radius_m: f32, settings: &VpmSettings) -> Pa {
+    Pa::new(settings.surface_tension_term() / radius_m)
+}
+
+/**
+* Crushing pressure left after the nuclei regenerate for `regeneration_time`:
+*     r_regen = r_0 + (r_crushed - r_0) e^{-t / tau}
+* expressed as the crushing pressure that squeezes r_0 to r_regen.
+*/
+pub fn regenerated_crushing_pressure(
+    initial_radius_um: f32,
+    crushing_pressure: Pa,
+    regeneration_time: Duration,
+    settings: &VpmSettings,
+) -> Pa {
+    if crushing_pressure.to_f32() <= 0.0 {
+        return crushing_pressure;
+    }
+    let initial_radius = initial_radius_um * MICRONS_TO_M;
+    let crushed_radius = adjusted_critical_radius(initial_radius_um, crushing_pressure, settings);
+    let decay = (-regeneration_time.as_secs_f32()
+        / settings.regeneration_time_constant.as_secs_f32())
+    .exp();
+    let regenerated_radius = initial_radius + (crushed_radius - initial_radius) * decay;
+    let ratio = (crushed_radius * (initial_radius - regenerated_radius))
+        / (regenerated_radius * (initial_radius - crushed_radius));
+    crushing_pressure * ratio
+}
+
+/**
+* Critical volume algorithm: gradient that keeps the bubble volume released
+* during `phase_volume_time` below the critical volume,
+*     G = (B + sqrt(B^2 - 4 C)) / 2,  B = G_0 + lambda gamma / (gamma_c t),
+*     C = gamma^2 lambda P_crush / (gamma_c^2 t)
+*/
+pub fn critical_volume_gradient(
+    initial_gradient: Pa,
+    crushing_pressure: Pa,
+    phase_volume_time: Duration,
+    settings: &VpmSettings,
+) -> Pa {
+    let minutes = phase_volume_time.as_secs_f32() / 60.0;
+    if minutes <= 0.0 {
+        return initial_gradient;
+    }
+    let gamma = settings.surface_tension_gamma;
+    let gamma_c = settings.skin_compression_gamma_c;
+    let lambda = settings.critical_volume_lambda;
+    let b = initial_gradient.to_f32() + lambda * gamma / (gamma_c * minutes);
+    let c = gamma * gamma * lambda * crushing_pressure.to_f32() / (gamma_c * gamma_c * minutes);
+    Pa::new(0.5 * (b + (b * b - 4.0 * c).max(0.0).sqrt()))
+}
+
+/**
+* VPM-B Boyle's law compensation: the bubble sized by `gradient_first_stop` at
+* `ambient_first_stop` expands on the way to `ambient_next`, so the gradient
+* allowed there is reduced accordingly. Solves
+*     P_next r^3 + 2 gamma r^2 = (P_first + 2 gamma / r_first) r_first^3
+* for r by bisection.
+*/
+pub fn boyle_compensated_gradient(
+    gradient_first_stop: Pa,
+    ambient_first_stop: Pa,
+    ambient_next: Pa,
+    settings: &VpmSettings,
+) -> Pa {
+    if ambient_next >= ambient_first_stop || gradient_first_stop.to_f32() <= 0.0 {
+        return gradient_first_stop;
+    }
+    let two_gamma = 2.0 * settings.surface_tension_gamma;
+    let p_first = ambient_first_stop.to_f32();
+    let p_next = ambient_next.to_f32();
+    let radius_first = settings.surface_tension_term() / gradient_first_stop.to_f32();
+    let c = (p_first + two_gamma / radius_first) * radius_first * radius_first * radius_first;
+    let f = |r: f32| p_next * r * r * r + two_gamma * r * r - c;
+
+    let mut low = radius_first;
+    let mut high = radius_first * (p_first / p_next).cbrt();
+    for _ in 0..BOYLE_MAX_ITER {
+        let mid = 0.5 * (low + high);
+        if f(mid) < 0.0 {
+            low = mid;
+        } else {
+            high = mid;
+        }
+        if high - low < BOYLE_TOLERANCE_M {
+            break;
+        }
+    }
+    allowable_gradient(0.5 * (low + high), settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pressure_unit::msw;
+
+    #[test]
+    fn allowable_gradient_of_default_n2_radius() {
+        let radius = adjusted_critical_radius(
+            VPM_B_DEFAULT.critical_radius_n2_um,
+            Pa::new(0.0),
+            &VPM_B_DEFAULT,
+        );
+        let gradient = allowable_gradient(radius, &VPM_B_DEFAULT).to_bar().to_f32();
+        // 2 * 0.0179 * 0.2391 / (0.55e-6 * 0.257) Pa
+        assert!((gradient - 0.6055).abs() < 0.001, "{gradient}");
+    }
+
+    #[test]
+    fn crushing_shrinks_radius_and_raises_gradient() {
+        let uncrushed = adjusted_critical_radius(0.55, Pa::new(0.0), &VPM_B_DEFAULT);
+        let crushed = adjusted_critical_radius(0.55, Bar::new(5.0).to_pa(), &VPM_B_DEFAULT);
+        assert!(crushed < uncrushed);
+        assert!(
+            allowable_gradient(crushed, &VPM_B_DEFAULT)
+                > allowable_gradient(uncrushed, &VPM_B_DEFAULT)
+        );
+    }
+
+    #[test]
+    fn conservatism_enlarges_radii() {
+        let plus2 = VPM_B_DEFAULT.with_conservatism(2);
+        assert!((plus2.critical_radius_n2_um - 0.55 * 1.12).abs() < 1e-6);
+        assert!((plus2.critical_radius_he_um - 0.45 * 1.12).abs() < 1e-6);
+        let clamped = VPM_B_DEFAULT.with_conservatism(10);
+        assert!((clamped.critical_radius_n2_um - 0.55 * 1.35).abs() < 1e-6);
+    }
+
+    #[test]
+    fn regeneration_relaxes_crushing_pressure() {
+        let crushing = Bar::new(5.0).to_pa();
+        let regenerated = |t: Duration| {
+            regenerated_crushing_pressure(0.55, crushing, t, &VPM_B_DEFAULT).to_f32()
+        };
+        assert!((regenerated(Duration::ZERO) - crushing.to_f32()).abs() < 1.0);
+        let day = regenerated(Duration::from_secs(24 * 3600));
+        assert!(day < crushing.to_f32() && day > 0.0);
+        assert!(regenerated(Duration::from_secs(100 * 24 * 3600)) < 0.01 * crushing.to_f32());
+    }
+
+    #[test]
+    fn critical_volume_relaxes_gradient_for_short_ascents() {
+        let crushing = Bar::new(5.0).to_pa();
+        let radius = adjusted_critical_radius(0.55, crushing, &VPM_B_DEFAULT);
+        let initial = allowable_gradient(radius, &VPM_B_DEFAULT);
+        let gradient = |minutes: u64| {
+            let time = Duration::from_secs(minutes * 60);
+            critical_volume_gradient(initial, crushing, time, &VPM_B_DEFAULT)
+        };
+        assert!(gradient(30) > gradient(300));
+        assert!(gradient(300) > initial);
+        // The gradient approaches the initial one as the ascent gets longer
+        assert!((gradient(1_000_000).to_f32() - initial.to_f32()).abs() < 0.01 * initial.to_f32());
+    }
+
+    #[test]
+    fn boyle_compensation_reduces_gradient_on_ascent() {
+        let gradient = Bar::new(0.6).to_pa();
+        let first = msw::new(30.0).to_pa();
+        let next = msw::new(3.0).to_pa();
+        let compensated = boyle_compensated_gradient(gradient, first, next, &VPM_B_DEFAULT);
+        assert!(compensated < gradient);
+        assert!(compensated.to_f32() > 0.0);
+        assert_eq!(
+            boyle_compensated_gradient(gradient, first, first, &VPM_B_DEFAULT),
+            gradient
+        );
+    }
+}
@@ -3,7 +3,7 @@ use core::time::Duration;
 use num::pow::Pow;
 
 use crate::dive::{DiveMeasurement, DiveProfile};
-use crate::gas::GasMix;
+use crate::gas::Gas;
 use crate::pressure_unit::{AbsPressure, Bar, Pressure};
 
 pub enum O2ToxCalculation {
@@ -23,6 +23,8 @@ pub enum O2ExposureType {
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum O2ExposureLimit {
+    /// PO2 at or below 0.5 bar, which does not count towards the CNS clock.
+    NoLimit,
     Limit(Duration),
     ExeptionalLimit(Duration),
     Unsafe,
@@ -56,50 +58,52 @@ impl O2ToxCalculation {
 
 /** https://pmc.ncbi.nlm.nih.gov/articles/PMC12500339/table/T1/ */
 fn noaa_o2_limit(po2: Bar, exposure: &O2ExposureType) -> O2ExposureLimit {
+    // Bands from the lowest PO2 up; the first band holding the PO2 applies
     let mins = match po2.to_f32() {
-        v if v <= 1.6 => match exposure {
-            O2ExposureType::Single => 45,
-            O2ExposureType::Daily24h => 150,
+        v if v <= 0.5 => return O2ExposureLimit::NoLimit,
+        v if v <= 0.6 => match exposure {
+            O2ExposureType::Single => 720,
+            O2ExposureType::Daily24h => 720,
         },
-        v if v <= 1.5 => match exposure {
-            O2ExposureType::Single => 120,
-            O2ExposureType::Daily24h => 180,
+        v if v <= 0.7 => match exposure {
+            O2ExposureType::Single => 570,
+            O2ExposureType::Daily24h => 570,
         },
-        v if v <= 1.4 => match exposure {
-            O2ExposureType::Single => 150,
-            O2ExposureType::Daily24h => 180,
+        v if v <= 0.8 => match exposure {
+            O2ExposureType::Single => 450,
+            O2ExposureType::Daily24h => 450,
         },
-        v if v <= 1.3 => match exposure {
-            O2ExposureType::Single => 180,
-            O2ExposureType::Daily24h => 210,
+        v if v <= 0.9 => match exposure {
+            O2ExposureType::Single => 360,
+            O2ExposureType::Daily24h => 360,
         },
-        v if v <= 1.2 => match exposure {
-            O2ExposureType::Single => 210,
-            O2ExposureType::Daily24h => 240,
+        v if v <= 1.0 => match exposure {
+            O2ExposureType::Single => 300,
+            O2ExposureType::Daily24h => 300,
         },
         v if v <= 1.1 => match exposure {
             O2ExposureType::Single => 240,
             O2ExposureType::Daily24h => 270,
         },
-        v if v <= 1.0 => match exposure {
-            O2ExposureType::Single => 300,
-            O2ExposureType::Daily24h => 300,
+        v if v <= 1.2 => match exposure {
+            O2ExposureType::Single => 210,
+            O2ExposureType::Daily24h => 240,
         },
-        v if v <= 0.9 => match exposure {
-            O2ExposureType::Single => 360,
-            O2ExposureType::Daily24h => 360,
+        v if v <= 1.3 => match exposure {
+            O2ExposureType::Single => 180,
+            O2ExposureType::Daily24h => 210,
         },
-        v if v <= 0.8 => match exposure {
-            O2ExposureType::Single => 450,
-            O2ExposureType::Daily24h => 450,
+        v if v <= 1.4 => match exposure {
+            O2ExposureType::Single => 150,
+            O2ExposureType::Daily24h => 180,
         },
-        v if v <= 0.7 => match exposure {
-            O2ExposureType::Single => 570,
-            O2ExposureType::Daily24h => 570,
+        v if v <= 1.5 => match exposure {
+            O2ExposureType::Single => 120,
+            O2ExposureType::Daily24h => 180,
         },
-        v if v <= 0.6 => match exposure {
-            O2ExposureType::Single => 720,
-            O2ExposureType::Daily24h => 720,
+        v if v <= 1.6 => match exposure {
+            O2ExposureType::Single => 45,
+            O2ExposureType::Daily24h => 150,
         },
         _ => 0,
     };
@@ -123,6 +127,9 @@ fn noaa_o2_limit(po2: Bar, exposure: &O2ExposureType) -> O2ExposureLimit {
 /** https://doi.org/10.28920/dhm55.3.262-270 */
 fn revised_dhm_2025_o2_limit(po2: Bar, exposure: &O2ExposureType) -> O2ExposureLimit {
     let noaa_limit = noaa_o2_limit(po2, exposure);
+    if noaa_limit == O2ExposureLimit::NoLimit {
+        return noaa_limit;
+    }
     let new_limit_1_3 = O2ExposureLimit::Limit(Duration::from_mins(match exposure {
         O2ExposureType::Single => 240,
         // TODO: Total with Working Phase 240 + Resting 240
@@ -229,9 +236,9 @@ pub fn calculate_toxicity_from_profile<
 ///
 /// # Returns
 /// The new cumulative O2ToxicityPercentage including all measurements from start_index onwards
-pub fn calculate_toxicity_diff<const NUM_GASES: usize, P: const AbsPressure>(
+pub fn calculate_toxicity_diff<const NUM_GASES: usize, P: const AbsPressure, G: const Gas>(
     measurements: &[DiveMeasurement<P>],
-    gases: &[GasMix<f32>; NUM_GASES],
+    gases: &[G; NUM_GASES],
     start_index: usize,
     previous_toxicity: &O2ToxicityPercentage,
     exposure_type: &O2ExposureType,
@@ -256,41 +263,39 @@ pub fn calculate_toxicity_diff<const NUM_GASES: usize, P: const AbsPressure>(
 
         // Use current depth
         let depth = curr_measurement.depth;
-        let gas_mix = &gases[curr_measurement.gas];
+        let gas = &gases[curr_measurement.gas];
 
-        // Calculate PO2 at current depth
-        let abs_pressure = depth.to_pa();
-        let po2 = Bar::new(abs_pressure.to_bar().to_f32() * gas_mix.fo2());
+        // Calculate PO2 at current depth; loops (CCR/SCR) report their own PO2
+        let po2 = gas.po2(depth.to_pa()).to_bar();
 
         // Get exposure limit for this PO2
         let limit = calculation_method.limit(exposure_type, po2);
 
-        // Add to percentage if within safe limits
-        match limit {
-            O2ExposureLimit::Limit(max_duration) => {
-                let max_secs = max_duration.as_secs_f32();
-                let elapsed_secs = delta_time.as_secs_f32();
-                cns_percent += (elapsed_secs / max_secs) * 100.0;
-                pulmonary_percent += (elapsed_secs / max_secs) * 100.0;
-            }
-            O2ExposureLimit::ExeptionalLimit(max_duration) => {
-                let max_secs = max_duration.as_secs_f32();
-                let elapsed_secs = delta_time.as_secs_f32();
-                // Exceptional limits contribute more aggressively to toxicity
-                cns_percent += (elapsed_secs / max_secs) * 150.0;
-                pulmonary_percent += (elapsed_secs / max_secs) * 150.0;
-            }
-            O2ExposureLimit::Unsafe => {
-                // Unsafe exposure contributes immediately
-                cns_percent += 200.0;
-                pulmonary_percent += 200.0;
-            }
-        }
+        let percent = exposure_percent(limit, delta_time);
+        cns_percent += percent;
+        pulmonary_percent += percent;
     }
 
     O2ToxicityPercentage::new(cns_percent, pulmonary_percent)
 }
 
+/// Percentage of `limit` used up by breathing for `time`.
+pub fn exposure_percent(limit: O2ExposureLimit, time: Duration) -> f32 {
+    let elapsed_secs = time.as_secs_f32();
+    match limit {
+        O2ExposureLimit::NoLimit => 0.0,
+        O2ExposureLimit::Limit(max_duration) => {
+            (elapsed_secs / max_duration.as_secs_f32()) * 100.0
+        }
+        // Exceptional limits contribute more aggressively to toxicity
+        O2ExposureLimit::ExeptionalLimit(max_duration) => {
+            (elapsed_secs / max_duration.as_secs_f32()) * 150.0
+        }
+        // Unsafe exposure contributes immediately
+        O2ExposureLimit::Unsafe => 200.0,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::dive::DiveMeasurement;
@@ -337,6 +342,33 @@ mod tests {
         }
     }
 
+    #[test]
+    fn noaa_limits_follow_the_po2_bands() {
+        let single =
+            |po2: f32| O2ToxCalculation::NOAA.limit(&O2ExposureType::Single, Bar::new(po2));
+        let minutes = |mins: u64| O2ExposureLimit::Limit(Duration::from_mins(mins));
+        assert_eq!(single(0.5), O2ExposureLimit::NoLimit);
+        assert_eq!(
+            O2ToxCalculation::RevisedDHM2025.limit(&O2ExposureType::Single, Bar::new(0.21)),
+            O2ExposureLimit::NoLimit
+        );
+        assert_eq!(single(0.6), minutes(720));
+        assert_eq!(single(1.0), minutes(300));
+        assert_eq!(single(1.3), minutes(180));
+        assert_eq!(single(1.4), minutes(150));
+        assert_eq!(single(1.45), minutes(120));
+        assert_eq!(single(1.6), minutes(45));
+        assert_eq!(
+            single(1.7),
+            O2ExposureLimit::ExeptionalLimit(Duration::from_mins(75))
+        );
+        assert_eq!(single(2.1), O2ExposureLimit::Unsafe);
+        assert_eq!(
+            O2ToxCalculation::NOAA.limit(&O2ExposureType::Daily24h, Bar::new(1.2)),
+            minutes(240)
+        );
+    }
+
     #[test]
     fn get_otu_test() {
         let po2_levels = [
@@ -618,6 +650,52 @@ mod tests {
         );
     }
 
+    #[test]
+    fn toxicity_of_scr_loop_follows_loop_po2_test() {
+        use crate::gas::{SCRGas, SCRSupply};
+
+        let measurements = [
+            DiveMeasurement {
+                time_ms: 0,
+                depth: Bar::new(4.0),
+                gas: 0,
+            },
+            DiveMeasurement {
+                time_ms: 1_800_000, // 30 minutes
+                depth: Bar::new(4.0),
+                gas: 0,
+            },
+        ];
+        let nitrox40 = GasMix::new(0.40, 0.0).unwrap();
+        let scr = SCRGas {
+            supply_gas: nitrox40,
+            supply: SCRSupply::Active { flow_l_min: 15.0 },
+            o2_consumption_l_min: 1.0,
+        };
+        let initial = O2ToxicityPercentage::new(0.0, 0.0);
+        let loop_equivalent = calculate_toxicity_diff(
+            &measurements,
+            &[scr.to_fixed_gas_mix(Bar::new(4.0))],
+            0,
+            &initial,
+            &O2ExposureType::Single,
+            O2ToxCalculation::NOAA,
+        );
+        let semi_closed = calculate_toxicity_diff(
+            &measurements,
+            &[scr],
+            0,
+            &initial,
+            &O2ExposureType::Single,
+            O2ToxCalculation::NOAA,
+        );
+
+        // PO2 1.43 bar on the loop, not the 1.6 bar of the supply gas
+        assert!((scr.po2(Bar::new(4.0)).to_f32() - 4.0 * 5.0 / 14.0).abs() < 1e-5);
+        assert!(semi_closed.cns_percent > 0.0);
+        assert!((semi_closed.cns_percent - loop_equivalent.cns_percent).abs() < 0.01);
+    }
+
     #[test]
     fn toxicity_differential_streaming_test() {
         // Test differential calculation for streaming scenario
@@ -0,0 +1,457 @@
+use core::{f32::consts::LN_2, time::Duration};
+
+#[allow(unused)]
+use num::Float;
+
+use crate::{
+    dive::{DiveMeasurement, DiveProfile, StopSchedule},
+    gas::{AIR, GasMix, TissuesLoading},
+    mptt::{ThalmannTissue, Tissue},
+    mptt_thalmann::{THALMANN_DEFAULT_SETTINGS, THALMANN_FSW_TO_PA},
+    pressure_unit::{AbsPressure, Pa, Pressure, ambient_pressure_at_depth},
+    setup::{DINC, DINC_PA},
+    update_exp_lin::update_model_state_lin_exp,
+};
+
+/// Step used to integrate hazard over ascents and the post-dive surface interval.
+const RISK_INTEGRATION_STEP: Duration = Duration::from_secs(10);
+/// Hazard is integrated after surfacing until it vanishes, or for at most this long.
+const MAX_SURFACE_TAIL: Duration = Duration::from_secs(48 * 60 * 60);
+
+/** Compartment of a probabilistic linear-exponential (LE) model. */
+#[derive(Clone, Copy)]
+pub struct RiskTissue {
+    pub tissue: Tissue,
+    /// Hazard gain in 1/min
+    pub gain: f32,
+    pub threshold: Pa,
+}
+
+/**
+* Thalmann linear-exponential probabilistic model:
+* r_i(t) = G_i * max(0, (p_i - p_amb - thr_i) / p_amb)
+* P_DCS = 1 - exp(-integral sum_i r_i dt)
+*/
+#[derive(Clone, Copy)]
+pub struct RiskModel<const NUM_TISSUES: usize> {
+    pub tissues: [RiskTissue; NUM_TISSUES],
+}
+
+const fn le_tissue(time_constant_min: f32, gain: f32, threshold_fsw: f32) -> RiskTissue {
+    RiskTissue {
+        tissue: Tissue {
+            half_time: time_constant_min * LN_2,
+            sdr: 1.0,
+        },
+        gain,
+        threshold: Pa::new(threshold_fsw * THALMANN_FSW_TO_PA),
+    }
+}
+
+/// LE1 parameters of the NMRI98 calibration (Thalmann et al. 1997, UHM 24(4)).
+pub const LE1_NMRI98: RiskModel<3> = RiskModel {
+    tissues: [
+        le_tissue(1.7727, 3.0918E-3, 0.0),
+        le_tissue(59.772, 1.1503E-4, 0.0),
+        le_tissue(520.66, 1.5935E-3, 0.0),
+    ],
+};
+
+impl<const NUM_TISSUES: usize> RiskModel<NUM_TISSUES> {
+    pub fn kinetics(&self) -> [ThalmannTissue; NUM_TISSUES] {
+        self.tissues.map(|t| ThalmannTissue::uniform(t.tissue))
+    }
+
+    /// Instantaneous hazard summed over all compartments, in 1/min.
+    pub fn hazard<P: const AbsPressure>(
+        &self,
+        loading: &TissuesLoading<NUM_TISSUES, P>,
+        ambient: P,
+    ) -> f32 {
+        let p_amb = ambient.to_pa().to_f32();
+        self.tissues
+            .iter()
+            .enumerate()
+            .map(|(i, t)| {
+                let p_tissue = loading.total_inert(i).to_pa().to_f32();
+                let supersaturation = (p_tissue - p_amb - t.threshold.to_f32()) / p_amb;
+                t.gain * supersaturation.max(0.0)
+            })
+            .sum()
+    }
+}
+
+/** Risk compartment state and the hazard accumulated so far. */
+#[derive(Debug, Clone)]
+pub struct RiskIntegral<const NUM_TISSUES: usize, P: const AbsPressure> {
+    pub loading: TissuesLoading<NUM_TISSUES, P>,
+    pub cumulative_risk: f32,
+}
+
+impl<const NUM_TISSUES: usize, P: const AbsPressure> RiskIntegral<NUM_TISSUES, P> {
+    /// Compartments saturated on air at the surface.
+    pub fn new(surface_pressure: P) -> Self {
+        RiskIntegral {
+            loading: TissuesLoading::new(surface_pressure, &AIR),
+            cumulative_risk: 0.0,
+        }
+    }
+
+    /// Advances the compartments at constant `ambient` and integrates the hazard
+    /// (trapezoidal rule over the step).
+    pub fn step(
+        &mut self,
+        model: &RiskModel<NUM_TISSUES>,
+        breathing_gas: &GasMix<f32>,
+        ambient: P,
+        delta_time: &Duration,
+    ) {
+        let hazard_before = model.hazard(&self.loading, ambient);
+        update_model_state_lin_exp(
+            &mut self.loading,
+            &model.kinetics(),
+            &THALMANN_DEFAULT_SETTINGS,
+            breathing_gas,
+            ambient,
+            delta_time,
+        );
+        let hazard_after = model.hazard(&self.loading, ambient);
+        self.cumulative_risk +=
+            0.5 * (hazard_before + hazard_after) * delta_time.as_secs_f32() / 60.0;
+    }
+
+    /// Linear depth change from `from` to `to` over `duration` (a constant depth
+    /// if both are equal), in integration steps.
+    pub fn travel(
+        &mut self,
+        model: &RiskModel<NUM_TISSUES>,
+        breathing_gas: &GasMix<f32>,
+        from: P,
+        to: P,
+        duration: &Duration,
+    ) {
+        let steps = (duration.as_secs_f32() / RISK_INTEGRATION_STEP.as_secs_f32()).ceil() as u32;
+        if steps == 0 {
+            return;
+        }
+        let dt = *duration / steps;
+        for s in 0..steps {
+            let fraction = (s as f32 + 0.5) / steps as f32;
+            let ambient = from + (to - from) * fraction;
+            self.step(model, breathing_gas, ambient, &dt);
+        }
+    }
+
+    /// Integrates the post-dive surface interval on air until the hazard vanishes.
+    pub fn surface_tail(&mut self, model: &RiskModel<NUM_TISSUES>, surface_pressure: P) {
+        let mut elapsed = Duration::ZERO;
+        while elapsed < MAX_SURFACE_TAIL && model.hazard(&self.loading, surface_pressure) > 0.0 {
+            self.step(model, &AIR, surface_pressure, &RISK_INTEGRATION_STEP);
+            elapsed += RISK_INTEGRATION_STEP;
+        }
+    }
+
+    pub fn p_dcs(&self) -> f32 {
+        1.0 - (-self.cumulative_risk).exp()
+    }
+}
+
+/// Estimated probability of DCS for a recorded dive, including the surface tail.
+pub fn p_dcs_from_dive_profile<
+    const NUM_TISSUES: usize,
+    const NUM_GASES: usize,
+    const NUM_MEASUREMENTS: usize,
+    P: const AbsPressure,
+>(
+    model: &RiskModel<NUM_TISSUES>,
+    profile: &DiveProfile<P, f32, NUM_GASES, NUM_MEASUREMENTS>,
+    surface_pressure: P,
+) -> f32 {
+    let mut integral = RiskIntegral::new(surface_pressure);
+    for w in profile.measurements.windows(2) {
+        let DiveMeasurement {
+            time_ms: time_ms_prev,
+            depth: depth_prev,
+            gas: _gas_prev,
+        } = &w[0];
+        let DiveMeasurement {
+            time_ms,
+            depth,
+            gas,
+        } = &w[1];
+        let delta_time = Duration::from_millis((time_ms - time_ms_prev) as u64);
+        integral.travel(
+            model,
+            &profile.gases[*gas],
+            *depth_prev,
+            *depth,
+            &delta_time,
+        );
+    }
+    integral.surface_tail(model, surface_pressure);
+    integral.p_dcs()
+}
+
+/**
+* Estimated probability of DCS for ascending from `bottom_depth` along `schedule`.
+* `integral` holds the risk compartments at the start of the ascent; travel
+* between stops uses `ascent_rate_per_meter` and the gas of the depth being left.
+*/
+pub fn p_dcs_from_schedule<
+    const NUM_TISSUES: usize,
+    const NUM_STOPS: usize,
+    P: const AbsPressure,
+>(
+    model: &RiskModel<NUM_TISSUES>,
+    integral: &RiskIntegral<NUM_TISSUES, P>,
+    bottom_depth: P,
+    bottom_gas: &GasMix<f32>,
+    schedule: &StopSchedule<NUM_STOPS>,
+    surface_pressure: P,
+    ascent_rate_per_meter: &Duration,
+) -> f32 {
+    let mut integral = integral.clone();
+    let mut depth = bottom_depth;
+    let mut gas = *bottom_gas;
+    let travel_time = |from: P, to: P| -> Duration {
+        let meters = (from.to_pa() - to.to_pa()).to_f32() / (DINC_PA.to_f32() / DINC.to_f32());
+        ascent_rate_per_meter.mul_f32(meters.max(0.0))
+    };
+    for stop in schedule.stops().iter().filter(|s| !s.duration().is_zero()) {
+        let stop_ambient = ambient_pressure_at_depth(surface_pressure, stop.depth());
+        integral.travel(
+            model,
+            &gas,
+            depth,
+            stop_ambient,
+            &travel_time(depth, stop_ambient),
+        );
+        gas = stop.gas().unwrap_or(gas);
+        integral.travel(model, &gas, stop_ambient, stop_ambient, &stop.duration());
+        depth = stop_ambient;
+    }
+    integral.travel(
+        model,
+        &gas,
+        depth,
+        surface_pressure,
+        &travel_time(depth, surface_pressure),
+    );
+    integral.surface_tail(model, surface_pressure);
+    integral.p_dcs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        deco_algorithm::{
+            DecoSettings, RiskTarget, RiskTargetedSchedule, calc_deco_schedule,
+            calc_deco_schedule_for_p_dcs,
+        },
+        deco_model::{DecoModel, THALMANN_XVAL_HE9_040},
+        dive::{Stop, get_ascent_rate_per_meter},
+        gas::{AIR, GasDensitySettings, IcdSettings, MAX_PO2_DECO, NX50, NarcosisSettings},
+        gas_policy::GasSwitchPolicy,
+        pressure_unit::{ambient_pressure_at_depth, msw},
+    };
+    use std::println;
+
+    fn air_bottom(depth: msw, bottom_time_min: u64) -> RiskIntegral<3, Pa> {
+        let surface = msw::new(0.0).to_pa();
+        let mut integral = RiskIntegral::new(surface);
+        integral.travel(
+            &LE1_NMRI98,
+            &AIR,
+            surface,
+            depth.to_pa(),
+            &Duration::from_secs(60),
+        );
+        integral.travel(
+            &LE1_NMRI98,
+            &AIR,
+            depth.to_pa(),
+            depth.to_pa(),
+            &Duration::from_secs(bottom_time_min * 60),
+        );
+        integral
+    }
+
+    fn direct_ascent_p_dcs(depth: msw, bottom_time_min: u64) -> f32 {
+        p_dcs_from_schedule(
+            &LE1_NMRI98,
+            &air_bottom(depth, bottom_time_min),
+            depth.to_pa(),
+            &AIR,
+            &StopSchedule::<1>::default(),
+            msw::new(0.0).to_pa(),
+            &get_ascent_rate_per_meter(9),
+        )
+    }
+
+    #[test]
+    fn no_risk_without_dive() {
+        let surface = msw::new(0.0).to_pa();
+        let mut integral: RiskIntegral<3, Pa> = RiskIntegral::new(surface);
+        integral.surface_tail(&LE1_NMRI98, surface);
+        assert_eq!(integral.p_dcs(), 0.0);
+    }
+
+    #[test]
+    fn risk_grows_with_bottom_time() {
+        let short = direct_ascent_p_dcs(msw::new(30.0), 20);
+        let long = direct_ascent_p_dcs(msw::new(30.0), 60);
+        println!("30 m air direct ascent: 20 min {short}, 60 min {long}");
+        assert!(short > 0.0);
+        assert!(long > short);
+        assert!(long < 1.0);
+    }
+
+    #[test]
+    fn stops_reduce_risk() {
+        let depth = msw::new(30.0);
+        let direct = direct_ascent_p_dcs(depth, 60);
+        let mut stops = *StopSchedule::<4>::default().stops();
+        stops[2] = Stop::new(msw::new(6.0), Duration::from_secs(10 * 60), Some(NX50));
+        stops[3] = Stop::new(msw::new(3.0), Duration::from_secs(20 * 60), Some(NX50));
+        let staged = p_dcs_from_schedule(
+            &LE1_NMRI98,
+            &air_bottom(depth, 60),
+            depth.to_pa(),
+            &AIR,
+            &StopSchedule::new(stops),
+            msw::new(0.0).to_pa(),
+            &get_ascent_rate_per_meter(9),
+        );
+        println!("30 m / 60 min air: direct {direct}, staged {staged}");
+        assert!(staged < direct);
+    }
+
+    #[test]
+    fn schedule_search_meets_target_p_dcs() {
+        let depth = msw::new(40.0);
+        let surface = msw::new(0.0).to_pa();
+        let mut loading = TissuesLoading::new(surface, &AIR);
+        THALMANN_XVAL_HE9_040.update_model_state(
+            &mut loading,
+            &AIR,
+            depth.to_pa(),
+            &Duration::from_secs(40 * 60),
+        );
+        let integral = air_bottom(depth, 40);
+        let settings = DecoSettings {
+            gas_density_settings: GasDensitySettings::Ignore,
+            narcosis_settings: NarcosisSettings::Ignore,
+            max_deco_po2: MAX_PO2_DECO.to_pa(),
+            surface_pressure: surface,
+            icd_settings: IcdSettings::Ignore,
+            gf_low: 0.5,
+            gf_high: 0.85,
+            last_deco_stop: msw::new(3.0),
+            ascent_rate_per_meter: Duration::from_secs(6),
+            gas_switch_policy: GasSwitchPolicy::default(),
+        };
+        let plan = |max_p_dcs: f32| {
+            let target = RiskTarget {
+                model: &LE1_NMRI98,
+                integral: &integral,
+                bottom_depth: depth.to_pa(),
+                bottom_gas: &AIR,
+                max_p_dcs,
+            };
+            calc_deco_schedule_for_p_dcs::<_, _, 30, 2, 3>(
+                &THALMANN_XVAL_HE9_040,
+                &loading,
+                &[AIR, NX50],
+                &[true; 2],
+                &settings,
+                &target,
+            )
+            .expect("target reachable")
+        };
+        let total = |plan: &RiskTargetedSchedule<30>| -> Duration {
+            plan.schedule.stops().iter().map(|s| s.duration()).sum()
+        };
+
+        let liberal = plan(0.05);
+        let strict = plan(0.015);
+        println!(
+            "40 m / 40 min air: P_DCS {} in {:?}, P_DCS {} in {:?}",
+            liberal.p_dcs,
+            total(&liberal),
+            strict.p_dcs,
+            total(&strict)
+        );
+        assert!(liberal.p_dcs <= 0.05);
+        assert!(strict.p_dcs <= 0.015);
+        assert!(total(&strict) >= total(&liberal));
+        // A loose target is met above the configured gradient factors
+        assert!(liberal.gradient_factors.high > settings.gf_high);
+        assert!(strict.gradient_factors.high < settings.gf_high);
+    }
+
+    #[test]
+    fn schedule_search_starts_at_bottom_depth_at_altitude() {
+        let depth = msw::new(40.0);
+        let surface = Pa::new(0.8e5);
+        let bottom = ambient_pressure_at_depth(surface, depth);
+        let mut loading = TissuesLoading::new(surface, &AIR);
+        THALMANN_XVAL_HE9_040.update_model_state(
+            &mut loading,
+            &AIR,
+            bottom,
+            &Duration::from_secs(40 * 60),
+        );
+        let mut integral = RiskIntegral::new(surface);
+        integral.travel(&LE1_NMRI98, &AIR, surface, bottom, &Duration::from_secs(60));
+        integral.travel(&LE1_NMRI98, &AIR, bottom, bottom, &Duration::from_secs(40 * 60));
+        let settings = DecoSettings {
+            gas_density_settings: GasDensitySettings::Ignore,
+            narcosis_settings: NarcosisSettings::Ignore,
+            max_deco_po2: MAX_PO2_DECO.to_pa(),
+            surface_pressure: surface,
+            icd_settings: IcdSettings::Ignore,
+            gf_low: 0.5,
+            gf_high: 0.85,
+            last_deco_stop: msw::new(3.0),
+            ascent_rate_per_meter: Duration::from_secs(6),
+            gas_switch_policy: GasSwitchPolicy::default(),
+        };
+        let target = RiskTarget {
+            model: &LE1_NMRI98,
+            integral: &integral,
+            bottom_depth: bottom,
+            bottom_gas: &AIR,
+            max_p_dcs: 0.05,
+        };
+        let plan = calc_deco_schedule_for_p_dcs::<_, _, 30, 2, 3>(
+            &THALMANN_XVAL_HE9_040,
+            &loading,
+            &[AIR, NX50],
+            &[true; 2],
+            &settings,
+            &target,
+        )
+        .expect("target reachable");
+        // The searched schedule matches a plain one from 40 m with the same gradient factors
+        let reference = calc_deco_schedule::<_, _, 30, 2>(
+            &THALMANN_XVAL_HE9_040,
+            &loading,
+            depth,
+            0,
+            &[AIR, NX50],
+            &[true; 2],
+            &DecoSettings {
+                gf_low: plan.gradient_factors.low,
+                gf_high: plan.gradient_factors.high,
+                ..settings
+            },
+        )
+        .expect("schedule");
+        println!("40 m / 40 min air at 0.8 bar: {:?}", plan.schedule);
+        for (planned, expected) in plan.schedule.stops().iter().zip(reference.stops()) {
+            assert_eq!(planned.depth(), expected.depth());
+            assert_eq!(planned.duration(), expected.duration());
+        }
+    }
+}
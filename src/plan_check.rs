@@ -0,0 +1,223 @@
+use crate::{
+    dive::{DiveMeasurement, DiveProfile, StopSchedule},
+    gas::{Gas, GasMix},
+    pressure_unit::{
+        AbsPressure, Bar, Pa, Pressure, ambient_pressure_at_depth, depth_at_ambient_pressure, msw,
+    },
+};
+
+/** ppO2 window a gas may be breathed in, e.g. `MIN_PO2_HYPOXIC` to `MAX_PO2_DECO`. */
+#[derive(Debug, Clone, Copy)]
+pub struct PO2Limits {
+    pub min_po2: Bar,
+    pub max_po2: Bar,
+}
+
+impl PO2Limits {
+    /// Depth range from the minimum to the maximum operating depth of `gas`.
+    pub fn operating_depths(&self, gas: &GasMix<f32>, surface_pressure: Pa) -> (msw, msw) {
+        (
+            gas.min_operating_depth(self.min_po2, surface_pressure),
+            gas.max_operating_depth(self.max_po2, surface_pressure),
+        )
+    }
+
+    fn check(&self, gas: &GasMix<f32>, depth: msw, surface_pressure: Pa) -> Option<GasViolation> {
+        let po2 = gas
+            .po2(ambient_pressure_at_depth(surface_pressure, depth))
+            .to_bar();
+        let kind = if po2 < self.min_po2 {
+            PO2Violation::Hypoxic
+        } else if po2 > self.max_po2 {
+            PO2Violation::Hyperoxic
+        } else {
+            return None;
+        };
+        Some(GasViolation {
+            depth,
+            gas: *gas,
+            po2,
+            kind,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PO2Violation {
+    /// Breathed shallower than its minimum operating depth.
+    Hypoxic,
+    /// Breathed deeper than its maximum operating depth.
+    Hyperoxic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GasViolation {
+    pub depth: msw,
+    pub gas: GasMix<f32>,
+    pub po2: Bar,
+    pub kind: PO2Violation,
+}
+
+/** Violations of a planned ascent, see `check_ascent`. */
+#[derive(Debug, Clone, Copy)]
+pub struct AscentCheck<const NUM_STOPS: usize> {
+    /// Bottom gas on the way up to the first stop.
+    pub bottom: Option<GasViolation>,
+    /// Indexed like the stops of the schedule.
+    pub stops: [Option<GasViolation>; NUM_STOPS],
+}
+
+impl<const NUM_STOPS: usize> AscentCheck<NUM_STOPS> {
+    pub fn is_safe(&self) -> bool {
+        self.bottom.is_none() && self.stops.iter().all(|v| v.is_none())
+    }
+}
+
+/// Checks the gas of every measurement at its depth, indexed like the measurements.
+pub fn check_profile<
+    P: const AbsPressure,
+    const NUM_GASES: usize,
+    const NUM_MEASUREMENTS: usize,
+>(
+    profile: &DiveProfile<P, f32, NUM_GASES, NUM_MEASUREMENTS>,
+    limits: &PO2Limits,
+    surface_pressure: P,
+) -> [Option<GasViolation>; NUM_MEASUREMENTS] {
+    let surface_pressure = surface_pressure.to_pa();
+    profile.measurements.map(|m: DiveMeasurement<P>| {
+        let depth = depth_at_ambient_pressure(surface_pressure, m.depth.to_pa());
+        limits.check(&profile.gases[m.gas], depth, surface_pressure)
+    })
+}
+
+/**
+* Checks `bottom_gas` from `depth` up to the first stop and every stop gas from
+* its stop up to the next one, or to the surface after the last stop. A gas is
+* hyperoxic if it is too rich where it is switched to, and hypoxic if it is too
+* lean where it is left.
+*/
+pub fn check_ascent<const NUM_STOPS: usize>(
+    depth: msw,
+    bottom_gas: &GasMix<f32>,
+    schedule: &StopSchedule<NUM_STOPS>,
+    limits: &PO2Limits,
+    surface_pressure: Pa,
+) -> Result<AscentCheck<NUM_STOPS>, &'static str> {
+    let check_leg = |gas: &GasMix<f32>, from: msw, to: msw| {
+        limits
+            .check(gas, from, surface_pressure)
+            .or_else(|| limits.check(gas, to, surface_pressure))
+    };
+    let stops = schedule.stops();
+    let next_depth = |i: usize| {
+        stops[i + 1..]
+            .iter()
+            .find(|s| !s.duration().is_zero())
+            .map_or(msw::new(0.0), |s| s.depth())
+    };
+
+    let mut check = AscentCheck {
+        bottom: check_leg(
+            bottom_gas,
+            depth,
+            schedule.first_stop().map_or(msw::new(0.0), |s| s.depth()),
+        ),
+        stops: [None; NUM_STOPS],
+    };
+    for (i, stop) in stops.iter().enumerate() {
+        if stop.duration().is_zero() {
+            continue;
+        }
+        let gas = stop.gas().ok_or("Stop without a gas.")?;
+        check.stops[i] = check_leg(&gas, stop.depth(), next_depth(i));
+    }
+    Ok(check)
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::*;
+    use crate::{
+        dive::Stop,
+        gas::{AIR, MAX_PO2_DECO, MAX_PO2_WORKING, MIN_PO2_HYPOXIC, NX50, NX100, TMX10_80},
+    };
+
+    const WORKING: PO2Limits = PO2Limits {
+        min_po2: MIN_PO2_HYPOXIC,
+        max_po2: MAX_PO2_WORKING,
+    };
+    const DECO: PO2Limits = PO2Limits {
+        min_po2: MIN_PO2_HYPOXIC,
+        max_po2: MAX_PO2_DECO,
+    };
+
+    #[test]
+    fn operating_depths() {
+        let surface = msw::new(0.0).to_pa();
+        let (min, max) = DECO.operating_depths(&NX50, surface);
+        assert_eq!(min, msw::new(0.0));
+        assert!((max.to_f32() - 21.7).abs() < 0.1, "{max:?}");
+        let (min, max) = WORKING.operating_depths(&TMX10_80, surface);
+        assert!((min.to_f32() - 5.9).abs() < 0.1, "{min:?}");
+        assert!((max.to_f32() - 128.3).abs() < 0.1, "{max:?}");
+    }
+
+    #[test]
+    fn profile_flags_hypoxic_surface_and_hyperoxic_bottom() {
+        let measurement = |time_ms: usize, depth: f32, gas: usize| DiveMeasurement {
+            time_ms,
+            depth: msw::new(depth).to_pa(),
+            gas,
+        };
+        let profile: DiveProfile<Pa, f32, 2, 4> = DiveProfile {
+            dive_id: 1,
+            max_depth: msw::new(40.0).to_pa(),
+            gases: [TMX10_80, NX50],
+            measurements: [
+                measurement(0, 0.0, 0),
+                measurement(60_000, 40.0, 0),
+                measurement(120_000, 40.0, 1),
+                measurement(180_000, 16.0, 1),
+            ],
+        };
+        let violations = check_profile(&profile, &WORKING, msw::new(0.0).to_pa());
+        std::println!("{violations:?}");
+        assert_eq!(violations[0].map(|v| v.kind), Some(PO2Violation::Hypoxic));
+        assert_eq!(violations[1], None);
+        assert_eq!(violations[2].map(|v| v.kind), Some(PO2Violation::Hyperoxic));
+        assert_eq!(violations[3], None);
+    }
+
+    #[test]
+    fn ascent_check_follows_the_legs() {
+        let surface = msw::new(0.0).to_pa();
+        let mut stops = *StopSchedule::<30>::default().stops();
+        for (depth, gas) in [(21.0, NX50), (9.0, NX50), (6.0, NX100)] {
+            let idx = 29 - (depth / 3.0) as usize;
+            stops[idx] = Stop::new(msw::new(depth), Duration::from_secs(120), Some(gas));
+        }
+        let schedule = StopSchedule::new(stops);
+        let safe = check_ascent(msw::new(60.0), &TMX10_80, &schedule, &DECO, surface)
+            .expect("all stops have a gas");
+        // 10/80 is left at 21 m, deep enough to still be normoxic
+        assert!(safe.is_safe(), "{safe:?}");
+
+        stops[29 - 7] = Stop::new(msw::new(21.0), Duration::from_secs(120), Some(NX100));
+        stops[29 - 2] = Stop::new(msw::new(6.0), Duration::from_secs(120), Some(TMX10_80));
+        let unsafe_schedule = StopSchedule::new(stops);
+        let check = check_ascent(msw::new(30.0), &AIR, &unsafe_schedule, &DECO, surface)
+            .expect("all stops have a gas");
+        assert_eq!(check.bottom, None);
+        assert_eq!(
+            check.stops[29 - 7].map(|v| v.kind),
+            Some(PO2Violation::Hyperoxic)
+        );
+        assert_eq!(check.stops[29 - 3], None);
+        assert_eq!(
+            check.stops[29 - 2].map(|v| v.kind),
+            Some(PO2Violation::Hypoxic)
+        );
+    }
+}
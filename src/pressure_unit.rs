@@ -32,6 +32,12 @@ pub fn ambient_pressure_at_depth<P: const AbsPressure>(surface_pressure: P, dept
     surface_pressure + (depth.to_pa() - msw::new(0.0).to_pa()).into()
 }
 
+/// Inverse of `ambient_pressure_at_depth`.
+pub fn depth_at_ambient_pressure<P: const AbsPressure>(surface_pressure: P, ambient: P) -> msw {
+    let pa_per_msw = msw::new(1.0).to_pa().to_f32() - msw::new(0.0).to_pa().to_f32();
+    msw::new((ambient.to_pa().to_f32() - surface_pressure.to_pa().to_f32()) / pa_per_msw)
+}
+
 /// Macro to generate pressure unit newtypes + trait impl + arithmetic
 macro_rules! pressure_unit {
     ($name:ident, $to_pa_factor:expr) => {
@@ -221,9 +227,8 @@ mod tests {
     #[test]
     fn imp_metric_test() {
         assert_eq!(fsw::new(0.0).to_msw(), msw::new(0.0));
-        assert_eq!(
-            msw::new(10.0).to_f32() - fsw::new(33.0).to_msw().to_f32() < msw::new(0.02).to_f32(),
-            true
+        assert!(
+            msw::new(10.0).to_f32() - fsw::new(33.0).to_msw().to_f32() < msw::new(0.02).to_f32()
         );
     }
 
@@ -247,4 +252,12 @@ mod tests {
         assert_eq!(msw6.to_bar(), Bar::new(1.6078));
         assert_eq!(msw10.to_bar(), Bar::new(2.013));
     }
+
+    #[test]
+    fn depth_at_ambient_pressure_test() {
+        let surface = Bar::new(0.9);
+        let ambient = ambient_pressure_at_depth(surface, msw::new(21.0));
+        let depth = depth_at_ambient_pressure(surface, ambient);
+        assert!((depth.to_f32() - 21.0).abs() < 1e-4, "{depth:?}");
+    }
 }
@@ -1,51 +1,67 @@
-#[cfg(feature = "lin_exp")]
-use core::f32::consts::LN_2;
-
 #[cfg(not(test))]
 use num::Float;
 
-#[cfg(feature = "lin_exp")]
-use crate::mptt::Tissue;
-use crate::pressure_unit::AbsPressure;
-#[cfg(not(feature = "lin_exp"))]
-use crate::pressure_unit::{Pa, Pressure};
+use crate::gas::{InertGas, NUM_INERT_GASES};
+use crate::mptt::TissueRow;
+use crate::mptt_buehlmann::BuehlmannTissue;
+use crate::pressure_unit::{AbsPressure, Pa, Pressure, msw};
 
 pub(crate) fn exp_pressure<P: const AbsPressure>(p_inspired: P, p_old: P, k: f32, t: f32) -> P {
     let exp = (-k * t).exp();
     p_inspired + (p_old - p_inspired) * exp
 }
 
-/// Return (KSAT_array, KDSAT_array) for the given tissues.
-#[cfg(feature = "lin_exp")]
-pub(crate) fn ks_arrays<const N: usize>(tissues: &[Tissue; N]) -> ([f32; N], [f32; N]) {
-    let ks_sat: [f32; N] = tissues.map(|t| LN_2 / t.half_time);
-    let ks_desat: [f32; N] = tissues.map(|t| (LN_2 / t.half_time) * t.sdr);
-    (ks_sat, ks_desat)
-}
-
-/// Compute mixed Buehlmann a/b based M-value for a tissue using current
-/// tissue partial pressures as weights. Falls back to zero if total inert is zero.
-#[cfg(not(feature = "lin_exp"))]
+/// Compute mixed Buehlmann M-value (M = a + P_amb / b) for a tissue using current
+/// tissue partial pressures as weights. Falls back to the N2 coefficients if total inert is zero.
 pub(crate) fn mixed_buehlmann_mvalue(
-    tissue_idx: usize,
-    p_n2: Pa,
-    p_he: Pa,
+    tissue: &BuehlmannTissue,
+    tensions: [Pa; NUM_INERT_GASES],
     stop_depth_pa: Pa,
 ) -> Pa {
-    use crate::mptt_buehlmann::TISSUES as BUEHL_TISSUES;
-    let total = p_n2 + p_he;
+    let total = tensions.iter().fold(Pa::new(0.0), |sum, p| sum + *p);
     if total.to_f32() <= 0.0 {
-        return BUEHL_TISSUES[tissue_idx].n2.a.to_pa()
-            + stop_depth_pa * BUEHL_TISSUES[tissue_idx].n2.b;
+        return tissue.n2.a.to_pa() + stop_depth_pa / tissue.n2.b;
+    }
+
+    let mut a_mix = Pa::new(0.0);
+    let mut b_mix = 0.0;
+    for gas in InertGas::ALL {
+        let coefficients = tissue.for_gas(gas);
+        let frac = tensions[gas.idx()] / total;
+        a_mix += coefficients.a.to_pa() * frac;
+        b_mix += coefficients.b * frac;
+    }
+    a_mix + stop_depth_pa / b_mix
+}
+
+/// Shifts every M-value row by the per-tissue slope of the table times the
+/// difference between `surface_pressure` and sea level.
+pub(crate) fn surface_pressure_adjusted_mvalues<const NUM_TISSUES: usize, P: const AbsPressure>(
+    m_values: &mut [TissueRow<NUM_TISSUES, P>],
+    surface_pressure: P,
+) {
+    if m_values.len() < 2 {
+        return;
+    }
+
+    let sea_level_surface = msw::new(0.0).to_pa();
+    let surface_delta_pa = surface_pressure.to_pa() - sea_level_surface;
+    if surface_delta_pa == Pa::new(0.0) {
+        return;
+    }
+
+    let depth_delta_pa = m_values[1].depth.to_pa() - m_values[0].depth.to_pa();
+    if depth_delta_pa == Pa::new(0.0) {
+        return;
+    }
+
+    for tissue_idx in 0..NUM_TISSUES {
+        let saturation_delta_per_pa = (m_values[1].max_saturation[tissue_idx].to_pa()
+            - m_values[0].max_saturation[tissue_idx].to_pa())
+            / depth_delta_pa;
+        let saturation_shift: P = (surface_delta_pa * saturation_delta_per_pa).into();
+        for row in m_values.iter_mut() {
+            row.max_saturation[tissue_idx] = row.max_saturation[tissue_idx] + saturation_shift;
+        }
     }
-    let a_n2 = BUEHL_TISSUES[tissue_idx].n2.a.to_pa();
-    let a_he = BUEHL_TISSUES[tissue_idx].he.a.to_pa();
-    let b_n2 = BUEHL_TISSUES[tissue_idx].n2.b;
-    let b_he = BUEHL_TISSUES[tissue_idx].he.b;
-
-    let n2_frac = p_n2 / total;
-    let he_frac = p_he / total;
-    let a_mix = a_n2 * n2_frac + a_he * he_frac;
-    let b_mix = b_n2 * n2_frac + b_he * he_frac;
-    a_mix + stop_depth_pa * b_mix
 }
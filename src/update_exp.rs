@@ -1,27 +1,23 @@
-#[cfg(not(feature = "lin_exp"))]
 use core::{f32::consts::LN_2, time::Duration};
 
-#[cfg(not(feature = "lin_exp"))]
+#[allow(unused)]
 use num::Float;
 
-#[cfg(not(feature = "lin_exp"))]
 use crate::{
-    deco_algorithm::MValues,
-    depth_utils::get_depth_idx,
-    gas::{Gas, GasMix, HE_IDX, N2_IDX, TissuesLoading},
-    mptt_buehlmann::{BuehlmannTissue, TISSUES},
+    gas::{Gas, GasMix, InertGas, NUM_INERT_GASES, TissuesLoading},
+    mptt::TissueRow,
+    mptt_buehlmann::BuehlmannTissue,
     pressure_unit::{AbsPressure, Pa, Pressure, ambient_pressure_at_depth, msw},
+    setup::DINC,
     time_utils::max,
-    update_common::exp_pressure,
+    update::allowed_with_gf,
+    update_common::{exp_pressure, mixed_buehlmann_mvalue},
 };
 
-#[cfg(not(feature = "lin_exp"))]
-type BuehlmannLoading<P> = TissuesLoading<{ TISSUES.len() }, P>;
+const EXP_STOP_SCAN_STEP_MIN: f32 = 1.0;
+const EXP_STOP_MAX_MIN: f32 = 24.0 * 60.0;
+const EXP_STOP_BISECTION_ITER: usize = 20;
 
-#[cfg(not(feature = "lin_exp"))]
-type BuehlmannTissues = [BuehlmannTissue; TISSUES.len()];
-
-#[cfg(not(feature = "lin_exp"))]
 /**
 * Schreiner Update:
 * P(t) = P_{inspired} + (P_0 - P_{inspired}) * e^{-kt}
@@ -29,106 +25,260 @@ type BuehlmannTissues = [BuehlmannTissue; TISSUES.len()];
 * <=> dP = P(t) - P_0 = (P_{inspired} - P_0) - (P_{inspired} - P_0) * e^{-kt}
 * <=> dP = P(t) - P_0 = (P_{inspired} - P_0) * (1 - e^{-kt})
 */
-pub fn update_model_state_exp<P: Pressure + const AbsPressure>(
-    loading: &mut BuehlmannLoading<P>,
-    tissues: &BuehlmannTissues,
-    _m_values: &MValues<P>,
+pub fn update_model_state_exp<P: const AbsPressure, const NUM_TISSUES: usize>(
+    loading: &mut TissuesLoading<NUM_TISSUES, P>,
+    tissues: &[BuehlmannTissue; NUM_TISSUES],
     breathing_gas: &GasMix<f32>,
     current_depth: P,
     delta_time: &Duration,
 ) {
     let delta_time_minutes: f32 = delta_time.as_secs_f32() / 60.0;
-    let current_depth_pa: Pa = current_depth.to_pa();
-
-    for gas_idx in [N2_IDX, HE_IDX] {
-        let mut ks: [f32; TISSUES.len()] = [0.0; TISSUES.len()];
-        for tissue_idx in 0..TISSUES.len() {
-            ks[tissue_idx] = match gas_idx {
-                N2_IDX => LN_2 / tissues[tissue_idx].n2.half_time,
-                HE_IDX => LN_2 / tissues[tissue_idx].he.half_time,
-                _ => unreachable!(),
-            };
-        }
 
-        let (gas_loading, p_inspired): ([P; TISSUES.len()], P) = match gas_idx {
-            N2_IDX => (loading.n2, breathing_gas.pn2(current_depth)),
-            HE_IDX => (loading.he, breathing_gas.phe(current_depth)),
-            _ => unreachable!(),
-        };
-        for tissue_idx in 0..TISSUES.len() {
+    for gas in InertGas::ALL {
+        let ks: [f32; NUM_TISSUES] = tissues.map(|t| LN_2 / t.for_gas(gas).half_time);
+        let p_inspired = breathing_gas.p_inert(gas, current_depth);
+        let gas_loading = loading.tension_mut(gas);
+        for tissue_idx in 0..NUM_TISSUES {
             let p_tissue = gas_loading[tissue_idx];
             let k = ks[tissue_idx];
-            let p_new = exp_pressure(p_inspired, p_tissue, k, delta_time_minutes);
-            match gas_idx {
-                N2_IDX => loading.n2[tissue_idx] = p_new,
-                HE_IDX => loading.he[tissue_idx] = p_new,
-                _ => unreachable!(),
+            gas_loading[tissue_idx] = exp_pressure(p_inspired, p_tissue, k, delta_time_minutes);
+        }
+    }
+}
+
+/// Absolute and GF-adjusted mixed-gas Buehlmann M-value of `tissue_idx` at `current_depth`.
+pub fn tissue_mvalues_with_gf_exp<P: const AbsPressure, const NUM_TISSUES: usize>(
+    loading: &TissuesLoading<NUM_TISSUES, P>,
+    tissues: &[BuehlmannTissue; NUM_TISSUES],
+    current_depth: msw,
+    surface_pressure: P,
+    tissue_idx: usize,
+    gf: f32,
+) -> (P, P) {
+    let tensions = loading.tissue_tensions(tissue_idx).map(|p| p.to_pa());
+    let p_amb = ambient_pressure_at_depth(surface_pressure, current_depth).to_pa();
+    let absolute = mixed_buehlmann_mvalue(&tissues[tissue_idx], tensions, p_amb);
+    let gf_mvalue = allowed_with_gf(p_amb, absolute, gf);
+    (absolute.into(), gf_mvalue.into())
+}
+
+/// Deepest table depth at which the total inert tension of any tissue exceeds
+/// its mixed-gas (GF-adjusted) Buehlmann M-value.
+pub fn first_stop_depth_exp<P: const AbsPressure, const NUM_TISSUES: usize>(
+    loading: &TissuesLoading<NUM_TISSUES, P>,
+    tissues: &[BuehlmannTissue; NUM_TISSUES],
+    m_values: &[TissueRow<NUM_TISSUES, P>],
+    surface_pressure: P,
+    gf: f32,
+) -> Option<msw> {
+    for mvalues_at_depth in m_values.iter().rev() {
+        for i in 0..NUM_TISSUES {
+            let total = loading.total_inert(i).to_pa();
+            if total.to_f32() <= 0.0 {
+                // No inert present, skip
+                continue;
+            }
+            let (_absolute, allowed) = tissue_mvalues_with_gf_exp(
+                loading,
+                tissues,
+                mvalues_at_depth.depth,
+                surface_pressure,
+                i,
+                gf,
+            );
+            if total > allowed.to_pa() {
+                return Some(mvalues_at_depth.depth);
             }
         }
     }
+    None
+}
+
+/**
+* Inert gas tension of `tissue` minus its GF-adjusted mixed M-value at
+* `target_ambient`, after `t` minutes breathing `p_inspired` (per inert gas).
+* Each gas follows its own exponential, so the sum is not a single exponential.
+*/
+fn excess_tension_after(
+    tensions: &[Pa; NUM_INERT_GASES],
+    p_inspired: &[Pa; NUM_INERT_GASES],
+    tissue: &BuehlmannTissue,
+    target_ambient: Pa,
+    gf: f32,
+    t: f32,
+) -> f32 {
+    let after = InertGas::ALL.map(|gas| {
+        let k = LN_2 / tissue.for_gas(gas).half_time;
+        exp_pressure(p_inspired[gas.idx()], tensions[gas.idx()], k, t)
+    });
+    let total = after.iter().map(|p| p.to_f32()).sum::<f32>();
+    let m_value = mixed_buehlmann_mvalue(tissue, after, target_ambient);
+    total - allowed_with_gf(target_ambient, m_value, gf).to_f32()
+}
+
+/**
+* Minutes until `excess` first drops to zero: scanned in EXP_STOP_SCAN_STEP_MIN
+* steps to bracket the first crossing, then bisected. `None` if the tissue does
+* not clear within EXP_STOP_MAX_MIN (the inspired gas alone violates the M-value).
+*/
+fn first_clearing_time(excess: impl Fn(f32) -> f32) -> Option<f32> {
+    if excess(0.0) <= 0.0 {
+        return Some(0.0);
+    }
+    let mut high = 0.0;
+    loop {
+        high += EXP_STOP_SCAN_STEP_MIN;
+        if high >= EXP_STOP_MAX_MIN {
+            return None;
+        }
+        if excess(high) <= 0.0 {
+            break;
+        }
+    }
+    let mut low = high - EXP_STOP_SCAN_STEP_MIN;
+    for _ in 0..EXP_STOP_BISECTION_ITER {
+        let mid = 0.5 * (low + high);
+        if excess(mid) > 0.0 {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    Some(high)
 }
 
-#[cfg(not(feature = "lin_exp"))]
 /**
-* P_{tissue}(t) = P_{inspired} + (P_{tissue,gas} - P_{inspired}) * e^{-kt}
-* t_{tissue} = -1/k * ln((M - P_{inspired}) / (P_{tissue,gas} - P_{inspired}))
+* Time at `stop_depth` until every tissue tolerates the next shallower stop
+* (the surface after `last_deco_stop`). Every inert gas is advanced with its own
+* half-time and checked against the mixed a/b M-value, so opposite gradients
+* after a gas switch are handled exactly. `None` if some tissue never clears.
 */
-pub fn compute_stop_time_exp<P: const AbsPressure>(
-    loading: &BuehlmannLoading<P>,
-    tissues: &BuehlmannTissues,
+#[allow(clippy::too_many_arguments)]
+pub fn compute_stop_time_exp<P: const AbsPressure, const NUM_TISSUES: usize>(
+    loading: &TissuesLoading<NUM_TISSUES, P>,
+    tissues: &[BuehlmannTissue; NUM_TISSUES],
     breathing_gas: &GasMix<f32>,
-    m_values: &MValues<P>,
     stop_depth: msw,
     gf: f32,
     surface_pressure: P,
     last_deco_stop: msw,
-) -> Duration {
-    let stop_depth_idx = get_depth_idx(stop_depth);
+) -> Option<Duration> {
     let is_last_stop = stop_depth.to_msw().to_f32() <= last_deco_stop.to_msw().to_f32();
+    let next_depth = if is_last_stop {
+        msw::new(0.0)
+    } else {
+        msw::new((stop_depth.to_msw().to_f32() - DINC.to_f32()).max(0.0))
+    };
+    let stop_ambient = ambient_pressure_at_depth(surface_pressure, stop_depth).to_pa();
+    let next_ambient = ambient_pressure_at_depth(surface_pressure, next_depth).to_pa();
+    let p_inspired = InertGas::ALL.map(|gas| breathing_gas.p_inert(gas, stop_ambient));
 
     let mut t_stop_mins: f32 = 0.0;
-    // Use total inert pressure (N2 + He) per tissue and total inspired inert.
-    let stop_ambient = ambient_pressure_at_depth(surface_pressure, stop_depth);
-    let inspired_inert = stop_ambient * (breathing_gas.fn2() + breathing_gas.fhe());
-    for tissue_idx in 0..TISSUES.len() {
-        let p_n2 = loading.n2[tissue_idx].to_pa();
-        let p_he = loading.he[tissue_idx].to_pa();
-        let p_tissue = p_n2 + p_he;
-
-        // Prefer mixed Buehlmann a/b when available; otherwise use table entry
-        let m_value = if is_last_stop {
-            crate::update_common::mixed_buehlmann_mvalue(
-                tissue_idx,
-                p_n2,
-                p_he,
-                surface_pressure,
-            )
-        } else if p_tissue.to_f32() > 0.0 {
-            crate::update_common::mixed_buehlmann_mvalue(tissue_idx, p_n2, p_he, stop_ambient)
-        } else {
-            m_values[stop_depth_idx].max_saturation[tissue_idx].to_pa()
-        };
+    for (tissue_idx, tissue) in tissues.iter().enumerate() {
+        let tensions = loading.tissue_tensions(tissue_idx).map(|p| p.to_pa());
+        let t_tissue = first_clearing_time(|t| {
+            excess_tension_after(&tensions, &p_inspired, tissue, next_ambient, gf, t)
+        })?;
+        t_stop_mins = max(t_stop_mins, t_tissue);
+    }
 
-        // Apply gradient factor to derive target stopping M-value
-        let p_amb = stop_ambient;
-        let target_m = super::update::allowed_with_gf(p_amb, m_value, gf);
+    Some(Duration::from_secs_f32(t_stop_mins * 60.0))
+}
 
-        if target_m.to_f32() - p_tissue.to_f32() >= 0.0 {
-            // Tissue is safe
-            continue;
-        }
-        // Compute desaturation rate k as weighted per-gas rate using current
-        // tissue partial pressures as weights.
-        let k_n2 = LN_2 / tissues[tissue_idx].n2.half_time;
-        let k_he = LN_2 / tissues[tissue_idx].he.half_time;
-        let k = if p_tissue.to_f32() > 0.0 {
-            (k_n2 * p_n2.to_f32() + k_he * p_he.to_f32()) / p_tissue.to_f32()
-        } else {
-            k_n2
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        gas::{AIR, NX50, TMX10_80, TMX18_45},
+        mptt_buehlmann::BuehlmannCoefficients,
+    };
+    use std::println;
+
+    #[test]
+    fn stop_time_clears_next_stop_after_gas_switch() {
+        let tissues = BuehlmannCoefficients::ZhL16C.tissues();
+        let surface = msw::new(0.0).to_pa();
+        let mut loading = TissuesLoading::new(surface, &crate::gas::AIR);
+        update_model_state_exp(
+            &mut loading,
+            &tissues,
+            &TMX18_45,
+            msw::new(60.0).to_pa(),
+            &Duration::from_secs(30 * 60),
+        );
+        // Helium leaves faster than nitrogen enters after the switch to EAN50.
+        let stop = msw::new(21.0);
+        let gf = 0.6;
+        let duration =
+            compute_stop_time_exp(&loading, &tissues, &NX50, stop, gf, surface, msw::new(3.0))
+                .expect("EAN50 clears the 21 m stop");
+        println!("21 m stop on EAN50 after 60 m / 30 min trimix: {duration:?}");
+        assert!(!duration.is_zero());
+
+        let next_ambient = msw::new(18.0).to_pa();
+        let exceeds_after = |t: Duration| {
+            let mut after = loading.clone();
+            update_model_state_exp(&mut after, &tissues, &NX50, stop.to_pa(), &t);
+            (0..tissues.len()).any(|i| {
+                let tensions = after.tissue_tensions(i);
+                let m = mixed_buehlmann_mvalue(&tissues[i], tensions, next_ambient);
+                after.total_inert(i) > allowed_with_gf(next_ambient, m, gf) + Pa::new(1.0)
+            })
         };
-        let t_gas_tissue = -((target_m - inspired_inert) / (p_tissue - inspired_inert)).ln() / k;
-        t_stop_mins = max(t_stop_mins, t_gas_tissue);
+        assert!(!exceeds_after(duration));
+        assert!(exceeds_after(
+            duration.saturating_sub(Duration::from_secs(5))
+        ));
     }
 
-    Duration::from_secs_f32(t_stop_mins * 60.0)
+    #[test]
+    fn stop_that_never_clears_has_no_stop_time() {
+        let tissues = BuehlmannCoefficients::ZhL16C.tissues();
+        let surface = msw::new(0.0).to_pa();
+        let stop = msw::new(3.0);
+        let mut loading = TissuesLoading::new(surface, &AIR);
+        update_model_state_exp(
+            &mut loading,
+            &tissues,
+            &TMX10_80,
+            ambient_pressure_at_depth(surface, stop).to_pa(),
+            &Duration::from_secs(48 * 60 * 60),
+        );
+        // 90 % inert at 3 m is above what GF 10 allows at the surface
+        let duration =
+            compute_stop_time_exp(&loading, &tissues, &TMX10_80, stop, 0.1, surface, stop);
+        assert_eq!(duration, None);
+    }
+
+    #[test]
+    fn hydrogen_and_argon_use_their_own_half_times() {
+        let tissues = BuehlmannCoefficients::ZhL16C.tissues();
+        let surface = msw::new(0.0).to_pa();
+        let load = |gas: &GasMix<f32>, depth: f32, minutes: u64| {
+            let mut loading = TissuesLoading::new(surface, &AIR);
+            update_model_state_exp(
+                &mut loading,
+                &tissues,
+                gas,
+                msw::new(depth).to_pa(),
+                &Duration::from_secs(minutes * 60),
+            );
+            loading
+        };
+
+        // Half the He of 10/80 replaced by H2, which diffuses faster still
+        let hydreliox = GasMix::with_inert(0.10, 0.40, 0.40, 0.0, 0.0).expect("sums to 90 %");
+        let with_h2 = load(&hydreliox, 100.0, 5);
+        let with_he = load(&TMX10_80, 100.0, 5);
+        assert!(with_h2.tension(InertGas::H2)[0].to_f32() > 0.0);
+        assert!(with_h2.total_inert(0) > with_he.total_inert(0));
+
+        // Argon is slower than the N2 it replaces
+        let argox = GasMix::with_inert(0.21, 0.0, 0.0, 0.0, 0.79).expect("sums to 100 %");
+        let with_ar = load(&argox, 30.0, 10);
+        let with_n2 = load(&AIR, 30.0, 10);
+        println!("Ar {:?}, N2 {:?}", with_ar.total_inert(0), with_n2.total_inert(0));
+        assert!(with_ar.total_inert(0) < with_n2.total_inert(0));
+        assert!(with_ar.n2()[0].to_f32() < with_n2.n2()[0].to_f32());
+    }
 }
@@ -1,104 +1,106 @@
-use core::time::Duration;
+use core::{f32::consts::LN_2, time::Duration};
 #[allow(unused)]
 use num::Float;
 
-#[cfg(feature = "lin_exp")]
-use crate::deco_algorithm::MValues;
 use crate::{
-    depth_utils::get_depth_idx,
-    gas::{Gas, GasMix, HE_IDX, N2_IDX, TissuesLoading},
-    mptt::Tissue,
+    depth_utils::get_grid_depth_idx,
+    gas::{Gas, GasMix, InertGas, NUM_INERT_GASES, TissuesLoading},
+    mptt::{ThalmannTissue, Tissue, TissueRow},
+    mptt_thalmann::ThalmannSettings,
     pressure_unit::{AbsPressure, Pa, Pressure, ambient_pressure_at_depth, msw},
     time_utils::max,
     update_common::exp_pressure,
 };
 
-#[cfg(feature = "lin_exp")]
-const THALMANN_FSW_TO_PA: f32 = 3_064.305_931_38;
-#[cfg(feature = "lin_exp")]
-const THALMANN_PVO2: Pa = Pa::new(2.0 * THALMANN_FSW_TO_PA);
-#[cfg(feature = "lin_exp")]
-const THALMANN_PVCO2: Pa = Pa::new(2.3 * THALMANN_FSW_TO_PA);
-#[cfg(feature = "lin_exp")]
-const THALMANN_PH2O: Pa = Pa::new(0.0);
-#[cfg(feature = "lin_exp")]
-const THALMANN_PBOVP: Pa = Pa::new(0.0);
-#[cfg(feature = "lin_exp")]
 const LIN_EXP_STOP_EPSILON_PA: Pa = Pa::new(10.0);
-
-#[cfg(feature = "lin_exp")]
-fn thalmann_crossover_pressure<P: const AbsPressure>(ambient_pressure: P) -> P {
+const MIXED_STOP_SEARCH_STEP_MIN: f32 = 1.0;
+const MIXED_STOP_MAX_MIN: f32 = 24.0 * 60.0;
+const MIXED_STOP_BISECTION_ITER: usize = 16;
+
+fn thalmann_crossover_pressure<P: const AbsPressure>(
+    ambient_pressure: P,
+    settings: &ThalmannSettings,
+) -> P {
     // Thalmann defines the linear->exponential crossover at PVSAT + PBOVP.
     // PVSAT = PAMB - (PVO2 + PVCO2 + PH2O), and PBOVP is the configurable
     // gas-phase overpressure threshold. The paper's default examples use 0.
-    ambient_pressure - THALMANN_PVO2.into() - THALMANN_PVCO2.into() - THALMANN_PH2O.into()
-        + THALMANN_PBOVP.into()
+    ambient_pressure - settings.pvo2.into() - settings.pvco2.into() - settings.ph2o.into()
+        + settings.pbovp.into()
 }
 
-#[cfg(feature = "lin_exp")]
-fn thalmann_mvalue_idx(stop_depth: msw) -> usize {
-    get_depth_idx(stop_depth)
+fn thalmann_mvalue_idx(stop_depth: msw, stop_increment: msw) -> usize {
+    get_grid_depth_idx(stop_depth, stop_increment)
         .checked_sub(1)
-        .expect("Thalmann stop depths start at 3m")
+        .expect("Thalmann stop depths start at the first stop increment")
+}
+
+/**
+* Tension of one inert gas after `t` minutes: exponential (KSAT) while
+* ongassing, linear (KDSAT) above the crossover pressure while offgassing and
+* exponential below it.
+*/
+fn lin_exp_pressure<P: const AbsPressure>(
+    p_old: P,
+    p_inspired: P,
+    crossover_pressure: P,
+    tissue: &Tissue,
+    t: f32,
+) -> P {
+    let k_sat = LN_2 / tissue.half_time;
+    if p_old <= p_inspired {
+        // Ongassing pure exponential uses KSAT
+        return exp_pressure(p_inspired, p_old, k_sat, t);
+    }
+
+    // For desaturation computations use KDSAT.
+    // When the tissue tension is above the Thalmann crossover pressure,
+    // use linear washout first. Once the tissue tension is no longer too
+    // large, the remaining tail is exponential.
+    let k = k_sat * tissue.sdr;
+
+    if crossover_pressure <= p_inspired || p_old <= crossover_pressure {
+        // Exponential only: the tissue is already below the crossover pressure.
+        exp_pressure(p_inspired, p_old, k, t)
+    } else {
+        // Linear first until the crossover pressure is reached, at the rate
+        // of the gradient between the crossover and the inspired pressure.
+        let linear_rate = (crossover_pressure - p_inspired) * k;
+        let t_linear = (p_old - crossover_pressure) / linear_rate;
+
+        if t <= t_linear {
+            p_old - linear_rate * t
+        } else {
+            let t_exp = t - t_linear;
+            exp_pressure(p_inspired, crossover_pressure, k, t_exp)
+        }
+    }
 }
 
-#[cfg(feature = "lin_exp")]
 pub fn update_model_state_lin_exp<P: const AbsPressure, const NUM_TISSUES: usize>(
     loading: &mut TissuesLoading<NUM_TISSUES, P>,
-    tissues: &[Tissue; NUM_TISSUES],
-    _m_values: &MValues<P>,
+    tissues: &[ThalmannTissue; NUM_TISSUES],
+    settings: &ThalmannSettings,
     breathing_gas: &GasMix<f32>,
     current_depth: P,
     delta_time: &Duration,
 ) {
     let delta_time_minutes: f32 = delta_time.as_secs_f32() / 60.0;
 
-    let p_inspired_n2 = breathing_gas.pn2(current_depth);
-    let p_inspired_he = breathing_gas.phe(current_depth);
-    let crossover_pressure = thalmann_crossover_pressure(current_depth);
+    let crossover_pressure = thalmann_crossover_pressure(current_depth, settings);
 
-    // KSAT and KDSAT per tissue (KDSAT = KSAT * SDR)
-    let (k_values_sat, k_values_desat) = crate::update_common::ks_arrays(tissues);
-
-    for (gas_idx, p_inspired) in [(N2_IDX, p_inspired_n2), (HE_IDX, p_inspired_he)] {
-        let gas_loading = match gas_idx {
-            N2_IDX => &mut loading.n2,
-            HE_IDX => &mut loading.he,
-            _ => unreachable!(),
-        };
+    for gas in InertGas::ALL {
+        let p_inspired = breathing_gas.p_inert(gas, current_depth);
+        let gas_loading = loading.tension_mut(gas);
+        let kinetics = tissues.map(|t| t.for_gas(gas));
 
         for tissue_idx in 0..NUM_TISSUES {
-            let p_old = gas_loading[tissue_idx];
-            let k_sat: f32 = k_values_sat[tissue_idx];
-            let k_desat: f32 = k_values_desat[tissue_idx];
-            if p_old <= p_inspired {
-                // Ongassing pure exponential uses KSAT
-                gas_loading[tissue_idx] =
-                    exp_pressure(p_inspired, p_old, k_sat, delta_time_minutes);
-                continue;
-            }
-
-            // For desaturation computations use KDSAT.
-            // When the tissue tension is above the Thalmann crossover pressure,
-            // use linear washout first. Once the tissue tension is no longer too
-            // large, the remaining tail is exponential.
-            let k = k_desat;
-
-            let p_new = if crossover_pressure <= p_inspired || p_old <= crossover_pressure {
-                // Exponential only: the tissue is already below the crossover pressure.
-                exp_pressure(p_inspired, p_old, k, delta_time_minutes)
-            } else {
-                // Linear first until the crossover pressure is reached.
-                let linear_rate = (p_old - p_inspired) * k;
-                let t_linear = (p_old - crossover_pressure) / linear_rate;
-
-                if delta_time_minutes <= t_linear {
-                    p_old - linear_rate * delta_time_minutes
-                } else {
-                    let t_exp = delta_time_minutes - t_linear;
-                    exp_pressure(p_inspired, crossover_pressure, k, t_exp)
-                }
-            };
+            let p_new = lin_exp_pressure(
+                gas_loading[tissue_idx],
+                p_inspired,
+                crossover_pressure,
+                &kinetics[tissue_idx],
+                delta_time_minutes,
+            );
             if p_new < Pa::new(0.0).into() {
                 panic!("Illegal p_new: {:?}", p_new);
             }
@@ -107,44 +109,106 @@ pub fn update_model_state_lin_exp<P: const AbsPressure, const NUM_TISSUES: usize
     }
 }
 
-#[cfg(feature = "lin_exp")]
+/**
+* Minutes until the combined inert gas tension of a compartment with distinct
+* kinetics per gas drops to `target`. The gases are advanced in
+* MIXED_STOP_SEARCH_STEP_MIN steps until the target is met, then the last
+* step is bisected.
+*/
+fn mixed_stop_time_minutes<P: const AbsPressure>(
+    tensions: &[P; NUM_INERT_GASES],
+    p_inspired: &[P; NUM_INERT_GASES],
+    crossover_pressure: P,
+    tissue: &ThalmannTissue,
+    target: P,
+) -> f32 {
+    let total = |t: f32| {
+        InertGas::ALL.iter().fold(LIN_EXP_STOP_EPSILON_PA.into(), |sum: P, gas| {
+            let (p, p_insp) = (tensions[gas.idx()], p_inspired[gas.idx()]);
+            sum + lin_exp_pressure(p, p_insp, crossover_pressure, &tissue.for_gas(*gas), t)
+        })
+    };
+    let mut high = 0.0;
+    while total(high) > target {
+        if high >= MIXED_STOP_MAX_MIN {
+            return MIXED_STOP_MAX_MIN;
+        }
+        high += MIXED_STOP_SEARCH_STEP_MIN;
+    }
+    if high == 0.0 {
+        return 0.0;
+    }
+    let mut low = high - MIXED_STOP_SEARCH_STEP_MIN;
+    for _ in 0..MIXED_STOP_BISECTION_ITER {
+        let mid = 0.5 * (low + high);
+        if total(mid) > target {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    high
+}
+
+/**
+* Closed form of `mixed_stop_time_minutes` for a compartment holding a single
+* gas with desaturation rate `k` (KDSAT): minutes until `p_tissue` drops to
+* `target`.
+*/
+fn single_gas_stop_time_minutes<P: const AbsPressure>(
+    p_tissue: P,
+    p_inspired: P,
+    crossover_pressure: P,
+    k: f32,
+    target: P,
+) -> f32 {
+    if p_tissue <= crossover_pressure {
+        // Below the crossover pressure: exponential washout to the stop limit.
+        -((target - p_inspired) / (p_tissue - p_inspired)).ln() / k
+    } else if target >= crossover_pressure {
+        // The ceiling is still in the linear region, so stop once the tissue
+        // reaches the current stop limit.
+        (p_tissue - target) / ((crossover_pressure - p_inspired) * k)
+    } else {
+        // Above the crossover pressure and the stop limit lies below it:
+        // linear first, then exponential to the stop limit.
+        let linear_rate = (crossover_pressure - p_inspired) * k;
+        let t_linear = (p_tissue - crossover_pressure) / linear_rate;
+        let t_exp = -((target - p_inspired) / (crossover_pressure - p_inspired)).ln() / k;
+        t_linear + t_exp
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn compute_stop_time_lin_exp<const NUM_TISSUES: usize, P: const AbsPressure>(
     loading: &TissuesLoading<NUM_TISSUES, P>,
-    tissues: &[Tissue; NUM_TISSUES],
+    tissues: &[ThalmannTissue; NUM_TISSUES],
+    settings: &ThalmannSettings,
     breathing_gas: &GasMix<f32>,
-    m_values: &MValues<P>,
+    m_values: &[TissueRow<NUM_TISSUES, P>],
+    stop_increment: msw,
     stop_depth: msw,
     gf: f32,
     surface_pressure: P,
     last_deco_stop: msw,
 ) -> Duration {
-    let stop_idx = thalmann_mvalue_idx(stop_depth);
+    let stop_idx = thalmann_mvalue_idx(stop_depth, stop_increment);
     let is_last_stop = stop_depth.to_msw().to_f32() <= last_deco_stop.to_msw().to_f32();
     let mut t_stop_mins = 0.0;
     let stop_ambient = ambient_pressure_at_depth(surface_pressure, stop_depth);
-    let crossover_pressure: P = thalmann_crossover_pressure(stop_ambient);
-
-    // KDSAT values for desaturation (KSAT * SDR)
-    let (_k_values_sat, k_values_desat) = crate::update_common::ks_arrays(tissues);
-
-    // Use total inert pressure (N2 + He) per tissue and total inspired inert.
-    let inspired_inert_pa = stop_ambient * (breathing_gas.fn2() + breathing_gas.fhe());
-    let p_inspired: P = inspired_inert_pa.into();
-    let surface_targets: [P; NUM_TISSUES] = core::array::from_fn(|tissue_idx| {
-        let shallow = m_values[0].max_saturation[tissue_idx];
-        let next = m_values[1].max_saturation[tissue_idx];
-        shallow - (next - shallow)
-    });
-
-    for tissue_idx in 0..NUM_TISSUES {
-        let p_tissue =
-            loading.n2[tissue_idx] + loading.he[tissue_idx] + LIN_EXP_STOP_EPSILON_PA.into();
-        // Use desaturation rate (KDSAT = KSAT * SDR) when computing stop times
-        let k = k_values_desat[tissue_idx];
-        let m_value = if is_last_stop || stop_idx == 0 {
-            surface_targets[tissue_idx]
+    let crossover_pressure: P = thalmann_crossover_pressure(stop_ambient, settings);
+
+    // Use total inert pressure per tissue and total inspired inert.
+    let p_inspired_per_gas = InertGas::ALL.map(|gas| breathing_gas.p_inert(gas, stop_ambient));
+    let p_inspired: P = stop_ambient * (1.0 - breathing_gas.fo2());
+
+    for (tissue_idx, tissue) in tissues.iter().enumerate() {
+        let tensions = loading.tissue_tensions(tissue_idx);
+        let p_tissue = loading.total_inert(tissue_idx) + LIN_EXP_STOP_EPSILON_PA.into();
+        let m_value = if is_last_stop {
+            m_values[0].max_saturation[tissue_idx]
         } else {
-            m_values[stop_idx - 1].max_saturation[tissue_idx]
+            m_values[stop_idx].max_saturation[tissue_idx]
         };
         let p_amb: P = stop_ambient;
         let target_m = super::update::allowed_with_gf(p_amb, m_value, gf);
@@ -155,20 +219,25 @@ pub fn compute_stop_time_lin_exp<const NUM_TISSUES: usize, P: const AbsPressure>
             continue;
         }
 
-        let t_tissue = if p_tissue <= crossover_pressure {
-            // Below the crossover pressure: exponential washout to the stop limit.
-            -((target_m - p_inspired) / (p_tissue - p_inspired)).ln() / k
-        } else if target_m >= crossover_pressure {
-            // The ceiling is still in the linear region, so stop once the tissue
-            // reaches the current stop limit.
-            (p_tissue - target_m) / ((p_tissue - p_inspired) * k)
-        } else {
-            // Above the crossover pressure and the stop limit lies below it:
-            // linear first, then exponential to the stop limit.
-            let linear_rate = (p_tissue - p_inspired) * k;
-            let t_linear = (p_tissue - crossover_pressure) / linear_rate;
-            let t_exp = -((target_m - p_inspired) / (crossover_pressure - p_inspired)).ln() / k;
-            t_linear + t_exp
+        // The closed form holds while one gas carries the compartment. Traces
+        // below LIN_EXP_STOP_EPSILON_PA do not move the stop time.
+        let mut present = InertGas::ALL.iter().filter(|gas| {
+            let eps = LIN_EXP_STOP_EPSILON_PA.into();
+            tensions[gas.idx()] > eps || p_inspired_per_gas[gas.idx()] > eps
+        });
+        let t_tissue = match (present.next(), present.next()) {
+            (Some(gas), None) => {
+                let kinetics = tissue.for_gas(*gas);
+                let k = LN_2 / kinetics.half_time * kinetics.sdr;
+                single_gas_stop_time_minutes(p_tissue, p_inspired, crossover_pressure, k, target_m)
+            }
+            _ => mixed_stop_time_minutes(
+                &tensions,
+                &p_inspired_per_gas,
+                crossover_pressure,
+                tissue,
+                target_m,
+            ),
         };
 
         t_stop_mins = max(t_stop_mins, t_tissue);
@@ -180,15 +249,21 @@ pub fn compute_stop_time_lin_exp<const NUM_TISSUES: usize, P: const AbsPressure>
 mod tests {
     use super::*;
     use crate::{
-        deco_algorithm::{MVALUES, TISSUES, update_model_state},
+        deco_model::{DecoModel, THALMANN_XVAL_HE9_040},
         dive::{DiveMeasurement, DiveProfile},
         gas::{AIR, NX50, NX100, TMX18_45, TissuesLoading},
         loadings_from_dive_profile,
+        mptt_thalmann::THALMANN_DEFAULT_SETTINGS,
         pressure_unit::{Pa, Pressure, msw},
+        setup::DINC,
         update::first_stop_depth_with_gf,
     };
     use core::f32::consts::LN_2;
     use std::println;
+
+    const TISSUES: [ThalmannTissue; 5] = THALMANN_XVAL_HE9_040.tissues;
+    const MVALUES: [TissueRow<5, Pa>; 64] = THALMANN_XVAL_HE9_040.m_values;
+
     #[test]
     fn compute_first_stops_from_realistic_profile() {
         let gases = [TMX18_45, NX50, NX100];
@@ -244,7 +319,7 @@ mod tests {
         };
 
         let mut current_loading =
-            loadings_from_dive_profile(&TISSUES, &profile, &MVALUES, msw::new(0.0).to_pa());
+            loadings_from_dive_profile(&THALMANN_XVAL_HE9_040, &profile, msw::new(0.0).to_pa());
 
         for _step in 0..3 {
             let Some(stop_depth) = first_stop_depth_with_gf(
@@ -258,8 +333,10 @@ mod tests {
             let stop_duration = compute_stop_time_lin_exp(
                 &current_loading,
                 &TISSUES,
+                &THALMANN_DEFAULT_SETTINGS,
                 &gases[0],
                 &MVALUES,
+                DINC,
                 stop_depth,
                 1.0,
                 msw::new(0.0).to_pa(),
@@ -267,12 +344,10 @@ mod tests {
             );
             println!("Stop {:?}: {:?}", stop_depth, stop_duration);
             assert!(!stop_duration.is_zero());
-            update_model_state(
+            THALMANN_XVAL_HE9_040.update_model_state(
                 &mut current_loading,
-                &TISSUES,
-                &MVALUES,
                 &gases[0],
-                stop_depth.to_pa().into(),
+                stop_depth.to_pa(),
                 &stop_duration,
             );
         }
@@ -280,49 +355,56 @@ mod tests {
 
     #[test]
     fn update_model_state_lin_exp_uses_exponential_update_while_ongassing() {
-        let mut loading: TissuesLoading<{ TISSUES.len() }, Pa> = TissuesLoading {
-            n2: TISSUES.map(|_| msw::new(0.0).to_pa()),
-            he: TISSUES.map(|_| msw::new(0.0).to_pa()),
-        };
+        let mut loading: TissuesLoading<{ TISSUES.len() }, Pa> = TissuesLoading::from_n2_he(
+            TISSUES.map(|_| msw::new(0.0).to_pa()),
+            TISSUES.map(|_| msw::new(0.0).to_pa()),
+        );
 
         update_model_state_lin_exp(
             &mut loading,
             &TISSUES,
-            &MVALUES,
+            &THALMANN_DEFAULT_SETTINGS,
             &TMX18_45,
             msw::new(30.0).to_pa(),
             &Duration::from_secs(60),
         );
 
-        assert!(loading.n2.iter().all(|value| value.to_f32().is_finite()));
-        assert!(loading.he.iter().all(|value| value.to_f32().is_finite()));
-        assert!(loading.n2.iter().all(|value| value.to_f32() > 0.0));
-        assert!(loading.he.iter().all(|value| value.to_f32() > 0.0));
+        assert!(loading.n2().iter().all(|value| value.to_f32().is_finite()));
+        assert!(loading.he().iter().all(|value| value.to_f32().is_finite()));
+        assert!(loading.n2().iter().all(|value| value.to_f32() > 0.0));
+        assert!(loading.he().iter().all(|value| value.to_f32() > 0.0));
     }
 
     #[test]
     fn update_model_state_lin_exp_uses_kdsat_for_desaturation_branch() {
-        let mut loading: TissuesLoading<{ TISSUES.len() }, Pa> = TissuesLoading {
-            n2: TISSUES.map(|_| msw::new(0.0).to_pa()),
-            he: TISSUES.map(|_| msw::new(0.0).to_pa()),
-        };
+        let mut loading: TissuesLoading<{ TISSUES.len() }, Pa> = TissuesLoading::from_n2_he(
+            TISSUES.map(|_| msw::new(0.0).to_pa()),
+            TISSUES.map(|_| msw::new(0.0).to_pa()),
+        );
 
         let depth = msw::new(10.0).to_pa();
         let tissue_idx = 2; // Thalmann compartment with half-time=20, SDR=0.67
         let p_inspired = AIR.pn2(depth);
-        let crossover_pressure = thalmann_crossover_pressure(depth);
+        let crossover_pressure = thalmann_crossover_pressure(depth, &THALMANN_DEFAULT_SETTINGS);
 
         // Force the linear-first desaturation branch:
         // p_old > crossover_pressure > p_inspired.
         let p_old = crossover_pressure + msw::new(5.0).to_pa();
-        loading.n2[tissue_idx] = p_old;
+        loading.tension_mut(InertGas::N2)[tissue_idx] = p_old;
 
         let dt = Duration::from_secs(60);
-        update_model_state_lin_exp(&mut loading, &TISSUES, &MVALUES, &AIR, depth, &dt);
+        update_model_state_lin_exp(
+            &mut loading,
+            &TISSUES,
+            &THALMANN_DEFAULT_SETTINGS,
+            &AIR,
+            depth,
+            &dt,
+        );
 
         let dt_min = 1.0;
-        let k_desat = (LN_2 / TISSUES[tissue_idx].half_time) * TISSUES[tissue_idx].sdr;
-        let linear_rate = (p_old - p_inspired) * k_desat;
+        let k_desat = (LN_2 / TISSUES[tissue_idx].n2.half_time) * TISSUES[tissue_idx].n2.sdr;
+        let linear_rate = (crossover_pressure - p_inspired) * k_desat;
         let t_linear = (p_old - crossover_pressure) / linear_rate;
         let expected = if dt_min <= t_linear {
             p_old - linear_rate * dt_min
@@ -330,30 +412,72 @@ mod tests {
             let t_exp = dt_min - t_linear;
             exp_pressure(p_inspired, crossover_pressure, k_desat, t_exp)
         };
-        let actual = loading.n2[tissue_idx];
+        let actual = loading.n2()[tissue_idx];
         assert!((actual.to_f32() - expected.to_f32()).abs() < 1e-3);
     }
 
+    #[test]
+    fn single_gas_stop_time_matches_mixed_stop_time() {
+        let depth = msw::new(9.0).to_pa();
+        let crossover_pressure = thalmann_crossover_pressure(depth, &THALMANN_DEFAULT_SETTINGS);
+        let mut p_inspired = [Pa::new(0.0); NUM_INERT_GASES];
+        let heliox = GasMix::new(0.21, 0.79).expect("heliox");
+        p_inspired[InertGas::He.idx()] = heliox.p_inert(InertGas::He, depth);
+        let mut tensions = [Pa::new(0.0); NUM_INERT_GASES];
+        tensions[InertGas::He.idx()] = Pa::new(2.3E5);
+        let p_tissue = tensions[InertGas::He.idx()] + LIN_EXP_STOP_EPSILON_PA;
+
+        for tissue in TISSUES {
+            let he = tissue.for_gas(InertGas::He);
+            let k = LN_2 / he.half_time * he.sdr;
+            // Stop limits below and above the crossover pressure.
+            for target in [crossover_pressure - Pa::new(2E4), crossover_pressure + Pa::new(2E4)] {
+                let single = single_gas_stop_time_minutes(
+                    p_tissue,
+                    p_inspired[InertGas::He.idx()],
+                    crossover_pressure,
+                    k,
+                    target,
+                );
+                let mixed = mixed_stop_time_minutes(
+                    &tensions,
+                    &p_inspired,
+                    crossover_pressure,
+                    &tissue,
+                    target,
+                );
+                assert!((single - mixed).abs() < 1E-3 * single, "{single} vs {mixed} min");
+            }
+        }
+    }
+
     #[test]
     fn update_model_state_lin_exp_uses_ksat_for_ongassing() {
-        let mut loading: TissuesLoading<{ TISSUES.len() }, Pa> = TissuesLoading {
-            n2: TISSUES.map(|_| msw::new(0.0).to_pa()),
-            he: TISSUES.map(|_| msw::new(0.0).to_pa()),
-        };
+        let mut loading: TissuesLoading<{ TISSUES.len() }, Pa> = TissuesLoading::from_n2_he(
+            TISSUES.map(|_| msw::new(0.0).to_pa()),
+            TISSUES.map(|_| msw::new(0.0).to_pa()),
+        );
 
         let depth = msw::new(30.0).to_pa();
         let tissue_idx = 2; // half-time=20, SDR=0.67
         let p_inspired = AIR.pn2(depth);
         let p_old = msw::new(0.0).to_pa();
-        loading.n2[tissue_idx] = p_old;
+        loading.tension_mut(InertGas::N2)[tissue_idx] = p_old;
 
         let dt = Duration::from_secs(60);
-        update_model_state_lin_exp(&mut loading, &TISSUES, &MVALUES, &AIR, depth, &dt);
+        update_model_state_lin_exp(
+            &mut loading,
+            &TISSUES,
+            &THALMANN_DEFAULT_SETTINGS,
+            &AIR,
+            depth,
+            &dt,
+        );
 
         let dt_min = 1.0;
-        let k_sat = LN_2 / TISSUES[tissue_idx].half_time;
+        let k_sat = LN_2 / TISSUES[tissue_idx].n2.half_time;
         let expected = exp_pressure(p_inspired, p_old, k_sat, dt_min);
-        let actual = loading.n2[tissue_idx];
+        let actual = loading.n2()[tissue_idx];
         assert!((actual.to_f32() - expected.to_f32()).abs() < 1e-6);
     }
 }
@@ -0,0 +1,325 @@
+use core::{f32::consts::LN_2, time::Duration};
+
+#[allow(unused)]
+use num::Float;
+
+use crate::{
+    gas::{AIR, Gas, GasMix, InertGas, TissuesLoading},
+    mptt::{MValues, TissueRow},
+    mptt_buehlmann::BuehlmannTissue,
+    mptt_vpm::{
+        VpmSettings, adjusted_critical_radius, allowable_gradient, boyle_compensated_gradient,
+        critical_volume_gradient, regenerated_crushing_pressure,
+    },
+    pressure_unit::{AbsPressure, Pa, Pressure, ambient_pressure_at_depth, msw},
+    setup::{DINC, MSW_0_PA},
+    update_exp::update_model_state_exp,
+};
+
+/// VPM-B stop times are whole minutes, as in Baker's reference implementation.
+const VPM_STOP_TIME_INCREMENT: Duration = Duration::from_secs(60);
+const VPM_MAX_STOP_MINUTES: u64 = 24 * 60;
+/// Longest surface phase volume time, for tissues whose gradient never vanishes.
+const VPM_MAX_SURFACE_PHASE_MINUTES: f32 = 100_000.0;
+const VPM_SURFACE_PHASE_BISECTION_ITER: usize = 30;
+
+/**
+* Gradient allowed before decompression: the nucleus radius adjusted by the
+* crushing pressure of the dive sets the initial allowable supersaturation.
+*/
+pub const fn initial_gradients_vpm(settings: &VpmSettings, crushing_pressure: Pa) -> (Pa, Pa) {
+    let radius_n2 =
+        adjusted_critical_radius(settings.critical_radius_n2_um, crushing_pressure, settings);
+    let radius_he =
+        adjusted_critical_radius(settings.critical_radius_he_um, crushing_pressure, settings);
+    (
+        allowable_gradient(radius_n2, settings),
+        allowable_gradient(radius_he, settings),
+    )
+}
+
+/// N2 and He crushing pressures left after the nuclei regenerate for `regeneration_time`.
+fn regenerated_crushing_pressures_vpm(
+    settings: &VpmSettings,
+    crushing_pressure: Pa,
+    regeneration_time: Duration,
+) -> (Pa, Pa) {
+    let regenerated = |radius_um: f32| {
+        regenerated_crushing_pressure(radius_um, crushing_pressure, regeneration_time, settings)
+    };
+    (
+        regenerated(settings.critical_radius_n2_um),
+        regenerated(settings.critical_radius_he_um),
+    )
+}
+
+/// Initial gradients of nuclei that regenerated for `regeneration_time` since the crushing.
+pub fn regenerated_gradients_vpm(
+    settings: &VpmSettings,
+    crushing_pressure: Pa,
+    regeneration_time: Duration,
+) -> (Pa, Pa) {
+    let (crushing_n2, crushing_he) =
+        regenerated_crushing_pressures_vpm(settings, crushing_pressure, regeneration_time);
+    let gradient = |radius_um: f32, crushing: Pa| {
+        allowable_gradient(adjusted_critical_radius(radius_um, crushing, settings), settings)
+    };
+    (
+        gradient(settings.critical_radius_n2_um, crushing_n2),
+        gradient(settings.critical_radius_he_um, crushing_he),
+    )
+}
+
+/**
+* Gradients of the critical volume algorithm for bubbles that grow during
+* `phase_volume_time`, from the initial gradients of the regenerated nuclei.
+*/
+pub fn critical_volume_gradients_vpm(
+    settings: &VpmSettings,
+    crushing_pressure: Pa,
+    regeneration_time: Duration,
+    phase_volume_time: Duration,
+) -> (Pa, Pa) {
+    let (crushing_n2, crushing_he) =
+        regenerated_crushing_pressures_vpm(settings, crushing_pressure, regeneration_time);
+    let (initial_n2, initial_he) =
+        regenerated_gradients_vpm(settings, crushing_pressure, regeneration_time);
+    (
+        critical_volume_gradient(initial_n2, crushing_n2, phase_volume_time, settings),
+        critical_volume_gradient(initial_he, crushing_he, phase_volume_time, settings),
+    )
+}
+
+/**
+* Time each tissue keeps releasing gas after surfacing with `surfaced`: the
+* integral of its supersaturation over air at the surface until it vanishes,
+* divided by the supersaturation on arrival.
+*/
+pub fn surface_phase_volume_time_vpm<P: const AbsPressure, const NUM_TISSUES: usize>(
+    surfaced: &TissuesLoading<NUM_TISSUES, P>,
+    tissues: &[BuehlmannTissue; NUM_TISSUES],
+    surface_pressure: P,
+) -> [Duration; NUM_TISSUES] {
+    core::array::from_fn(|tissue_idx| {
+        let excess = InertGas::ALL.map(|gas| {
+            (surfaced.tension(gas)[tissue_idx] - AIR.p_inert(gas, surface_pressure))
+                .to_pa()
+                .to_f32()
+        });
+        let ks = InertGas::ALL.map(|gas| LN_2 / tissues[tissue_idx].for_gas(gas).half_time);
+        let gradient_after =
+            |t: f32| -> f32 { (0..excess.len()).map(|g| excess[g] * (-ks[g] * t).exp()).sum() };
+        let initial = gradient_after(0.0);
+        if initial <= 0.0 {
+            return Duration::ZERO;
+        }
+        // Bracket the time the supersaturation vanishes, then bisect it
+        let (mut low, mut high) = (0.0, 1.0);
+        while high < VPM_MAX_SURFACE_PHASE_MINUTES && gradient_after(high) > 0.0 {
+            low = high;
+            high *= 2.0;
+        }
+        let end = if high < VPM_MAX_SURFACE_PHASE_MINUTES {
+            for _ in 0..VPM_SURFACE_PHASE_BISECTION_ITER {
+                let mid = 0.5 * (low + high);
+                if gradient_after(mid) > 0.0 {
+                    low = mid;
+                } else {
+                    high = mid;
+                }
+            }
+            high
+        } else {
+            f32::INFINITY
+        };
+        let integral: f32 = (0..excess.len())
+            .map(|g| excess[g] / ks[g] * (1.0 - (-ks[g] * end).exp()))
+            .sum();
+        Duration::from_secs_f32(60.0 * integral / initial)
+    })
+}
+
+/**
+* Supersaturation allowed for `tissue_idx` at `ambient`, with the N2 and He
+* `gradients` weighted by the tissue's tensions. Once the first stop is known,
+* gradients shallower than it are reduced by Boyle's law compensation.
+*/
+fn weighted_gradient_vpm<P: const AbsPressure, const NUM_TISSUES: usize>(
+    loading: &TissuesLoading<NUM_TISSUES, P>,
+    tissue_idx: usize,
+    settings: &VpmSettings,
+    gradients: (Pa, Pa),
+    first_stop_ambient: Option<Pa>,
+    ambient: Pa,
+) -> Pa {
+    let (mut gradient_n2, mut gradient_he) = gradients;
+    if let Some(first_stop_ambient) = first_stop_ambient {
+        gradient_n2 =
+            boyle_compensated_gradient(gradient_n2, first_stop_ambient, ambient, settings);
+        gradient_he =
+            boyle_compensated_gradient(gradient_he, first_stop_ambient, ambient, settings);
+    }
+    // H2 takes the He gradient, Ne and Ar the N2 one
+    let (mut p_n2, mut p_he) = (0.0, 0.0);
+    for gas in InertGas::ALL {
+        let p = loading.tension(gas)[tissue_idx].to_pa().to_f32();
+        match gas.reference() {
+            InertGas::He => p_he += p,
+            _ => p_n2 += p,
+        }
+    }
+    if p_n2 + p_he <= 0.0 {
+        return gradient_n2;
+    }
+    Pa::new((gradient_n2.to_f32() * p_n2 + gradient_he.to_f32() * p_he) / (p_n2 + p_he))
+}
+
+/// Whether any tissue's gas tension exceeds ambient plus its allowed gradient.
+fn exceeds_tolerance_vpm<P: const AbsPressure, const NUM_TISSUES: usize>(
+    loading: &TissuesLoading<NUM_TISSUES, P>,
+    settings: &VpmSettings,
+    gradients: &[(Pa, Pa); NUM_TISSUES],
+    first_stop_ambient: Option<Pa>,
+    ambient: Pa,
+) -> bool {
+    (0..NUM_TISSUES).any(|i| {
+        let inert = loading.total_inert(i).to_pa();
+        if inert.to_f32() <= 0.0 {
+            return false;
+        }
+        let gradient = weighted_gradient_vpm(
+            loading,
+            i,
+            settings,
+            gradients[i],
+            first_stop_ambient,
+            ambient,
+        );
+        inert + settings.pressure_other_gases > ambient + gradient
+    })
+}
+
+/**
+* Raises the recorded crushing pressure of each tissue to the current
+* ambient - (inert + other gases) gradient. Gradients beyond the onset of
+* impermeability are capped there instead of solving the impermeable regime.
+*/
+pub fn record_crushing_pressure_vpm<P: const AbsPressure, const NUM_TISSUES: usize>(
+    crushing_pressures: &mut [Pa; NUM_TISSUES],
+    loading: &TissuesLoading<NUM_TISSUES, P>,
+    settings: &VpmSettings,
+    ambient: P,
+) {
+    for (i, crushing) in crushing_pressures.iter_mut().enumerate() {
+        let tension = loading.total_inert(i).to_pa() + settings.pressure_other_gases;
+        let gradient = (ambient.to_pa().to_f32() - tension.to_f32())
+            .clamp(0.0, settings.gradient_onset_of_impermeability.to_f32());
+        if gradient > crushing.to_f32() {
+            *crushing = Pa::new(gradient);
+        }
+    }
+}
+
+/**
+* Stop grid of the VPM-B model. `max_saturation` holds the tension a pure N2
+* tissue tolerates at each depth with the (uncompensated) `gradients`.
+*/
+pub const fn tolerance_rows_vpm<const NUM_TISSUES: usize, const NUM_STOP_DEPTHS: usize>(
+    settings: &VpmSettings,
+    gradients: &[(Pa, Pa); NUM_TISSUES],
+) -> MValues<Pa, NUM_TISSUES, NUM_STOP_DEPTHS> {
+    let mut rows = [TissueRow::empty_pa(); NUM_STOP_DEPTHS];
+    let mut depth_idx = 0;
+    while depth_idx < NUM_STOP_DEPTHS {
+        let depth = msw::new(DINC.0 * depth_idx as f32);
+        let ambient = depth.to_pa();
+        let mut max_saturation = [MSW_0_PA; NUM_TISSUES];
+        let mut i = 0;
+        while i < NUM_TISSUES {
+            max_saturation[i] = ambient + gradients[i].0 - settings.pressure_other_gases;
+            i += 1;
+        }
+        rows[depth_idx] = TissueRow {
+            depth,
+            max_saturation,
+        };
+        depth_idx += 1;
+    }
+    rows
+}
+
+/**
+* Deepest grid depth below the surface at which any tissue's tension exceeds
+* the VPM-B tolerance. Tissues that tolerate every stop but may not surface
+* stop at the shallowest depth of the grid.
+*/
+pub fn first_stop_depth_vpm<P: const AbsPressure, const NUM_TISSUES: usize>(
+    loading: &TissuesLoading<NUM_TISSUES, P>,
+    settings: &VpmSettings,
+    gradients: &[(Pa, Pa); NUM_TISSUES],
+    first_stop_ambient: Option<Pa>,
+    m_values: &[TissueRow<NUM_TISSUES, P>],
+    surface_pressure: P,
+) -> Option<msw> {
+    let exceeds = |depth: msw| {
+        let ambient = ambient_pressure_at_depth(surface_pressure, depth).to_pa();
+        exceeds_tolerance_vpm(loading, settings, gradients, first_stop_ambient, ambient)
+    };
+    let stops = m_values.iter().map(|row| row.depth).filter(|depth| depth.to_f32() > 0.0);
+    let shallowest = stops.clone().next()?;
+    stops
+        .rev()
+        .find(|depth| exceeds(*depth))
+        .or_else(|| exceeds(msw::new(0.0)).then_some(shallowest))
+}
+
+/**
+* Whole minutes at `stop_depth` until every tissue tolerates the next shallower
+* stop (the surface after `last_deco_stop`). The tolerance is bracketed by
+* doubling the stop time and the minute is then bisected.
+*/
+#[allow(clippy::too_many_arguments)]
+pub fn compute_stop_time_vpm<P: const AbsPressure, const NUM_TISSUES: usize>(
+    loading: &TissuesLoading<NUM_TISSUES, P>,
+    tissues: &[BuehlmannTissue; NUM_TISSUES],
+    breathing_gas: &GasMix<f32>,
+    settings: &VpmSettings,
+    gradients: &[(Pa, Pa); NUM_TISSUES],
+    first_stop_ambient: Option<Pa>,
+    stop_depth: msw,
+    surface_pressure: P,
+    last_deco_stop: msw,
+) -> Duration {
+    let is_last_stop = stop_depth.to_msw().to_f32() <= last_deco_stop.to_msw().to_f32();
+    let next_depth = if is_last_stop {
+        msw::new(0.0)
+    } else {
+        msw::new((stop_depth.to_msw().to_f32() - DINC.to_f32()).max(0.0))
+    };
+    let next_ambient = ambient_pressure_at_depth(surface_pressure, next_depth).to_pa();
+    let stop_ambient = ambient_pressure_at_depth(surface_pressure, stop_depth);
+
+    let exceeds_after = |minutes: u64| {
+        let mut loading = loading.clone();
+        let stop_time = Duration::from_secs(minutes * VPM_STOP_TIME_INCREMENT.as_secs());
+        update_model_state_exp(&mut loading, tissues, breathing_gas, stop_ambient, &stop_time);
+        exceeds_tolerance_vpm(&loading, settings, gradients, first_stop_ambient, next_ambient)
+    };
+    if !exceeds_after(0) {
+        return Duration::ZERO;
+    }
+    let (mut low, mut high) = (0, 1);
+    while high < VPM_MAX_STOP_MINUTES && exceeds_after(high) {
+        low = high;
+        high = (2 * high).min(VPM_MAX_STOP_MINUTES);
+    }
+    while high - low > 1 {
+        let mid = (low + high) / 2;
+        if exceeds_after(mid) {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    Duration::from_secs(high * VPM_STOP_TIME_INCREMENT.as_secs())
+}
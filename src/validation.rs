@@ -0,0 +1,1106 @@
+#[allow(unused)]
+use num::Float;
+
+use core::time::Duration;
+
+use crate::{
+    deco_algorithm::{CCRSettings, DecoSettings, calc_ccr_deco_schedule, calc_deco_schedule},
+    deco_model::DecoModel,
+    dive::{Stop, StopSchedule},
+    gas::{
+        AIR, CCRGas, GasDensitySettings, GasMix, IcdSettings, MAX_PO2_DECO, NarcosisSettings,
+        TissuesLoading,
+    },
+    gas_policy::GasSwitchPolicy,
+    pressure_unit::{Pa, Pressure, ambient_pressure_at_depth, fsw, msw},
+    setup::DINC,
+};
+
+/// Stop slots of the schedules computed for the reference rows (down to 87 msw).
+pub const NUM_VALIDATION_STOPS: usize = 30;
+const ATM_PA: f32 = 101_325.0;
+/// Diluent of the constant-PO2 He-O2 tables; the loop adds O2 up to the setpoint.
+const HELIUM: GasMix<f32> = match GasMix::new(0.0, 1.0) {
+    Ok(gas) => gas,
+    Err(_) => panic!("pure He is a valid mix"),
+};
+const DESCENT_STEP: msw = msw::new(1.0);
+/// Smallest total stop time difference a row tolerates, in minutes.
+const MIN_TST_TOLERANCE_MIN: u16 = 15;
+/// Total stop time difference a row tolerates, as a fraction of its published time.
+const TST_TOLERANCE_FRACTION: f32 = 0.2;
+/// Smallest no-stop limit difference a row tolerates, in minutes.
+const MIN_NO_STOP_LIMIT_TOLERANCE_MIN: u16 = 5;
+/// Longest bottom time searched for a no-stop limit, in minutes.
+const MAX_NO_STOP_LIMIT_MIN: u16 = 1440;
+
+/** One published schedule: depth, bottom time and stops from deepest to shallowest. */
+#[derive(Debug, Clone, Copy)]
+pub struct ReferenceRow {
+    pub depth: msw,
+    /// Leaving the surface to leaving the bottom, in minutes.
+    pub bottom_time_min: u16,
+    /// Stop times in minutes, deepest first, ending at the table's last stop.
+    pub stops_min: &'static [u16],
+    pub total_stop_time_min: u16,
+}
+
+impl ReferenceRow {
+    pub const fn new(
+        depth_msw: f32,
+        bottom_time_min: u16,
+        stops_min: &'static [u16],
+        total_stop_time_min: u16,
+    ) -> Self {
+        ReferenceRow {
+            depth: msw::new(depth_msw),
+            bottom_time_min,
+            stops_min,
+            total_stop_time_min,
+        }
+    }
+
+    /// Total stop time difference tolerated for this row, in minutes.
+    pub fn total_stop_time_tolerance_min(&self) -> u16 {
+        let relative = (self.total_stop_time_min as f32 * TST_TOLERANCE_FRACTION).ceil() as u16;
+        relative.max(MIN_TST_TOLERANCE_MIN)
+    }
+
+    /// Depth of `stops_min[i]` for a table whose shallowest stop is `last_stop`.
+    pub fn stop_depth(&self, i: usize, last_stop: msw) -> msw {
+        let from_last = (self.stops_min.len() - 1 - i) as f32;
+        msw::new(last_stop.to_f32() + DINC.to_f32() * from_last)
+    }
+}
+
+/**
+* Constant-PO2 He-O2 table of NEDU TR 18-05, Appendix E: the diver descends on
+* `descent_set_point` until `set_point_switch_depth`, then breathes
+* `set_point` for the rest of the dive and all stops.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct ConstantPo2Table<'a> {
+    pub set_point: Pa,
+    pub descent_set_point: Pa,
+    pub set_point_switch_depth: msw,
+    /// msw per minute
+    pub descent_rate: f32,
+    /// msw per minute, to the first stop and between stops
+    pub ascent_rate: f32,
+    pub last_stop: msw,
+    pub rows: &'a [ReferenceRow],
+}
+
+/** Difference between a reference row and the schedule computed for it. */
+#[derive(Debug, Clone, Copy)]
+pub struct RowDeviation {
+    pub row: ReferenceRow,
+    pub reference_first_stop: Option<msw>,
+    pub computed_first_stop: Option<msw>,
+    /// Computed stops with their travel rounded up to whole minutes, as in the tables.
+    pub computed_total_stop_time_min: u16,
+    /// Largest difference of a single stop, matched by depth, in minutes.
+    pub max_stop_error_min: u16,
+}
+
+impl RowDeviation {
+    /// Computed minus published total stop time.
+    pub fn total_stop_time_error_min(&self) -> i32 {
+        self.computed_total_stop_time_min as i32 - self.row.total_stop_time_min as i32
+    }
+
+    /// Computed minus published first stop depth; a missing stop counts as 0 msw.
+    pub fn first_stop_error(&self) -> msw {
+        let depth = |d: Option<msw>| d.map_or(0.0, |d| d.to_f32());
+        msw::new(depth(self.computed_first_stop) - depth(self.reference_first_stop))
+    }
+
+    pub fn matches(&self) -> bool {
+        self.total_stop_time_error_min() == 0 && self.max_stop_error_min == 0
+    }
+
+    /**
+    * Total stop time within the tolerance of the row and, when both schedules
+    * have stops, first stops at most one stop increment apart.
+    */
+    pub fn within_tolerance(&self) -> bool {
+        let first_stop_ok = match (self.computed_first_stop, self.reference_first_stop) {
+            (Some(_), Some(_)) => self.first_stop_error().to_f32().abs() <= DINC.to_f32(),
+            _ => true,
+        };
+        first_stop_ok
+            && self.total_stop_time_error_min().unsigned_abs()
+                <= self.row.total_stop_time_tolerance_min() as u32
+    }
+}
+
+fn ceil_minutes(duration: Duration) -> u16 {
+    duration.as_secs().div_ceil(60) as u16
+}
+
+impl ConstantPo2Table<'_> {
+    /// Setpoints of the table as a rebreather on a pure He diluent.
+    pub fn ccr_settings(&self) -> CCRSettings<Pa> {
+        CCRSettings {
+            low_set_point: self.descent_set_point,
+            high_set_point: self.set_point,
+            switch_up_depth: self.set_point_switch_depth,
+            switch_down_depth: msw::new(0.0),
+        }
+    }
+
+    fn loop_at(&self, depth: msw, descending: bool) -> CCRGas<f32, Pa> {
+        self.ccr_settings().loop_gas(&HELIUM, depth, !descending)
+    }
+
+    /// Tissue loading when leaving the bottom of `row`, starting saturated on air.
+    pub fn bottom_loading<M: DecoModel<NUM_TISSUES, Pa>, const NUM_TISSUES: usize>(
+        &self,
+        model: &M,
+        row: &ReferenceRow,
+        surface_pressure: Pa,
+    ) -> TissuesLoading<NUM_TISSUES, Pa> {
+        let mut loading = TissuesLoading::new(surface_pressure, &crate::gas::AIR);
+        let step = Duration::from_secs_f32(DESCENT_STEP.to_f32() / self.descent_rate * 60.0);
+        let mut depth = 0.0;
+        while depth < row.depth.to_f32() {
+            let next = (depth + DESCENT_STEP.to_f32()).min(row.depth.to_f32());
+            let mid = msw::new(0.5 * (depth + next));
+            let fraction = (next - depth) / DESCENT_STEP.to_f32();
+            model.update_model_state_loop(
+                &mut loading,
+                &self.loop_at(mid, true),
+                ambient_pressure_at_depth(surface_pressure, mid),
+                &step.mul_f32(fraction),
+            );
+            depth = next;
+        }
+        let descent = Duration::from_secs_f32(row.depth.to_f32() / self.descent_rate * 60.0);
+        let bottom = Duration::from_secs(row.bottom_time_min as u64 * 60).saturating_sub(descent);
+        model.update_model_state_loop(
+            &mut loading,
+            &self.loop_at(row.depth, false),
+            ambient_pressure_at_depth(surface_pressure, row.depth),
+            &bottom,
+        );
+        loading
+    }
+
+    /// Decompression settings of the table: gradient factors are not applied.
+    pub fn deco_settings(&self, surface_pressure: Pa) -> DecoSettings<'static, Pa> {
+        DecoSettings {
+            gas_density_settings: GasDensitySettings::Ignore,
+            narcosis_settings: NarcosisSettings::Ignore,
+            max_deco_po2: self.set_point,
+            surface_pressure,
+            icd_settings: IcdSettings::Ignore,
+            gf_low: 1.0,
+            gf_high: 1.0,
+            last_deco_stop: self.last_stop,
+            ascent_rate_per_meter: Duration::from_secs_f32(60.0 / self.ascent_rate),
+            gas_switch_policy: GasSwitchPolicy::default(),
+        }
+    }
+
+    /// Schedule computed by `model` for `row`, ascent and stops on constant-PO2 He-O2.
+    pub fn compute_schedule<M: DecoModel<NUM_TISSUES, Pa>, const NUM_TISSUES: usize>(
+        &self,
+        model: &M,
+        row: &ReferenceRow,
+    ) -> Result<StopSchedule<NUM_VALIDATION_STOPS>, &'static str> {
+        let surface_pressure = msw::new(0.0).to_pa();
+        let loading = self.bottom_loading(model, row, surface_pressure);
+        calc_ccr_deco_schedule(
+            model,
+            &loading,
+            row.depth,
+            0,
+            &self.ccr_settings(),
+            &[HELIUM],
+            &[true],
+            &self.deco_settings(surface_pressure),
+        )
+    }
+
+    /// Runs `model` on `row` and compares the stops with the published ones.
+    pub fn validate_row<M: DecoModel<NUM_TISSUES, Pa>, const NUM_TISSUES: usize>(
+        &self,
+        model: &M,
+        row: &ReferenceRow,
+    ) -> Result<RowDeviation, &'static str> {
+        let schedule = self.compute_schedule(model, row)?;
+        let computed = || schedule.stops().iter().filter(|s| !s.duration().is_zero());
+        // As in the tables, a stop after the first includes the travel to it
+        let travel_min = |depth: msw| {
+            computed()
+                .take_while(|s| s.depth() > depth)
+                .last()
+                .map_or(0.0, |s| {
+                    (s.depth().to_f32() - depth.to_f32()) / self.ascent_rate
+                })
+        };
+        let stop_minutes = |s: &Stop| {
+            ceil_minutes(s.duration() + Duration::from_secs_f32(60.0 * travel_min(s.depth())))
+        };
+
+        let reference_minutes_at = |depth: msw| {
+            (0..row.stops_min.len())
+                .find(|i| row.stop_depth(*i, self.last_stop) == depth)
+                .map_or(0, |i| row.stops_min[i])
+        };
+        let computed_minutes_at = |depth: msw| {
+            computed()
+                .find(|s| s.depth() == depth)
+                .map_or(0, stop_minutes)
+        };
+        let max_stop_error_min = computed()
+            .map(|s| s.depth())
+            .chain((0..row.stops_min.len()).map(|i| row.stop_depth(i, self.last_stop)))
+            .map(|depth| computed_minutes_at(depth).abs_diff(reference_minutes_at(depth)))
+            .max()
+            .unwrap_or(0);
+
+        Ok(RowDeviation {
+            row: *row,
+            reference_first_stop: (!row.stops_min.is_empty())
+                .then(|| row.stop_depth(0, self.last_stop)),
+            computed_first_stop: computed().next().map(|s| s.depth()),
+            computed_total_stop_time_min: computed().map(stop_minutes).sum(),
+            max_stop_error_min,
+        })
+    }
+
+    /// Deviation of every row of the table, in table order.
+    pub fn validate<'m, M: DecoModel<NUM_TISSUES, Pa>, const NUM_TISSUES: usize>(
+        &'m self,
+        model: &'m M,
+    ) -> impl Iterator<Item = Result<RowDeviation, &'static str>> + 'm {
+        self.rows
+            .iter()
+            .map(move |row| self.validate_row(model, row))
+    }
+}
+
+/**
+* XVal-He-9_040 1.3 atm PO2 He-O2 decompression tables (msw), NEDU TR 18-05
+* Appendix E, pp. E-1 to E-23 (one page per depth, cited above each group of rows):
+* no-stop limits and a selection of bottom times per depth.
+* Stop times after the first include the 9 msw/min travel from the previous stop.
+*/
+pub const XVAL_HE9_040_HEO2_1_3_ATA: ConstantPo2Table<'static> = ConstantPo2Table {
+    set_point: Pa::new(1.3 * ATM_PA),
+    descent_set_point: Pa::new(0.7 * ATM_PA),
+    set_point_switch_depth: msw::new(9.75),
+    descent_rate: 18.0,
+    ascent_rate: 9.0,
+    last_stop: msw::new(6.0),
+    rows: &XVAL_HE9_040_HEO2_1_3_ATA_ROWS,
+};
+
+pub const XVAL_HE9_040_HEO2_1_3_ATA_ROWS: [ReferenceRow; 204] = [
+    // 24 msw, TR 18-05 p. E-1
+    ReferenceRow::new(24.0, 90, &[], 0),
+    ReferenceRow::new(24.0, 120, &[20], 20),
+    ReferenceRow::new(24.0, 180, &[53], 53),
+    ReferenceRow::new(24.0, 240, &[86], 86),
+    // 27 msw, TR 18-05 p. E-2
+    ReferenceRow::new(27.0, 50, &[], 0),
+    ReferenceRow::new(27.0, 60, &[1], 1),
+    ReferenceRow::new(27.0, 90, &[20], 20),
+    ReferenceRow::new(27.0, 120, &[42], 42),
+    ReferenceRow::new(27.0, 180, &[88], 88),
+    ReferenceRow::new(27.0, 240, &[126], 126),
+    // 30 msw, TR 18-05 p. E-3
+    ReferenceRow::new(30.0, 34, &[], 0),
+    ReferenceRow::new(30.0, 40, &[2], 2),
+    ReferenceRow::new(30.0, 60, &[5], 5),
+    ReferenceRow::new(30.0, 90, &[37], 37),
+    ReferenceRow::new(30.0, 120, &[64], 64),
+    ReferenceRow::new(30.0, 180, &[120], 120),
+    ReferenceRow::new(30.0, 240, &[165], 165),
+    // 33 msw, TR 18-05 p. E-4
+    ReferenceRow::new(33.0, 26, &[], 0),
+    ReferenceRow::new(33.0, 30, &[1], 1),
+    ReferenceRow::new(33.0, 40, &[4], 4),
+    ReferenceRow::new(33.0, 60, &[19], 19),
+    ReferenceRow::new(33.0, 90, &[2, 53], 55),
+    ReferenceRow::new(33.0, 120, &[3, 87], 90),
+    ReferenceRow::new(33.0, 180, &[3, 150], 153),
+    ReferenceRow::new(33.0, 240, &[26, 179], 205),
+    // 36 msw, TR 18-05 p. E-5
+    ReferenceRow::new(36.0, 22, &[], 0),
+    ReferenceRow::new(36.0, 30, &[3], 3),
+    ReferenceRow::new(36.0, 40, &[6], 6),
+    ReferenceRow::new(36.0, 60, &[3, 30], 33),
+    ReferenceRow::new(36.0, 90, &[2, 4, 67], 73),
+    ReferenceRow::new(36.0, 120, &[3, 3, 109], 115),
+    ReferenceRow::new(36.0, 180, &[3, 4, 180], 187),
+    ReferenceRow::new(36.0, 240, &[3, 63, 179], 245),
+    // 39 msw, TR 18-05 p. E-6
+    ReferenceRow::new(39.0, 18, &[], 0),
+    ReferenceRow::new(39.0, 20, &[1], 1),
+    ReferenceRow::new(39.0, 30, &[6], 6),
+    ReferenceRow::new(39.0, 40, &[2, 8], 10),
+    ReferenceRow::new(39.0, 60, &[3, 3, 41], 47),
+    ReferenceRow::new(39.0, 90, &[2, 3, 4, 85], 94),
+    ReferenceRow::new(39.0, 120, &[3, 3, 6, 129], 141),
+    ReferenceRow::new(39.0, 180, &[3, 4, 34, 179], 220),
+    ReferenceRow::new(39.0, 240, &[3, 19, 84, 180], 286),
+    // 42 msw, TR 18-05 p. E-7
+    ReferenceRow::new(42.0, 16, &[], 0),
+    ReferenceRow::new(42.0, 20, &[3], 3),
+    ReferenceRow::new(42.0, 30, &[1, 7], 8),
+    ReferenceRow::new(42.0, 40, &[1, 4, 16], 21),
+    ReferenceRow::new(42.0, 60, &[2, 4, 4, 51], 61),
+    ReferenceRow::new(42.0, 90, &[2, 3, 4, 10, 97], 116),
+    ReferenceRow::new(42.0, 120, &[3, 3, 6, 10, 145], 167),
+    ReferenceRow::new(42.0, 180, &[3, 4, 6, 62, 179], 254),
+    ReferenceRow::new(42.0, 240, &[3, 4, 55, 85, 179], 326),
+    // 45 msw, TR 18-05 p. E-8
+    ReferenceRow::new(45.0, 13, &[], 0),
+    ReferenceRow::new(45.0, 20, &[6], 6),
+    ReferenceRow::new(45.0, 30, &[4, 8], 12),
+    ReferenceRow::new(45.0, 40, &[3, 4, 24], 31),
+    ReferenceRow::new(45.0, 60, &[2, 3, 4, 10, 56], 75),
+    ReferenceRow::new(45.0, 90, &[2, 3, 4, 10, 10, 111], 140),
+    ReferenceRow::new(45.0, 120, &[3, 3, 5, 11, 11, 161], 194),
+    ReferenceRow::new(45.0, 180, &[3, 4, 6, 12, 84, 179], 288),
+    ReferenceRow::new(45.0, 240, &[3, 4, 12, 84, 84, 179], 366),
+    // 48 msw, TR 18-05 p. E-9
+    ReferenceRow::new(48.0, 12, &[], 0),
+    ReferenceRow::new(48.0, 20, &[1, 7], 8),
+    ReferenceRow::new(48.0, 30, &[3, 4, 12], 19),
+    ReferenceRow::new(48.0, 40, &[3, 3, 5, 31], 42),
+    ReferenceRow::new(48.0, 60, &[1, 4, 3, 10, 10, 65], 93),
+    ReferenceRow::new(48.0, 90, &[1, 4, 4, 9, 11, 10, 125], 164),
+    ReferenceRow::new(48.0, 120, &[3, 3, 5, 11, 10, 12, 179], 223),
+    ReferenceRow::new(48.0, 180, &[3, 4, 6, 11, 35, 84, 180], 323),
+    ReferenceRow::new(48.0, 240, &[3, 4, 6, 46, 85, 84, 179], 407),
+    // 51 msw, TR 18-05 p. E-10
+    ReferenceRow::new(51.0, 10, &[], 0),
+    ReferenceRow::new(51.0, 20, &[3, 7], 10),
+    ReferenceRow::new(51.0, 30, &[3, 3, 4, 16], 26),
+    ReferenceRow::new(51.0, 40, &[2, 4, 3, 10, 35], 54),
+    ReferenceRow::new(51.0, 60, &[1, 3, 4, 8, 11, 10, 76], 113),
+    ReferenceRow::new(51.0, 90, &[1, 4, 3, 10, 10, 11, 10, 141], 190),
+    ReferenceRow::new(51.0, 120, &[3, 3, 5, 11, 10, 11, 30, 179], 252),
+    ReferenceRow::new(51.0, 180, &[3, 4, 6, 11, 10, 61, 84, 179], 358),
+    ReferenceRow::new(51.0, 240, &[3, 4, 6, 11, 76, 84, 85, 179], 448),
+    // 54 msw, TR 18-05 p. E-11
+    ReferenceRow::new(54.0, 9, &[], 0),
+    ReferenceRow::new(54.0, 10, &[1], 1),
+    ReferenceRow::new(54.0, 20, &[2, 4, 7], 13),
+    ReferenceRow::new(54.0, 30, &[2, 4, 3, 4, 21], 34),
+    ReferenceRow::new(54.0, 40, &[2, 4, 3, 7, 11, 40], 67),
+    ReferenceRow::new(54.0, 60, &[4, 3, 8, 10, 11, 10, 88], 134),
+    ReferenceRow::new(54.0, 90, &[1, 4, 3, 9, 11, 10, 11, 10, 157], 216),
+    ReferenceRow::new(54.0, 120, &[3, 3, 5, 11, 10, 11, 10, 51, 179], 283),
+    ReferenceRow::new(54.0, 180, &[3, 4, 6, 11, 10, 13, 84, 84, 179], 394),
+    ReferenceRow::new(54.0, 240, &[3, 4, 6, 11, 33, 85, 84, 84, 179], 489),
+    // 57 msw, TR 18-05 p. E-12
+    ReferenceRow::new(57.0, 8, &[], 0),
+    ReferenceRow::new(57.0, 10, &[3], 3),
+    ReferenceRow::new(57.0, 20, &[2, 3, 4, 7], 16),
+    ReferenceRow::new(57.0, 30, &[2, 3, 4, 3, 6, 27], 45),
+    ReferenceRow::new(57.0, 40, &[2, 3, 4, 5, 10, 11, 46], 81),
+    ReferenceRow::new(57.0, 60, &[3, 4, 6, 11, 10, 11, 10, 101], 156),
+    ReferenceRow::new(57.0, 90, &[1, 4, 3, 9, 10, 11, 10, 11, 10, 175], 244),
+    ReferenceRow::new(57.0, 120, &[2, 4, 5, 10, 11, 11, 10, 11, 71, 179], 314),
+    ReferenceRow::new(57.0, 180, &[3, 4, 6, 11, 10, 11, 39, 84, 84, 179], 431),
+    ReferenceRow::new(57.0, 240, &[3, 4, 6, 11, 11, 64, 84, 84, 84, 180], 531),
+    // 60 msw, TR 18-05 p. E-13
+    ReferenceRow::new(60.0, 8, &[], 0),
+    ReferenceRow::new(60.0, 10, &[4], 4),
+    ReferenceRow::new(60.0, 20, &[1, 3, 4, 3, 10], 21),
+    ReferenceRow::new(60.0, 30, &[1, 4, 3, 4, 3, 10, 31], 56),
+    ReferenceRow::new(60.0, 40, &[2, 3, 4, 3, 10, 10, 11, 53], 96),
+    ReferenceRow::new(60.0, 60, &[3, 3, 6, 10, 11, 10, 11, 11, 114], 179),
+    ReferenceRow::new(60.0, 90, &[1, 3, 4, 8, 11, 10, 11, 10, 11, 24, 180], 273),
+    ReferenceRow::new(60.0, 120, &[2, 4, 5, 10, 11, 10, 11, 10, 20, 84, 179], 346),
+    ReferenceRow::new(60.0, 180, &[3, 4, 6, 11, 10, 11, 10, 66, 84, 84, 180], 469),
+    ReferenceRow::new(60.0, 240, &[3, 4, 6, 11, 11, 22, 84, 84, 84, 84, 180], 573),
+    // 63 msw, TR 18-05 p. E-14
+    ReferenceRow::new(63.0, 7, &[], 0),
+    ReferenceRow::new(63.0, 10, &[6], 6),
+    ReferenceRow::new(63.0, 20, &[3, 4, 3, 4, 12], 26),
+    ReferenceRow::new(63.0, 30, &[1, 3, 4, 3, 4, 6, 11, 36], 68),
+    ReferenceRow::new(63.0, 40, &[1, 4, 3, 4, 7, 10, 11, 11, 64], 115),
+    ReferenceRow::new(63.0, 60, &[2, 4, 4, 11, 11, 10, 11, 10, 11, 129], 203),
+    ReferenceRow::new(
+        63.0,
+        90,
+        &[1, 3, 4, 8, 10, 11, 10, 11, 10, 11, 43, 180],
+        302,
+    ),
+    ReferenceRow::new(
+        63.0,
+        120,
+        &[2, 4, 4, 11, 11, 10, 11, 10, 11, 42, 84, 179],
+        379,
+    ),
+    ReferenceRow::new(
+        63.0,
+        180,
+        &[3, 4, 6, 11, 10, 11, 10, 20, 84, 85, 84, 179],
+        507,
+    ),
+    ReferenceRow::new(
+        63.0,
+        240,
+        &[3, 4, 6, 11, 11, 10, 54, 84, 85, 84, 84, 179],
+        615,
+    ),
+    // 66 msw, TR 18-05 p. E-15
+    ReferenceRow::new(66.0, 6, &[], 0),
+    ReferenceRow::new(66.0, 10, &[7], 7),
+    ReferenceRow::new(66.0, 20, &[2, 4, 3, 4, 3, 16], 32),
+    ReferenceRow::new(66.0, 30, &[3, 4, 4, 3, 4, 10, 10, 41], 79),
+    ReferenceRow::new(66.0, 40, &[1, 4, 3, 4, 4, 11, 10, 11, 10, 76], 134),
+    ReferenceRow::new(66.0, 60, &[2, 3, 4, 11, 10, 11, 10, 11, 10, 11, 144], 227),
+    ReferenceRow::new(
+        66.0,
+        90,
+        &[4, 3, 8, 10, 11, 10, 11, 11, 10, 11, 63, 180],
+        332,
+    ),
+    ReferenceRow::new(
+        66.0,
+        120,
+        &[2, 4, 4, 11, 10, 11, 11, 10, 11, 10, 65, 85, 179],
+        413,
+    ),
+    ReferenceRow::new(
+        66.0,
+        180,
+        &[3, 4, 6, 11, 10, 11, 10, 11, 48, 84, 84, 84, 180],
+        546,
+    ),
+    ReferenceRow::new(
+        66.0,
+        240,
+        &[3, 4, 6, 11, 11, 10, 13, 85, 84, 84, 84, 84, 180],
+        659,
+    ),
+    // 69 msw, TR 18-05 p. E-16
+    ReferenceRow::new(69.0, 6, &[], 0),
+    ReferenceRow::new(69.0, 10, &[2, 7], 9),
+    ReferenceRow::new(69.0, 20, &[1, 4, 3, 4, 3, 4, 18], 37),
+    ReferenceRow::new(69.0, 30, &[3, 4, 3, 4, 3, 7, 10, 11, 47], 92),
+    ReferenceRow::new(69.0, 40, &[1, 3, 4, 4, 3, 9, 10, 11, 11, 10, 87], 153),
+    ReferenceRow::new(
+        69.0,
+        60,
+        &[1, 4, 4, 9, 11, 10, 11, 10, 11, 11, 10, 160],
+        252,
+    ),
+    ReferenceRow::new(
+        69.0,
+        90,
+        &[4, 3, 7, 11, 10, 11, 11, 10, 11, 10, 11, 84, 180],
+        363,
+    ),
+    ReferenceRow::new(
+        69.0,
+        120,
+        &[2, 4, 4, 11, 10, 11, 10, 11, 10, 11, 15, 85, 84, 179],
+        447,
+    ),
+    ReferenceRow::new(
+        69.0,
+        180,
+        &[3, 4, 6, 11, 10, 11, 10, 11, 10, 77, 85, 84, 84, 179],
+        585,
+    ),
+    ReferenceRow::new(
+        69.0,
+        240,
+        &[3, 4, 6, 11, 11, 10, 11, 46, 84, 84, 84, 84, 85, 179],
+        702,
+    ),
+    // 72 msw, TR 18-05 p. E-17
+    ReferenceRow::new(72.0, 5, &[], 0),
+    ReferenceRow::new(72.0, 10, &[4, 7], 11),
+    ReferenceRow::new(72.0, 20, &[3, 4, 3, 4, 4, 3, 24], 45),
+    ReferenceRow::new(72.0, 30, &[2, 4, 4, 3, 4, 3, 10, 11, 11, 55], 107),
+    ReferenceRow::new(72.0, 40, &[1, 3, 4, 3, 4, 6, 11, 10, 11, 10, 11, 99], 173),
+    ReferenceRow::new(
+        72.0,
+        60,
+        &[1, 4, 3, 9, 10, 11, 10, 11, 11, 10, 11, 10, 177],
+        278,
+    ),
+    ReferenceRow::new(
+        72.0,
+        90,
+        &[3, 4, 7, 10, 11, 10, 11, 11, 10, 11, 10, 33, 84, 179],
+        394,
+    ),
+    ReferenceRow::new(
+        72.0,
+        120,
+        &[2, 4, 4, 10, 11, 11, 10, 11, 10, 11, 10, 41, 84, 84, 180],
+        483,
+    ),
+    ReferenceRow::new(
+        72.0,
+        180,
+        &[3, 4, 6, 11, 10, 11, 10, 11, 10, 33, 84, 84, 85, 84, 179],
+        625,
+    ),
+    ReferenceRow::new(
+        72.0,
+        240,
+        &[3, 4, 6, 11, 10, 11, 11, 10, 80, 84, 84, 84, 84, 84, 180],
+        746,
+    ),
+    // 75 msw, TR 18-05 p. E-18
+    ReferenceRow::new(75.0, 5, &[], 0),
+    ReferenceRow::new(75.0, 10, &[2, 3, 8], 13),
+    ReferenceRow::new(75.0, 20, &[2, 4, 4, 3, 4, 3, 6, 28], 54),
+    ReferenceRow::new(75.0, 30, &[2, 4, 3, 4, 3, 4, 7, 10, 11, 10, 66], 124),
+    ReferenceRow::new(75.0, 40, &[4, 3, 4, 3, 4, 11, 10, 11, 10, 11, 10, 112], 193),
+    ReferenceRow::new(
+        75.0,
+        60,
+        &[1, 3, 4, 7, 11, 10, 11, 11, 10, 11, 10, 11, 25, 180],
+        305,
+    ),
+    ReferenceRow::new(
+        75.0,
+        90,
+        &[3, 4, 6, 11, 10, 11, 10, 11, 11, 10, 11, 10, 55, 85, 179],
+        427,
+    ),
+    ReferenceRow::new(
+        75.0,
+        120,
+        &[2, 4, 4, 10, 11, 10, 11, 11, 10, 11, 10, 11, 66, 84, 84, 179],
+        518,
+    ),
+    ReferenceRow::new(
+        75.0,
+        180,
+        &[3, 4, 6, 11, 10, 11, 10, 11, 10, 11, 63, 84, 84, 84, 84, 180],
+        666,
+    ),
+    ReferenceRow::new(
+        75.0,
+        240,
+        &[3, 4, 6, 11, 10, 11, 11, 10, 40, 84, 84, 84, 84, 84, 85, 179],
+        790,
+    ),
+    // 78 msw, TR 18-05 p. E-19
+    ReferenceRow::new(78.0, 5, &[], 0),
+    ReferenceRow::new(78.0, 10, &[3, 4, 7], 14),
+    ReferenceRow::new(78.0, 20, &[2, 3, 4, 3, 4, 3, 4, 9, 32], 64),
+    ReferenceRow::new(78.0, 30, &[1, 4, 4, 3, 4, 3, 4, 10, 11, 11, 10, 76], 141),
+    ReferenceRow::new(
+        78.0,
+        40,
+        &[3, 4, 3, 4, 3, 9, 10, 11, 11, 10, 11, 10, 125],
+        214,
+    ),
+    ReferenceRow::new(
+        78.0,
+        60,
+        &[1, 3, 4, 6, 10, 11, 11, 10, 11, 10, 11, 10, 11, 44, 179],
+        332,
+    ),
+    ReferenceRow::new(
+        78.0,
+        90,
+        &[3, 4, 6, 10, 11, 10, 11, 11, 10, 11, 10, 11, 10, 79, 84, 179],
+        460,
+    ),
+    ReferenceRow::new(
+        78.0,
+        120,
+        &[
+            2, 4, 4, 10, 11, 10, 11, 10, 11, 11, 10, 11, 18, 84, 85, 84, 179,
+        ],
+        555,
+    ),
+    ReferenceRow::new(
+        78.0,
+        180,
+        &[
+            3, 4, 6, 10, 11, 11, 10, 11, 10, 11, 19, 85, 84, 84, 84, 84, 179,
+        ],
+        706,
+    ),
+    ReferenceRow::new(
+        78.0,
+        240,
+        &[
+            3, 4, 6, 11, 10, 11, 11, 10, 11, 73, 85, 84, 84, 84, 84, 84, 180,
+        ],
+        835,
+    ),
+    // 81 msw, TR 18-05 p. E-20
+    ReferenceRow::new(81.0, 10, &[2, 3, 4, 7], 16),
+    ReferenceRow::new(81.0, 20, &[1, 3, 4, 3, 4, 3, 4, 4, 11, 36], 73),
+    ReferenceRow::new(81.0, 30, &[1, 4, 3, 4, 3, 4, 3, 8, 10, 11, 10, 11, 86], 158),
+    ReferenceRow::new(
+        81.0,
+        40,
+        &[3, 4, 3, 4, 3, 6, 11, 10, 11, 10, 11, 10, 11, 138],
+        235,
+    ),
+    ReferenceRow::new(
+        81.0,
+        60,
+        &[1, 3, 4, 4, 11, 11, 10, 11, 10, 11, 10, 11, 11, 10, 63, 179],
+        360,
+    ),
+    ReferenceRow::new(
+        81.0,
+        90,
+        &[
+            3, 3, 6, 11, 10, 11, 10, 11, 11, 10, 11, 10, 11, 28, 84, 84, 180,
+        ],
+        494,
+    ),
+    ReferenceRow::new(
+        81.0,
+        120,
+        &[
+            2, 4, 3, 11, 10, 11, 11, 10, 11, 10, 11, 10, 11, 45, 84, 84, 85, 179,
+        ],
+        592,
+    ),
+    ReferenceRow::new(
+        81.0,
+        180,
+        &[
+            3, 4, 6, 10, 11, 11, 10, 11, 10, 11, 10, 51, 84, 84, 84, 84, 85, 179,
+        ],
+        748,
+    ),
+    ReferenceRow::new(
+        81.0,
+        240,
+        &[
+            3, 4, 6, 11, 10, 11, 11, 10, 11, 35, 84, 84, 84, 84, 84, 84, 85, 179,
+        ],
+        880,
+    ),
+    // 84 msw, TR 18-05 p. E-21
+    ReferenceRow::new(84.0, 10, &[3, 4, 3, 7], 17),
+    ReferenceRow::new(84.0, 20, &[3, 3, 4, 3, 4, 4, 3, 7, 11, 41], 83),
+    ReferenceRow::new(
+        84.0,
+        30,
+        &[4, 3, 4, 3, 4, 3, 4, 11, 11, 10, 11, 10, 97],
+        175,
+    ),
+    ReferenceRow::new(
+        84.0,
+        40,
+        &[3, 3, 4, 3, 4, 3, 11, 10, 11, 11, 10, 11, 10, 11, 152],
+        257,
+    ),
+    ReferenceRow::new(
+        84.0,
+        60,
+        &[
+            1, 3, 4, 3, 11, 10, 11, 10, 11, 10, 11, 11, 10, 11, 10, 83, 179,
+        ],
+        389,
+    ),
+    ReferenceRow::new(
+        84.0,
+        90,
+        &[
+            3, 3, 6, 10, 11, 10, 11, 11, 10, 11, 10, 11, 10, 11, 53, 84, 84, 180,
+        ],
+        529,
+    ),
+    ReferenceRow::new(
+        84.0,
+        120,
+        &[
+            2, 3, 4, 11, 10, 11, 10, 11, 11, 10, 11, 10, 11, 10, 73, 84, 84, 84, 180,
+        ],
+        630,
+    ),
+    ReferenceRow::new(
+        84.0,
+        180,
+        &[
+            3, 4, 6, 10, 11, 11, 10, 11, 10, 11, 10, 11, 82, 84, 84, 84, 84, 85, 179,
+        ],
+        790,
+    ),
+    ReferenceRow::new(
+        84.0,
+        240,
+        &[
+            3, 4, 6, 11, 10, 11, 11, 10, 11, 10, 70, 84, 84, 84, 84, 85, 84, 84, 179,
+        ],
+        925,
+    ),
+    // 87 msw, TR 18-05 p. E-22
+    ReferenceRow::new(87.0, 10, &[2, 3, 4, 3, 7], 19),
+    ReferenceRow::new(87.0, 20, &[2, 3, 4, 4, 3, 4, 3, 4, 9, 11, 46], 93),
+    ReferenceRow::new(
+        87.0,
+        30,
+        &[3, 4, 3, 4, 3, 4, 3, 8, 11, 10, 11, 10, 11, 108],
+        193,
+    ),
+    ReferenceRow::new(
+        87.0,
+        40,
+        &[3, 3, 4, 3, 4, 3, 8, 11, 10, 11, 10, 11, 10, 11, 11, 167],
+        280,
+    ),
+    ReferenceRow::new(
+        87.0,
+        60,
+        &[
+            1, 3, 4, 3, 9, 11, 10, 11, 10, 11, 10, 11, 11, 10, 11, 29, 84, 179,
+        ],
+        418,
+    ),
+    ReferenceRow::new(
+        87.0,
+        90,
+        &[
+            3, 3, 5, 11, 10, 11, 10, 11, 11, 10, 11, 10, 11, 10, 11, 78, 84, 84, 180,
+        ],
+        564,
+    ),
+    ReferenceRow::new(
+        87.0,
+        120,
+        &[
+            2, 3, 4, 11, 10, 11, 10, 11, 10, 11, 11, 10, 11, 10, 27, 84, 85, 84, 84, 179,
+        ],
+        668,
+    ),
+    ReferenceRow::new(
+        87.0,
+        180,
+        &[
+            3, 4, 6, 10, 11, 11, 10, 11, 10, 11, 10, 11, 40, 84, 85, 84, 84, 84, 84, 180,
+        ],
+        833,
+    ),
+    ReferenceRow::new(
+        87.0,
+        240,
+        &[
+            3, 4, 6, 11, 10, 11, 11, 10, 11, 10, 32, 84, 84, 84, 84, 85, 84, 84, 84, 179,
+        ],
+        971,
+    ),
+    // 90 msw, TR 18-05 p. E-23
+    ReferenceRow::new(90.0, 10, &[3, 4, 3, 4, 7], 21),
+    ReferenceRow::new(90.0, 20, &[1, 4, 3, 4, 3, 4, 3, 4, 5, 11, 11, 51], 104),
+    ReferenceRow::new(
+        90.0,
+        30,
+        &[3, 3, 4, 3, 4, 3, 4, 4, 11, 10, 11, 11, 10, 11, 119],
+        211,
+    ),
+    ReferenceRow::new(
+        90.0,
+        40,
+        &[2, 4, 3, 4, 3, 4, 5, 11, 10, 11, 11, 10, 11, 10, 11, 14, 179],
+        303,
+    ),
+    ReferenceRow::new(
+        90.0,
+        60,
+        &[
+            1, 3, 4, 3, 8, 10, 11, 10, 11, 10, 11, 11, 10, 11, 10, 11, 50, 84, 179,
+        ],
+        448,
+    ),
+    ReferenceRow::new(
+        90.0,
+        90,
+        &[
+            2, 4, 5, 10, 11, 10, 11, 10, 11, 11, 10, 11, 10, 11, 10, 30, 85, 84, 84, 179,
+        ],
+        599,
+    ),
+    ReferenceRow::new(
+        90.0,
+        120,
+        &[
+            2, 3, 4, 10, 11, 10, 11, 11, 10, 11, 10, 11, 10, 11, 11, 55, 84, 84, 84, 84, 180,
+        ],
+        707,
+    ),
+    ReferenceRow::new(
+        90.0,
+        180,
+        &[
+            3, 4, 6, 10, 11, 11, 10, 11, 10, 11, 10, 11, 11, 72, 84, 84, 85, 84, 84, 84, 179,
+        ],
+        875,
+    ),
+    ReferenceRow::new(
+        90.0,
+        240,
+        &[
+            3, 4, 6, 11, 10, 11, 11, 10, 11, 10, 11, 67, 84, 84, 84, 84, 85, 84, 84, 84, 179,
+        ],
+        1017,
+    ),
+];
+
+/** Published no-stop limit: the longest bottom time at `depth` that needs no stops. */
+#[derive(Debug, Clone, Copy)]
+pub struct NoStopLimitRow {
+    pub depth: msw,
+    /// Leaving the surface to leaving the bottom, in minutes.
+    pub limit_min: u16,
+}
+
+impl NoStopLimitRow {
+    pub const fn new(depth_fsw: f32, limit_min: u16) -> Self {
+        NoStopLimitRow {
+            depth: fsw::new(depth_fsw).to_msw(),
+            limit_min,
+        }
+    }
+
+    /// No-stop limit difference tolerated for this row, in minutes.
+    pub fn tolerance_min(&self) -> u16 {
+        let relative = (self.limit_min as f32 * TST_TOLERANCE_FRACTION).ceil() as u16;
+        relative.max(MIN_NO_STOP_LIMIT_TOLERANCE_MIN)
+    }
+}
+
+/** Difference between a published no-stop limit and the one computed for it. */
+#[derive(Debug, Clone, Copy)]
+pub struct NoStopLimitDeviation {
+    pub row: NoStopLimitRow,
+    pub computed_limit_min: u16,
+}
+
+impl NoStopLimitDeviation {
+    /// Computed minus published no-stop limit.
+    pub fn error_min(&self) -> i32 {
+        self.computed_limit_min as i32 - self.row.limit_min as i32
+    }
+
+    pub fn within_tolerance(&self) -> bool {
+        self.error_min().unsigned_abs() <= self.row.tolerance_min() as u32
+    }
+}
+
+/** Open-circuit air table: descent, bottom, ascent and stops all on air. */
+#[derive(Debug, Clone, Copy)]
+pub struct AirTable<'a> {
+    /// msw per minute
+    pub descent_rate: f32,
+    /// msw per minute, to the first stop and between stops
+    pub ascent_rate: f32,
+    pub last_stop: msw,
+    pub no_stop_limits: &'a [NoStopLimitRow],
+}
+
+impl AirTable<'_> {
+    /// Tissue loading on reaching `depth`, starting saturated on air.
+    fn descent_loading<M: DecoModel<NUM_TISSUES, Pa>, const NUM_TISSUES: usize>(
+        &self,
+        model: &M,
+        depth: msw,
+        surface_pressure: Pa,
+    ) -> TissuesLoading<NUM_TISSUES, Pa> {
+        let mut loading = TissuesLoading::new(surface_pressure, &AIR);
+        let step = Duration::from_secs_f32(DESCENT_STEP.to_f32() / self.descent_rate * 60.0);
+        let mut current = 0.0;
+        while current < depth.to_f32() {
+            let next = (current + DESCENT_STEP.to_f32()).min(depth.to_f32());
+            let mid = msw::new(0.5 * (current + next));
+            let fraction = (next - current) / DESCENT_STEP.to_f32();
+            model.update_model_state(
+                &mut loading,
+                &AIR,
+                ambient_pressure_at_depth(surface_pressure, mid),
+                &step.mul_f32(fraction),
+            );
+            current = next;
+        }
+        loading
+    }
+
+    /// Decompression settings of the table: gradient factors are not applied.
+    pub fn deco_settings(&self, surface_pressure: Pa) -> DecoSettings<'static, Pa> {
+        DecoSettings {
+            gas_density_settings: GasDensitySettings::Ignore,
+            narcosis_settings: NarcosisSettings::Ignore,
+            max_deco_po2: MAX_PO2_DECO.to_pa(),
+            surface_pressure,
+            icd_settings: IcdSettings::Ignore,
+            gf_low: 1.0,
+            gf_high: 1.0,
+            last_deco_stop: self.last_stop,
+            ascent_rate_per_meter: Duration::from_secs_f32(60.0 / self.ascent_rate),
+            gas_switch_policy: GasSwitchPolicy::default(),
+        }
+    }
+
+    /// Longest whole-minute bottom time at `row.depth` for which `model` gives no stops.
+    pub fn no_stop_limit<M: DecoModel<NUM_TISSUES, Pa>, const NUM_TISSUES: usize>(
+        &self,
+        model: &M,
+        row: &NoStopLimitRow,
+    ) -> Result<u16, &'static str> {
+        let surface_pressure = msw::new(0.0).to_pa();
+        let settings = self.deco_settings(surface_pressure);
+        let bottom = ambient_pressure_at_depth(surface_pressure, row.depth);
+        let mut loading = self.descent_loading(model, row.depth, surface_pressure);
+        let descent_min = row.depth.to_f32() / self.descent_rate;
+        let mut bottom_time_min = descent_min.ceil() as u16;
+        model.update_model_state(
+            &mut loading,
+            &AIR,
+            bottom,
+            &Duration::from_secs_f32((bottom_time_min as f32 - descent_min) * 60.0),
+        );
+        while bottom_time_min < MAX_NO_STOP_LIMIT_MIN {
+            model.update_model_state(&mut loading, &AIR, bottom, &Duration::from_secs(60));
+            let schedule: StopSchedule<NUM_VALIDATION_STOPS> =
+                calc_deco_schedule(model, &loading, row.depth, 0, &[AIR], &[true], &settings)?;
+            if schedule.first_stop().is_some() {
+                return Ok(bottom_time_min);
+            }
+            bottom_time_min += 1;
+        }
+        Err("No stop needed within the longest searched bottom time.")
+    }
+
+    /// Deviation of every no-stop limit of the table, in table order.
+    pub fn validate<'m, M: DecoModel<NUM_TISSUES, Pa>, const NUM_TISSUES: usize>(
+        &'m self,
+        model: &'m M,
+    ) -> impl Iterator<Item = Result<NoStopLimitDeviation, &'static str>> + 'm {
+        self.no_stop_limits.iter().map(move |row| {
+            Ok(NoStopLimitDeviation {
+                row: *row,
+                computed_limit_min: self.no_stop_limit(model, row)?,
+            })
+        })
+    }
+}
+
+/**
+* No-decompression limits of the U.S. Navy Diving Manual Rev 6 air tables (Table 9-7),
+* computed with VVal-18M, a modified VVal-18 (NEDU TR 10-09). Rates are 75 fsw/min down
+* and 30 fsw/min up; the last stop is 20 fsw, on the 3 msw grid.
+* Limits shallower than 50 fsw are left out: there the Rev 6 limits are far shorter
+* than VVal-18 gives, whose 240 min compartment never reaches its MPTT at 30 fsw.
+*/
+pub const USN_REV6_AIR: AirTable<'static> = AirTable {
+    descent_rate: fsw::new(75.0).to_msw().to_f32(),
+    ascent_rate: fsw::new(30.0).to_msw().to_f32(),
+    last_stop: msw::new(6.0),
+    no_stop_limits: &USN_REV6_AIR_NO_STOP_LIMITS,
+};
+
+pub const USN_REV6_AIR_NO_STOP_LIMITS: [NoStopLimitRow; 16] = [
+    NoStopLimitRow::new(50.0, 92),
+    NoStopLimitRow::new(55.0, 74),
+    NoStopLimitRow::new(60.0, 63),
+    NoStopLimitRow::new(70.0, 48),
+    NoStopLimitRow::new(80.0, 39),
+    NoStopLimitRow::new(90.0, 33),
+    NoStopLimitRow::new(100.0, 25),
+    NoStopLimitRow::new(110.0, 20),
+    NoStopLimitRow::new(120.0, 15),
+    NoStopLimitRow::new(130.0, 12),
+    NoStopLimitRow::new(140.0, 10),
+    NoStopLimitRow::new(150.0, 8),
+    NoStopLimitRow::new(160.0, 7),
+    NoStopLimitRow::new(170.0, 6),
+    NoStopLimitRow::new(180.0, 6),
+    NoStopLimitRow::new(190.0, 5),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deco_model::{THALMANN_VVAL18, THALMANN_XVAL_HE9_040};
+    use std::println;
+
+    #[test]
+    fn reference_rows_are_consistent() {
+        for row in XVAL_HE9_040_HEO2_1_3_ATA.rows {
+            let sum: u16 = row.stops_min.iter().sum();
+            assert_eq!(sum, row.total_stop_time_min, "{:?}", row);
+        }
+        let row = ReferenceRow::new(45.0, 40, &[3, 4, 24], 31);
+        assert_eq!(row.stop_depth(0, msw::new(6.0)), msw::new(12.0));
+        assert_eq!(row.stop_depth(2, msw::new(6.0)), msw::new(6.0));
+    }
+
+    /// Mean |TST error| of the current model. Lower it when the model gets closer to the tables.
+    const BASELINE_MEAN_ABS_TST_ERROR_MIN: f32 = 9.0;
+
+    /**
+    * Deviation report of the model against the published tables, not a
+    * reproduction: every row must stay within its tolerance.
+    */
+    #[test]
+    fn xval_he9_040_deviation_report() {
+        let table = XVAL_HE9_040_HEO2_1_3_ATA;
+        let mut matching = 0;
+        let mut abs_error_sum = 0;
+        let mut max_abs_error = 0;
+        for deviation in table.validate(&THALMANN_XVAL_HE9_040) {
+            let deviation = deviation.expect("schedule");
+            let error = deviation.total_stop_time_error_min();
+            println!(
+                "{:>5.1} msw {:>3} min: first stop {:?} vs {:?}, TST {:>4} vs {:>4} ({:+}), max stop error {}",
+                deviation.row.depth.to_f32(),
+                deviation.row.bottom_time_min,
+                deviation.computed_first_stop.map(|d| d.to_f32()),
+                deviation.reference_first_stop.map(|d| d.to_f32()),
+                deviation.computed_total_stop_time_min,
+                deviation.row.total_stop_time_min,
+                error,
+                deviation.max_stop_error_min,
+            );
+            assert!(deviation.within_tolerance(), "row out of tolerance: {:?}", deviation);
+            matching += deviation.matches() as usize;
+            abs_error_sum += error.unsigned_abs();
+            max_abs_error = max_abs_error.max(error.unsigned_abs());
+        }
+        let rows = table.rows.len();
+        let mean_abs_error = abs_error_sum as f32 / rows as f32;
+        println!(
+            "{matching}/{rows} rows match, mean |TST error| {mean_abs_error:.1} min, max {max_abs_error} min"
+        );
+        assert!(mean_abs_error <= BASELINE_MEAN_ABS_TST_ERROR_MIN);
+    }
+
+    /// Mean |no-stop limit error| of VVal-18. Lower it when the model gets closer to the table.
+    const BASELINE_MEAN_ABS_NO_STOP_LIMIT_ERROR_MIN: f32 = 3.5;
+
+    /// VVal-18 against the Rev 6 air no-stop limits: every row must stay within its tolerance.
+    #[test]
+    fn vval18_air_no_stop_limit_report() {
+        let table = USN_REV6_AIR;
+        let mut abs_error_sum = 0;
+        for deviation in table.validate(&THALMANN_VVAL18) {
+            let deviation = deviation.expect("no-stop limit");
+            println!(
+                "{:>5.1} msw: no-stop limit {:>3} vs {:>3} min ({:+})",
+                deviation.row.depth.to_f32(),
+                deviation.computed_limit_min,
+                deviation.row.limit_min,
+                deviation.error_min(),
+            );
+            assert!(deviation.within_tolerance(), "row out of tolerance: {:?}", deviation);
+            abs_error_sum += deviation.error_min().unsigned_abs();
+        }
+        let mean_abs_error = abs_error_sum as f32 / table.no_stop_limits.len() as f32;
+        println!("mean |no-stop limit error| {mean_abs_error:.1} min");
+        assert!(mean_abs_error <= BASELINE_MEAN_ABS_NO_STOP_LIMIT_ERROR_MIN);
+    }
+}